@@ -9,7 +9,7 @@ use ckb_merkle_mountain_range::leaf_index_to_mmr_size;
 use ckb_proposal_table::ProposalTable;
 use ckb_shared::block_status::BlockStatus;
 use ckb_shared::Shared;
-use ckb_store::{attach_block_cell, detach_block_cell, ChainStore, StoreTransaction};
+use ckb_store::{attach_block_cell, detach_blocks, ChainStore, StoreTransaction};
 use ckb_systemtime::unix_time_as_millis;
 use ckb_tx_pool::TxPoolController;
 use ckb_types::core::cell::{
@@ -448,11 +448,7 @@ impl ConsumeUnverifiedBlockProcessor {
     }
 
     pub(crate) fn rollback(&self, fork: &ForkChanges, txn: &StoreTransaction) -> Result<(), Error> {
-        for block in fork.detached_blocks().iter().rev() {
-            txn.detach_block(block)?;
-            detach_block_cell(txn, block)?;
-        }
-        Ok(())
+        detach_blocks(txn, fork.detached_blocks())
     }
 
     fn alignment_fork(
@@ -850,10 +846,7 @@ impl ConsumeUnverifiedBlockProcessor {
 
         let target_tip_header = snapshot.get_block_header(target_tip_hash).expect("checked");
         let target_block_ext = snapshot.get_block_ext(target_tip_hash).expect("checked");
-        let target_epoch_ext = snapshot
-            .get_block_epoch_index(target_tip_hash)
-            .and_then(|index| snapshot.get_epoch_ext(&index))
-            .expect("checked");
+        let target_epoch_ext = snapshot.get_block_epoch(target_tip_hash).expect("checked");
         let origin_proposals = snapshot.proposals();
         let mut fork = self.make_fork_for_truncate(&target_tip_header, snapshot.tip_header());
 
@@ -1,18 +1,27 @@
 use std::fs;
 use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
 
 use crate::helper::prompt;
 use base64::Engine;
 use ckb_app_config::{cli, AppConfig, ExitCode, InitArgs};
 use ckb_chain_spec::ChainSpec;
+use ckb_crypto::secp::{Generator, Privkey};
+use ckb_db::ReadOnlyDB;
+use ckb_db_schema::{COLUMN_INDEX, COLUMN_META, META_GENESIS_HASH_KEY};
+use ckb_hash::blake2b_256;
 use ckb_jsonrpc_types::ScriptHashType;
 use ckb_resource::{
     Resource, TemplateContext, AVAILABLE_SPECS, CKB_CONFIG_FILE_NAME, DB_OPTIONS_FILE_NAME,
     MINER_CONFIG_FILE_NAME, SPEC_DEV_FILE_NAME,
 };
-use ckb_types::{prelude::*, H256};
+use ckb_types::{packed, prelude::*, H160, H256};
+use is_terminal::IsTerminal;
 
 const DEFAULT_LOCK_SCRIPT_HASH_TYPE: &str = "type";
+const SPEC_DOWNLOAD_SIZE_LIMIT: u64 = 16 * 1024 * 1024;
+const SPEC_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
 const SECP256K1_BLAKE160_SIGHASH_ALL_ARG_LEN: usize = 20 * 2 + 2; // 42 = 20 x 2 + prefix 0x
 
 pub fn init(args: InitArgs) -> Result<(), ExitCode> {
@@ -31,7 +40,7 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
     }
 
     let exported = Resource::exported_in(&args.root_dir);
-    if !args.force && exported {
+    if !args.force && !args.dry_run && exported {
         eprintln!("Config files already exist; use --force to overwrite.");
 
         if args.interactive {
@@ -45,30 +54,21 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         }
     }
 
-    if args.interactive {
-        let in_block_assembler_code_hash = prompt("code hash: ");
-        let in_args = prompt("args: ");
-        let in_hash_type = prompt("hash_type: ");
-
-        args.block_assembler_code_hash = Some(in_block_assembler_code_hash.trim().to_string());
-
-        args.block_assembler_args = in_args
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>();
-
-        args.block_assembler_hash_type =
-            match serde_plain::from_str::<ScriptHashType>(in_hash_type.trim()).ok() {
-                Some(hash_type) => hash_type,
-                None => {
-                    eprintln!("Invalid block assembler hash type");
-                    return Err(ExitCode::Failure);
-                }
-            };
-
-        let in_message = prompt("message: ");
-        args.block_assembler_message = Some(in_message.trim().to_string());
+    // Block assembler options given on the command line are never prompted for, so the same
+    // flags produce the same config with or without `--interactive`.
+    let has_block_assembler_flags = args.block_assembler_generate
+        || args.block_assembler_code_hash.is_some()
+        || !args.block_assembler_args.is_empty()
+        || !args.block_assembler_profiles.is_empty();
+    if args.interactive && !has_block_assembler_flags {
+        prompt_block_assembler(&mut args)?;
     }
+    let generated_privkey = if args.block_assembler_generate {
+        Some(generate_block_assembler(&mut args)?)
+    } else {
+        None
+    };
+    validate_block_assembler(&args)?;
 
     // Try to find the default secp256k1 from bundled chain spec.
     let default_code_hash_option =
@@ -82,6 +82,14 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
                     .unpack();
                 format!("{hash:#x}")
             });
+    if generated_privkey.is_some() && default_code_hash_option.is_none() {
+        eprintln!(
+            "Couldn't find the default secp256k1 code hash for chain {}, \
+             so --ba-generate cannot be used",
+            args.chain
+        );
+        return Err(ExitCode::Failure);
+    }
 
     let block_assembler_code_hash =
         args.block_assembler_code_hash
@@ -93,39 +101,60 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
             });
 
     let block_assembler = match block_assembler_code_hash {
-        Some(hash) => {
-            if let Some(default_code_hash) = &default_code_hash_option {
-                if ScriptHashType::Type != args.block_assembler_hash_type {
-                    eprintln!(
-                        "WARN: the default lock should use hash type `{}`, you are using `{}`.\n\
-                         It will require `ckb run --ba-advanced` to enable this block assembler",
-                        DEFAULT_LOCK_SCRIPT_HASH_TYPE, args.block_assembler_hash_type
+        _ if !args.block_assembler_profiles.is_empty() => {
+            let active = args
+                .block_assembler_profiles
+                .iter()
+                .position(|profile| profile.active)
+                .unwrap_or(0);
+            let message = args.block_assembler_message.as_deref().unwrap_or("0x");
+            let sections: Vec<_> = args
+                .block_assembler_profiles
+                .iter()
+                .enumerate()
+                .map(|(i, profile)| {
+                    warn_block_assembler(
+                        default_code_hash_option.as_deref(),
+                        &profile.code_hash,
+                        &profile.args,
+                        &profile.hash_type,
                     );
-                } else if *default_code_hash != *hash {
-                    eprintln!(
-                        "WARN: Use the default secp256k1 code hash `{default_code_hash}` rather than `{hash}`.\n\
-                         To enable this block assembler, use `ckb run --ba-advanced`."
+                    let section = format_block_assembler(
+                        &profile.code_hash,
+                        &profile.args,
+                        &profile.hash_type,
+                        message,
                     );
-                } else if args.block_assembler_args.len() != 1
-                    || args.block_assembler_args[0].len() != SECP256K1_BLAKE160_SIGHASH_ALL_ARG_LEN
-                {
-                    eprintln!(
-                        "WARN: The block assembler arg is not a valid secp256k1 pubkey hash.\n\
-                         To enable this block assembler, use `ckb run --ba-advanced`. "
-                    );
-                }
-            }
-            format!(
-                "[block_assembler]\n\
-                 code_hash = \"{}\"\n\
-                 args = \"{}\"\n\
-                 hash_type = \"{}\"\n\
-                 message = \"{}\"",
+                    if i == active {
+                        format!(
+                            "# block assembler profile: {} (active)\n{section}",
+                            profile.name
+                        )
+                    } else {
+                        let commented: Vec<_> =
+                            section.lines().map(|line| format!("# {line}")).collect();
+                        format!(
+                            "# block assembler profile: {}\n{}",
+                            profile.name,
+                            commented.join("\n")
+                        )
+                    }
+                })
+                .collect();
+            sections.join("\n\n")
+        }
+        Some(hash) => {
+            warn_block_assembler(
+                default_code_hash_option.as_deref(),
                 hash,
-                args.block_assembler_args.join("\", \""),
-                args.block_assembler_hash_type,
-                args.block_assembler_message
-                    .unwrap_or_else(|| "0x".to_string()),
+                &args.block_assembler_args,
+                &args.block_assembler_hash_type,
+            );
+            format_block_assembler(
+                hash,
+                &args.block_assembler_args,
+                &args.block_assembler_hash_type,
+                args.block_assembler_message.as_deref().unwrap_or("0x"),
             )
         }
         None => {
@@ -143,16 +172,6 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         }
     };
 
-    println!(
-        "{} CKB directory in {}",
-        if !exported {
-            "Initialized"
-        } else {
-            "Reinitialized"
-        },
-        args.root_dir.display()
-    );
-
     let log_to_file = args.log_to_file.to_string();
     let log_to_stdout = args.log_to_stdout.to_string();
     let mut context = TemplateContext::new(
@@ -167,6 +186,24 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         ],
     );
 
+    if args.dry_run {
+        print_dry_run(&args, &context)?;
+        if let Some(privkey) = &generated_privkey {
+            print_generated_privkey(privkey);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{} CKB directory in {}",
+        if !exported {
+            "Initialized"
+        } else {
+            "Reinitialized"
+        },
+        args.root_dir.display()
+    );
+
     if let Some(spec_file) = args.import_spec {
         context.insert("spec_source", "file");
 
@@ -174,15 +211,19 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         fs::create_dir_all(&specs_dir)?;
         let target_file = specs_dir.join(format!("{}.toml", args.chain));
 
-        if spec_file == "-" {
+        if spec_file.starts_with("http://") || spec_file.starts_with("https://") {
+            println!("Download {} to specs/{}.toml", spec_file, args.chain);
+            let spec_content = fetch_spec(&spec_file)?;
+            println!(
+                "Spec content hash (blake2b-256): {}",
+                packed::CellOutput::calc_data_hash(&spec_content)
+            );
+            fs::write(target_file, spec_content)?;
+        } else if spec_file == "-" {
             println!("Create specs/{}.toml from stdin", args.chain);
             let mut encoded_content = String::new();
             io::stdin().read_to_string(&mut encoded_content)?;
-            let base64_config =
-                base64::engine::GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true);
-            let base64_engine =
-                base64::engine::GeneralPurpose::new(&base64::alphabet::STANDARD, base64_config);
-            let spec_content = base64_engine.encode(encoded_content.trim());
+            let spec_content = base64_engine().encode(encoded_content.trim());
             fs::write(target_file, spec_content)?;
         } else {
             println!("copy {} to specs/{}.toml", spec_file, args.chain);
@@ -204,7 +245,8 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
     println!("Create {DB_OPTIONS_FILE_NAME}");
     Resource::bundled_db_options().export(&context, &args.root_dir)?;
 
-    let genesis_hash = AppConfig::load_for_subcommand(args.root_dir, cli::CMD_INIT)?
+    let app_config = AppConfig::load_for_subcommand(args.root_dir, cli::CMD_INIT)?;
+    let genesis_hash = app_config
         .chain_spec()?
         .build_genesis()
         .map_err(|err| {
@@ -216,5 +258,328 @@ pub fn init(args: InitArgs) -> Result<(), ExitCode> {
         .hash();
     println!("Genesis Hash: {genesis_hash:#x}");
 
+    check_db_compatibility(
+        &app_config.into_ckb()?.db.path,
+        &genesis_hash,
+        args.assume_yes,
+    )?;
+
+    if let Some(privkey) = &generated_privkey {
+        print_generated_privkey(privkey);
+    }
+
     Ok(())
 }
+
+/// Removes a database left in the data directory by another chain.
+///
+/// The database is compared by the genesis hash it was initialized with. Without `--yes` the
+/// removal must be confirmed interactively, and is refused when there is no terminal to ask.
+fn check_db_compatibility(
+    db_path: &Path,
+    genesis_hash: &packed::Byte32,
+    assume_yes: bool,
+) -> Result<(), ExitCode> {
+    let db = match ReadOnlyDB::open_cf(db_path, vec![COLUMN_META, COLUMN_INDEX]) {
+        Ok(Some(db)) => db,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            eprintln!(
+                "Skip checking the database at {}, since it couldn't be opened: {err}",
+                db_path.display()
+            );
+            return Ok(());
+        }
+    };
+    let read = |col, key: &[u8]| {
+        db.get_pinned(col, key).map_err(|err| {
+            eprintln!(
+                "Failed to read the database at {}: {err}",
+                db_path.display()
+            );
+            ExitCode::IO
+        })
+    };
+    // Databases initialized before the genesis hash was recorded only have the main chain index
+    let genesis_number: packed::Uint64 = 0u64.pack();
+    let stored_genesis_hash = match read(COLUMN_META, META_GENESIS_HASH_KEY)? {
+        Some(slice) => Some(slice),
+        None => read(COLUMN_INDEX, genesis_number.as_slice())?,
+    }
+    .and_then(|slice| {
+        packed::Byte32Reader::from_slice(&slice)
+            .ok()
+            .map(|reader| reader.to_entity())
+    });
+    drop(db);
+
+    let stored_genesis_hash = match stored_genesis_hash {
+        Some(hash) if &hash != genesis_hash => hash,
+        _ => return Ok(()),
+    };
+    eprintln!(
+        "The database at {} belongs to another chain, whose genesis hash is {stored_genesis_hash:#x}.",
+        db_path.display()
+    );
+
+    if !assume_yes {
+        if !(io::stdin().is_terminal() && io::stdout().is_terminal()) {
+            eprintln!("Keep the database; use --yes to remove it without interactive prompt.");
+            return Err(ExitCode::Failure);
+        }
+        let input = prompt(&format!("Remove {} now? ", db_path.display()));
+        if !["y", "Y"].contains(&input.trim()) {
+            return Err(ExitCode::Failure);
+        }
+    }
+
+    fs::remove_dir_all(db_path).map_err(|err| {
+        eprintln!("Failed to remove {}: {err}", db_path.display());
+        ExitCode::IO
+    })?;
+    println!("Removed the database at {}", db_path.display());
+    Ok(())
+}
+
+/// Warns about a block assembler lock which requires `ckb run --ba-advanced` to be enabled.
+fn warn_block_assembler(
+    default_code_hash: Option<&str>,
+    code_hash: &str,
+    args: &[String],
+    hash_type: &ScriptHashType,
+) {
+    let default_code_hash = match default_code_hash {
+        Some(default_code_hash) => default_code_hash,
+        None => return,
+    };
+    if &ScriptHashType::Type != hash_type {
+        eprintln!(
+            "WARN: the default lock should use hash type `{DEFAULT_LOCK_SCRIPT_HASH_TYPE}`, you are using `{hash_type}`.\n\
+             It will require `ckb run --ba-advanced` to enable this block assembler"
+        );
+    } else if default_code_hash != code_hash {
+        eprintln!(
+            "WARN: Use the default secp256k1 code hash `{default_code_hash}` rather than `{code_hash}`.\n\
+             To enable this block assembler, use `ckb run --ba-advanced`."
+        );
+    } else if args.len() != 1 || args[0].len() != SECP256K1_BLAKE160_SIGHASH_ALL_ARG_LEN {
+        eprintln!(
+            "WARN: The block assembler arg is not a valid secp256k1 pubkey hash.\n\
+             To enable this block assembler, use `ckb run --ba-advanced`. "
+        );
+    }
+}
+
+/// Formats the `[block_assembler]` section of `ckb.toml`.
+fn format_block_assembler(
+    code_hash: &str,
+    args: &[String],
+    hash_type: &ScriptHashType,
+    message: &str,
+) -> String {
+    format!(
+        "[block_assembler]\n\
+         code_hash = \"{}\"\n\
+         args = \"{}\"\n\
+         hash_type = \"{}\"\n\
+         message = \"{}\"",
+        code_hash,
+        args.join("\", \""),
+        hash_type,
+        message,
+    )
+}
+
+/// Generates a secp256k1 key for the block assembler.
+///
+/// The block assembler args are set to the blake160 of the compressed pubkey, and the generated
+/// private key is returned so it can be shown to the user. It is never written to any file.
+fn generate_block_assembler(args: &mut InitArgs) -> Result<H256, ExitCode> {
+    let secret_key = Generator::random_secret_key();
+    let pubkey = Privkey::from(secret_key).pubkey().map_err(|err| {
+        eprintln!("Couldn't derive the pubkey of the generated key, since {err}");
+        ExitCode::Failure
+    })?;
+    let pubkey_hash =
+        H160::from_slice(&blake2b_256(pubkey.serialize())[..20]).expect("blake160 is 20 bytes");
+    let arg = format!("{pubkey_hash:#x}");
+    if arg.len() != SECP256K1_BLAKE160_SIGHASH_ALL_ARG_LEN {
+        eprintln!("The generated block assembler arg `{arg}` is not a valid secp256k1 pubkey hash");
+        return Err(ExitCode::Failure);
+    }
+
+    args.block_assembler_args = vec![arg];
+    args.block_assembler_hash_type = ScriptHashType::Type;
+    Ok(H256::from_slice(&secret_key[..]).expect("secret key is 32 bytes"))
+}
+
+fn print_generated_privkey(privkey: &H256) {
+    println!(
+        "\n\
+         WARNING: A new block assembler key has been generated. Its private key is printed below \
+         and is NOT saved anywhere.\n\
+         Store it somewhere safe now: anyone who has it can spend the mining rewards, \
+         and the rewards are lost forever if it is lost.\n\
+         \n\
+         Block assembler private key: {privkey:#x}"
+    );
+}
+
+/// Fills the block assembler options from prompts.
+///
+/// Empty answers leave the option unset, as if the flag were omitted.
+fn prompt_block_assembler(args: &mut InitArgs) -> Result<(), ExitCode> {
+    let optional = |input: String| {
+        let input = input.trim();
+        (!input.is_empty()).then(|| input.to_string())
+    };
+
+    args.block_assembler_code_hash = optional(prompt("code hash: "));
+    args.block_assembler_args = prompt("args: ")
+        .split_whitespace()
+        .map(|arg| arg.to_string())
+        .collect();
+
+    let in_hash_type = prompt("hash_type: ");
+    if !in_hash_type.trim().is_empty() {
+        args.block_assembler_hash_type =
+            match serde_plain::from_str::<ScriptHashType>(in_hash_type.trim()).ok() {
+                Some(hash_type) => hash_type,
+                None => {
+                    eprintln!("Invalid block assembler hash type");
+                    return Err(ExitCode::Failure);
+                }
+            };
+    }
+
+    args.block_assembler_message = optional(prompt("message: "));
+    Ok(())
+}
+
+/// Checks the block assembler options are 0x-prefixed hex strings, reporting every bad field at
+/// once.
+fn validate_block_assembler(args: &InitArgs) -> Result<(), ExitCode> {
+    let mut errors = Vec::new();
+    if let Some(code_hash) = &args.block_assembler_code_hash {
+        if let Err(err) = cli::is_h256(code_hash) {
+            errors.push(format!("code_hash `{code_hash}`: {err}"));
+        }
+    }
+    for (i, arg) in args.block_assembler_args.iter().enumerate() {
+        if let Err(err) = cli::is_hex(arg) {
+            errors.push(format!("args[{i}] `{arg}`: {err}"));
+        }
+    }
+    if let Some(message) = &args.block_assembler_message {
+        if let Err(err) = cli::is_hex(message) {
+            errors.push(format!("message `{message}`: {err}"));
+        }
+    }
+    for profile in &args.block_assembler_profiles {
+        if let Err(err) = cli::is_h256(&profile.code_hash) {
+            errors.push(format!(
+                "profile {} code_hash `{}`: {err}",
+                profile.name, profile.code_hash
+            ));
+        }
+        for (i, arg) in profile.args.iter().enumerate() {
+            if let Err(err) = cli::is_hex(arg) {
+                errors.push(format!("profile {} args[{i}] `{arg}`: {err}", profile.name));
+            }
+        }
+    }
+    let active_profiles: Vec<_> = args
+        .block_assembler_profiles
+        .iter()
+        .filter(|profile| profile.active)
+        .map(|profile| profile.name.as_str())
+        .collect();
+    if active_profiles.len() > 1 {
+        errors.push(format!(
+            "only one profile can be active, got {}",
+            active_profiles.join(", ")
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        eprintln!("Invalid block assembler options:");
+        for error in errors {
+            eprintln!("  {error}");
+        }
+        Err(ExitCode::Failure)
+    }
+}
+
+/// Prints the files `init` would create, each after a header naming it, without touching
+/// `root_dir`.
+fn print_dry_run(args: &InitArgs, context: &TemplateContext<'_>) -> Result<(), ExitCode> {
+    let mut files = Vec::new();
+    if args.chain == "dev" {
+        let kvs = args.customize_spec.key_value_pairs();
+        let context_spec =
+            TemplateContext::new("customize", kvs.iter().map(|(k, v)| (*k, v.as_str())));
+        let spec = Resource::bundled(SPEC_DEV_FILE_NAME.to_string()).render(&context_spec)?;
+        files.push((SPEC_DEV_FILE_NAME, spec));
+    }
+    files.push((
+        CKB_CONFIG_FILE_NAME,
+        Resource::bundled_ckb_config().render(context)?,
+    ));
+    files.push((
+        MINER_CONFIG_FILE_NAME,
+        Resource::bundled_miner_config().render(context)?,
+    ));
+    files.push((
+        DB_OPTIONS_FILE_NAME,
+        Resource::bundled_db_options().render(context)?,
+    ));
+
+    for (name, content) in files {
+        println!("# ==> {} <==", args.root_dir.join(name).display());
+        print!("{content}");
+    }
+    Ok(())
+}
+
+fn base64_engine() -> base64::engine::GeneralPurpose {
+    let base64_config =
+        base64::engine::GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true);
+    base64::engine::GeneralPurpose::new(&base64::alphabet::STANDARD, base64_config)
+}
+
+/// Downloads a chain spec, bounded in size and time.
+///
+/// A base64 encoded body is decoded, anything else is kept as is.
+fn fetch_spec(url: &str) -> Result<Vec<u8>, ExitCode> {
+    let fail = |err: &dyn std::fmt::Display| {
+        eprintln!("Failed to download the chain spec from {url}: {err}");
+        ExitCode::Failure
+    };
+    let client = reqwest::blocking::Client::builder()
+        .timeout(SPEC_DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|err| fail(&err))?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| fail(&err))?;
+
+    let mut content = Vec::new();
+    response
+        .take(SPEC_DOWNLOAD_SIZE_LIMIT + 1)
+        .read_to_end(&mut content)
+        .map_err(|err| fail(&err))?;
+    if content.len() as u64 > SPEC_DOWNLOAD_SIZE_LIMIT {
+        return Err(fail(&format!(
+            "the response exceeds {SPEC_DOWNLOAD_SIZE_LIMIT} bytes"
+        )));
+    }
+
+    let decoded = std::str::from_utf8(&content)
+        .ok()
+        .and_then(|text| base64_engine().decode(text.trim()).ok());
+    Ok(decoded.unwrap_or(content))
+}
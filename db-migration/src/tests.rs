@@ -4,9 +4,12 @@ use ckb_db::RocksDB;
 use ckb_db_schema::MIGRATION_VERSION_KEY;
 use ckb_error::Error;
 use indicatif::ProgressBar;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
 
-use crate::{DefaultMigration, Migration, Migrations};
+use crate::{internal_error, DefaultMigration, Migration, Migrations};
 
 #[test]
 fn test_default_migration() {
@@ -126,6 +129,93 @@ fn test_customized_migration() {
     }
 }
 
+#[test]
+fn test_migration_idempotent_and_resumable() {
+    struct CountingMigration {
+        version: &'static str,
+        runs: Arc<AtomicUsize>,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl Migration for CountingMigration {
+        fn migrate(
+            &self,
+            db: RocksDB,
+            _pb: Arc<dyn Fn(u64) -> ProgressBar + Send + Sync>,
+        ) -> Result<RocksDB, Error> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(internal_error(format!("{} interrupted", self.version)));
+            }
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(db)
+        }
+
+        fn version(&self) -> &str {
+            self.version
+        }
+    }
+
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("test_migration_idempotent_and_resumable")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        ..Default::default()
+    };
+    let first_runs = Arc::new(AtomicUsize::new(0));
+    let second_runs = Arc::new(AtomicUsize::new(0));
+    let second_fail = Arc::new(AtomicBool::new(true));
+    let migrations = {
+        let mut migrations = Migrations::default();
+        migrations.add_migration(Arc::new(DefaultMigration::new("20191116225943")));
+        migrations.add_migration(Arc::new(CountingMigration {
+            version: "20191127101121",
+            runs: Arc::clone(&first_runs),
+            fail: Arc::new(AtomicBool::new(false)),
+        }));
+        migrations.add_migration(Arc::new(CountingMigration {
+            version: "20191201091330",
+            runs: Arc::clone(&second_runs),
+            fail: Arc::clone(&second_fail),
+        }));
+        migrations
+    };
+    let version = |db: &RocksDB| {
+        db.get_pinned_default(MIGRATION_VERSION_KEY)
+            .unwrap()
+            .unwrap()
+            .to_vec()
+    };
+
+    {
+        let db = RocksDB::open(&config, 1);
+        db.put_default(MIGRATION_VERSION_KEY, "20191116225943")
+            .unwrap();
+        // the second migration is interrupted, the version stays at the first one
+        assert!(migrations.migrate(db, false).is_err());
+        let db = RocksDB::open(&config, 1);
+        assert_eq!(b"20191127101121".to_vec(), version(&db));
+    }
+    assert_eq!(first_runs.load(Ordering::SeqCst), 1);
+    assert_eq!(second_runs.load(Ordering::SeqCst), 0);
+
+    // resuming only runs the pending migration
+    second_fail.store(false, Ordering::SeqCst);
+    let db = migrations
+        .migrate(RocksDB::open(&config, 1), false)
+        .unwrap();
+    assert_eq!(b"20191201091330".to_vec(), version(&db));
+    assert_eq!(first_runs.load(Ordering::SeqCst), 1);
+    assert_eq!(second_runs.load(Ordering::SeqCst), 1);
+
+    // running again is a no-op
+    let db = migrations.migrate(db, false).unwrap();
+    assert_eq!(b"20191201091330".to_vec(), version(&db));
+    assert_eq!(first_runs.load(Ordering::SeqCst), 1);
+    assert_eq!(second_runs.load(Ordering::SeqCst), 1);
+}
+
 #[test]
 fn test_background_migration() {
     use ckb_stop_handler::broadcast_exit_signals;
@@ -45,12 +45,37 @@ pub const COLUMN_BLOCK_FILTER: Col = "17";
 /// Column store filter data hash for client-side filtering
 pub const COLUMN_BLOCK_FILTER_HASH: Col = "18";
 
+/// All column families, in numeric order
+pub const ALL_COLUMNS: [Col; COLUMNS as usize] = [
+    COLUMN_INDEX,
+    COLUMN_BLOCK_HEADER,
+    COLUMN_BLOCK_BODY,
+    COLUMN_BLOCK_UNCLE,
+    COLUMN_META,
+    COLUMN_TRANSACTION_INFO,
+    COLUMN_BLOCK_EXT,
+    COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_EPOCH,
+    COLUMN_EPOCH,
+    COLUMN_CELL,
+    COLUMN_UNCLES,
+    COLUMN_CELL_DATA,
+    COLUMN_NUMBER_HASH,
+    COLUMN_CELL_DATA_HASH,
+    COLUMN_BLOCK_EXTENSION,
+    COLUMN_CHAIN_ROOT_MMR,
+    COLUMN_BLOCK_FILTER,
+    COLUMN_BLOCK_FILTER_HASH,
+];
+
 /// META_TIP_HEADER_KEY tracks the latest known best block header
 pub const META_TIP_HEADER_KEY: &[u8] = b"TIP_HEADER";
 /// META_CURRENT_EPOCH_KEY tracks the latest known epoch
 pub const META_CURRENT_EPOCH_KEY: &[u8] = b"CURRENT_EPOCH";
 /// META_FILTER_DATA_KEY tracks the latest built filter data block hash
 pub const META_LATEST_BUILT_FILTER_DATA_KEY: &[u8] = b"LATEST_BUILT_FILTER_DATA";
+/// META_GENESIS_HASH_KEY tracks the hash of the genesis block the database was initialized with
+pub const META_GENESIS_HASH_KEY: &[u8] = b"GENESIS_HASH";
 
 /// CHAIN_SPEC_HASH_KEY tracks the hash of chain spec which created current database
 pub const CHAIN_SPEC_HASH_KEY: &[u8] = b"chain-spec-hash";
@@ -6,17 +6,21 @@ use crate::{internal_error, Result};
 use ckb_app_config::DBConfig;
 use ckb_db_schema::Col;
 use ckb_logger::info;
+use libc::{c_char, c_void, size_t};
 use rocksdb::ops::{
-    CompactRangeCF, CreateCF, DropCF, GetColumnFamilys, GetPinned, GetPinnedCF, IterateCF, OpenCF,
-    Put, SetOptions, WriteOps,
+    CompactRangeCF, CreateCF, DropCF, GetColumnFamilys, GetPinned, GetPinnedCF, GetPropertyCF,
+    IterateCF, OpenCF, Put, SetOptions, WriteOps,
 };
 use rocksdb::{
-    ffi, BlockBasedIndexType, BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor,
-    DBPinnableSlice, FullOptions, IteratorMode, OptimisticTransactionDB,
-    OptimisticTransactionOptions, Options, SliceTransform, WriteBatch, WriteOptions,
+    ffi, ffi_util, BlockBasedIndexType, BlockBasedOptions, Cache, ColumnFamily,
+    ColumnFamilyDescriptor, DBPinnableSlice, FullOptions, Handle, IteratorMode,
+    OptimisticTransactionDB, OptimisticTransactionOptions, Options, ReadOptions, SliceTransform,
+    WriteBatch, WriteOptions,
 };
+use std::ops::ControlFlow;
 use std::path::Path;
 use std::sync::Arc;
+use std::{ptr, slice};
 
 /// RocksDB wrapper base on OptimisticTransactionDB
 ///
@@ -31,6 +35,17 @@ const DEFAULT_CACHE_ENTRY_CHARGE_SIZE: usize = 4096;
 
 impl RocksDB {
     pub(crate) fn open_with_check(config: &DBConfig, columns: u32) -> Result<Self> {
+        if config.write_buffer_size == Some(0) {
+            return Err(internal_error(
+                "invalid db config: write_buffer_size must be greater than 0",
+            ));
+        }
+        if matches!(config.max_background_jobs, Some(jobs) if jobs <= 0) {
+            return Err(internal_error(
+                "invalid db config: max_background_jobs must be greater than 0",
+            ));
+        }
+
         let cf_names: Vec<_> = (0..columns).map(|c| c.to_string()).collect();
         let mut cache = None;
 
@@ -91,6 +106,13 @@ impl RocksDB {
                     .set_prefix_extractor(SliceTransform::create_fixed_prefix(32));
             }
             cf.options.set_block_based_table_factory(&block_opts);
+            if let Some(size) = config.write_buffer_size {
+                cf.options.set_write_buffer_size(size);
+            }
+        }
+
+        if let Some(jobs) = config.max_background_jobs {
+            opts.set_max_background_jobs(jobs);
         }
 
         opts.create_if_missing(true);
@@ -173,6 +195,15 @@ impl RocksDB {
         self.inner.get_pinned_cf(cf, key).map_err(internal_error)
     }
 
+    /// Return the values associated with `keys` in the given column, in the order of `keys`.
+    ///
+    /// The keys are read in a single `MultiGet`, which batches the lookups of the memtables and
+    /// the SST files instead of paying for a full `get` per key.
+    pub fn multi_get(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let cf = cf_handle(&self.inner, col)?;
+        multi_get_cf(&self.inner, cf, keys, &ReadOptions::default())
+    }
+
     /// Return the value associated with a key using RocksDB's PinnableSlice from the default column
     /// so as to avoid unnecessary memory copy.
     pub fn get_pinned_default(&self, key: &[u8]) -> Result<Option<DBPinnableSlice>> {
@@ -234,6 +265,47 @@ impl RocksDB {
         Ok((count, next_key))
     }
 
+    /// Traverse database column with the given callback function, until it returns
+    /// `ControlFlow::Break`.
+    ///
+    /// Unlike `traverse`, the callback decides when to stop, so a search does not have to scan the
+    /// rest of the column or stop by returning an error. Returns the count of visited entries and
+    /// the key of the first entry not visited, which is empty if the column is exhausted.
+    pub fn traverse_until<F>(
+        &self,
+        col: Col,
+        callback: &mut F,
+        mode: IteratorMode,
+    ) -> Result<(usize, Vec<u8>)>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<ControlFlow<()>>,
+    {
+        let mut count: usize = 0;
+        let cf = cf_handle(&self.inner, col)?;
+        let mut iter = self
+            .inner
+            .full_iterator_cf(cf, mode)
+            .map_err(internal_error)?;
+        for (key, val) in iter.by_ref() {
+            count += 1;
+            if callback(&key, &val)?.is_break() {
+                break;
+            }
+        }
+        let next_key = iter.next().map(|(key, _)| key.to_vec()).unwrap_or_default();
+        Ok((count, next_key))
+    }
+
+    /// Returns an integer property of the column family, e.g. "rocksdb.estimate-num-keys".
+    ///
+    /// Returns `None` when the property is unknown.
+    pub fn property_int_value_cf(&self, col: Col, name: &str) -> Result<Option<u64>> {
+        let cf = cf_handle(&self.inner, col)?;
+        self.inner
+            .property_int_value_cf(cf, name)
+            .map_err(internal_error)
+    }
+
     /// Set a snapshot at start of transaction by setting set_snapshot=true
     pub fn transaction(&self) -> RocksDBTransaction {
         let write_options = WriteOptions::default();
@@ -336,3 +408,62 @@ pub(crate) fn cf_handle(db: &OptimisticTransactionDB, col: Col) -> Result<&Colum
     db.cf_handle(col)
         .ok_or_else(|| internal_error(format!("column {col} not found")))
 }
+
+/// Reads `keys` of the column family `cf` in a single `MultiGet` of the base db, with `readopts`
+pub(crate) fn multi_get_cf(
+    db: &OptimisticTransactionDB,
+    cf: &ColumnFamily,
+    keys: &[&[u8]],
+    readopts: &ReadOptions,
+) -> Result<Vec<Option<Vec<u8>>>> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    let cfs: Vec<*const ffi::rocksdb_column_family_handle_t> =
+        vec![cf.handle() as *const _; keys.len()];
+    let keys_list: Vec<*const c_char> = keys
+        .iter()
+        .map(|key| key.as_ptr() as *const c_char)
+        .collect();
+    let keys_list_sizes: Vec<size_t> = keys.iter().map(|key| key.len() as size_t).collect();
+    let mut values_list: Vec<*mut c_char> = vec![ptr::null_mut(); keys.len()];
+    let mut values_list_sizes: Vec<size_t> = vec![0; keys.len()];
+    let mut errs: Vec<*mut c_char> = vec![ptr::null_mut(); keys.len()];
+    unsafe {
+        ffi::rocksdb_multi_get_cf(
+            db.base_db_ptr(),
+            readopts.handle(),
+            cfs.as_ptr(),
+            keys.len(),
+            keys_list.as_ptr(),
+            keys_list_sizes.as_ptr(),
+            values_list.as_mut_ptr(),
+            values_list_sizes.as_mut_ptr(),
+            errs.as_mut_ptr(),
+        );
+    }
+
+    // every value and error is allocated by rocksdb, all of them are freed before returning
+    let mut values = Vec::with_capacity(keys.len());
+    let mut error = None;
+    for ((value, size), err) in values_list.into_iter().zip(values_list_sizes).zip(errs) {
+        unsafe {
+            if !err.is_null() {
+                let message = ffi_util::error_message(err);
+                error.get_or_insert(message);
+            }
+            if value.is_null() {
+                values.push(None);
+            } else {
+                values.push(Some(
+                    slice::from_raw_parts(value as *const u8, size).to_vec(),
+                ));
+                ffi::rocksdb_free(value as *mut c_void);
+            }
+        }
+    }
+    match error {
+        Some(message) => Err(internal_error(message)),
+        None => Ok(values),
+    }
+}
@@ -1,5 +1,5 @@
 //! RocksDB snapshot wrapper
-use crate::db::cf_handle;
+use crate::db::{cf_handle, multi_get_cf};
 use crate::{internal_error, Result};
 use ckb_db_schema::Col;
 use libc::{self, c_char, size_t};
@@ -40,6 +40,15 @@ impl RocksDBSnapshot {
         self.get_pinned_cf_full(Some(cf), key, None)
             .map_err(internal_error)
     }
+
+    /// Return the values associated with `keys` in the given column, in the order of `keys`,
+    /// read in a single `MultiGet`, see `RocksDB::multi_get`.
+    pub fn multi_get(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let cf = cf_handle(&self.db, col)?;
+        let mut ro = ReadOptions::default();
+        ro.set_snapshot(self);
+        multi_get_cf(&self.db, cf, keys, &ro)
+    }
 }
 
 impl Read for RocksDBSnapshot {}
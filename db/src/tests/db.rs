@@ -1,7 +1,8 @@
 use ckb_app_config::DBConfig;
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 
-use crate::{Result, RocksDB};
+use crate::{Direction, IteratorMode, Result, RocksDB};
 
 fn setup_db(prefix: &str, columns: u32) -> RocksDB {
     setup_db_with_check(prefix, columns).unwrap()
@@ -68,6 +69,56 @@ fn test_panic_on_invalid_rocksdb_options() {
     RocksDB::open(&config, 2); // panic
 }
 
+#[test]
+fn test_set_rocksdb_tuning_knobs() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("test_set_rocksdb_tuning_knobs")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        write_buffer_size: Some(128 << 20),
+        max_background_jobs: Some(6),
+        ..Default::default()
+    };
+    let db = RocksDB::open(&config, 2);
+    db.put_default("key", "value").unwrap();
+
+    // the options in effect are persisted in the latest OPTIONS file of the db
+    let options_file = std::fs::read_dir(&tmp_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("OPTIONS-") && !name.ends_with(".dbtmp"))
+        .max()
+        .unwrap();
+    let options = std::fs::read_to_string(tmp_dir.path().join(options_file)).unwrap();
+    assert!(options.contains("max_background_jobs=6"));
+    assert!(options.contains(&format!("write_buffer_size={}", 128 << 20)));
+}
+
+#[test]
+fn test_reject_invalid_rocksdb_tuning_knobs() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("test_reject_invalid_rocksdb_tuning_knobs")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        write_buffer_size: Some(0),
+        ..Default::default()
+    };
+    let err = RocksDB::open_with_check(&config, 2).err().unwrap();
+    assert!(err.to_string().contains("write_buffer_size"));
+
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        max_background_jobs: Some(0),
+        ..Default::default()
+    };
+    let err = RocksDB::open_with_check(&config, 2).err().unwrap();
+    assert!(err.to_string().contains("max_background_jobs"));
+}
+
 #[test]
 fn write_and_read() {
     let db = setup_db("write_and_read", 2);
@@ -97,6 +148,58 @@ fn write_and_read() {
     assert_eq!(r.get(&vec![1, 1]), Some(&vec![1, 1, 1]));
 }
 
+#[test]
+fn traverse_until() {
+    let db = setup_db("traverse_until", 1);
+
+    let txn = db.transaction();
+    for i in 0..10u8 {
+        txn.put("0", &[i], &[i * 2]).unwrap();
+    }
+    txn.commit().unwrap();
+
+    // stops at the searched key
+    let mut visited = Vec::new();
+    let mut callback = |k: &[u8], v: &[u8]| -> Result<ControlFlow<()>> {
+        visited.push(k.to_vec());
+        if v == [8] {
+            Ok(ControlFlow::Break(()))
+        } else {
+            Ok(ControlFlow::Continue(()))
+        }
+    };
+    let (count, next_key) = db
+        .traverse_until("0", &mut callback, IteratorMode::Start)
+        .unwrap();
+    assert_eq!(count, 5);
+    assert_eq!(next_key, vec![5]);
+    assert_eq!(visited, (0..5u8).map(|i| vec![i]).collect::<Vec<_>>());
+
+    // resumes from the next key and runs to the end
+    let mut visited = Vec::new();
+    let mut callback = |k: &[u8], _v: &[u8]| -> Result<ControlFlow<()>> {
+        visited.push(k.to_vec());
+        Ok(ControlFlow::Continue(()))
+    };
+    let (count, next_key) = db
+        .traverse_until(
+            "0",
+            &mut callback,
+            IteratorMode::From(&next_key, Direction::Forward),
+        )
+        .unwrap();
+    assert_eq!(count, 5);
+    assert!(next_key.is_empty());
+    assert_eq!(visited, (5..10u8).map(|i| vec![i]).collect::<Vec<_>>());
+
+    // a real error is not a stop
+    let mut callback =
+        |_k: &[u8], _v: &[u8]| -> Result<ControlFlow<()>> { Err(crate::internal_error("failed")) };
+    assert!(db
+        .traverse_until("0", &mut callback, IteratorMode::Start)
+        .is_err());
+}
+
 #[test]
 fn snapshot_isolation() {
     let db = setup_db("snapshot_isolation", 2);
@@ -119,6 +222,33 @@ fn snapshot_isolation() {
     );
 }
 
+#[test]
+fn multi_get() {
+    let db = setup_db("multi_get", 2);
+    let txn = db.transaction();
+    txn.put("0", &[0, 0], &[5, 4, 3, 2]).unwrap();
+    txn.put("0", &[0, 1], &[1]).unwrap();
+    txn.put("1", &[0, 0], &[9]).unwrap();
+    txn.commit().unwrap();
+    let snapshot = db.get_snapshot();
+    let txn = db.transaction();
+    txn.put("0", &[0, 2], &[2]).unwrap();
+    txn.commit().unwrap();
+
+    let keys: [&[u8]; 4] = [&[0, 1], &[1, 1], &[0, 0], &[0, 2]];
+    assert_eq!(
+        db.multi_get("0", &keys).unwrap(),
+        vec![Some(vec![1]), None, Some(vec![5, 4, 3, 2]), Some(vec![2])]
+    );
+    // reads the snapshot view of the column
+    assert_eq!(
+        snapshot.multi_get("0", &keys).unwrap(),
+        vec![Some(vec![1]), None, Some(vec![5, 4, 3, 2]), None]
+    );
+    assert!(db.multi_get("0", &[]).unwrap().is_empty());
+    assert!(db.multi_get("2", &keys).is_err());
+}
+
 #[test]
 fn write_and_partial_read() {
     let db = setup_db("write_and_partial_read", 2);
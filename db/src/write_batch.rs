@@ -69,4 +69,12 @@ impl RocksDBWriteBatch {
     pub fn clear(&mut self) -> Result<()> {
         self.inner.clear().map_err(internal_error)
     }
+
+    /// Write the batch into the db, then clear it so it can be reused.
+    ///
+    /// The batch is kept untouched if the write fails.
+    pub fn commit_and_clear(&mut self) -> Result<()> {
+        self.db.write(&self.inner).map_err(internal_error)?;
+        self.clear()
+    }
 }
@@ -44,6 +44,8 @@ fn add_ban_addr(data: &mut BufManager, peer_store: &mut PeerStore) {
             ban_until: data.get(),
             created_at: data.get(),
             ban_reason: String::new(),
+            score: None,
+            reason: Default::default(),
         };
         peer_store.mut_ban_list().ban(ban_addr);
     }
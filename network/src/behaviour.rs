@@ -5,7 +5,7 @@ use crate::Score;
 /// report peer behaviour will affects peer's score
 ///
 /// Currently this feature is disabled, maybe someday we will add it back or totally remove it.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Behaviour {
     #[cfg(test)]
     TestGood,
@@ -51,6 +51,8 @@ pub enum PeerStoreError {
     EvictionFailed,
     /// File data is not json format
     Serde(serde_json::Error),
+    /// File is dumped by a newer version
+    UnsupportedVersion(u64),
 }
 
 /// Peer error
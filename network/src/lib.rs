@@ -28,6 +28,7 @@ pub use crate::{
         DefaultExitHandler, EventHandler, ExitHandler, NetworkController, NetworkService,
         NetworkState,
     },
+    network_group::Group,
     peer::{Peer, PeerIdentifyInfo},
     peer_registry::PeerRegistry,
     peer_store::Score,
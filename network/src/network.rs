@@ -168,9 +168,14 @@ impl NetworkState {
                 .filter(|peer| !peer.is_whitelist)
                 .map(|peer| peer.connected_addr.clone())
         }) {
-            trace!("Report {:?} because {:?}", addr, behaviour);
-            let report_result = self.peer_store.lock().report(&addr, behaviour);
-            if report_result.is_banned() {
+            let report = self.peer_store.lock().report(&addr, behaviour);
+            trace!(
+                "Report {:?} because {:?}, score: {:?}",
+                addr,
+                report.behaviour,
+                report.score
+            );
+            if report.is_banned() {
                 if let Err(err) = disconnect_with_message(p2p_control, session_id, "banned") {
                     debug!("Disconnect failed {:?}, error: {:?}", session_id, err);
                 }
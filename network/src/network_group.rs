@@ -1,11 +1,16 @@
 use crate::{multiaddr::Multiaddr, multiaddr_to_socketaddr};
 use std::net::IpAddr;
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+/// Network group of an address, peers in the same group are likely run by the same operator
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
 pub enum Group {
+    /// The address has no ip
     None,
+    /// Loopback addresses
     LocalNetwork,
+    /// The /16 of an IPv4 address, IPv4-mapped IPv6 addresses included
     IP4([u8; 2]),
+    /// The /32 of an IPv6 address
     IP6([u8; 4]),
 }
 
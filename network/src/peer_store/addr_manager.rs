@@ -1,36 +1,80 @@
 //! Address manager
-use crate::peer_store::types::AddrInfo;
+use crate::{
+    peer_store::{system_clock, types::AddrInfo, Clock, PeerStoreConfig, TransportKind},
+    PeerId,
+};
 use p2p::{multiaddr::Multiaddr, utils::multiaddr_to_socketaddr};
 use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 /// Address manager
-#[derive(Default)]
 pub struct AddrManager {
     next_id: u64,
     addr_to_id: HashMap<SocketAddr, u64>,
     id_to_info: HashMap<u64, AddrInfo>,
     random_ids: Vec<u64>,
+    // ids of the addresses each source contributed, ascending ids are in insertion order
+    source_to_ids: HashMap<PeerId, BTreeSet<u64>>,
+    id_to_source: HashMap<u64, PeerId>,
+    clock: Arc<dyn Clock>,
+    config: PeerStoreConfig,
+}
+
+impl Default for AddrManager {
+    fn default() -> Self {
+        AddrManager {
+            next_id: 0,
+            addr_to_id: HashMap::default(),
+            id_to_info: HashMap::default(),
+            random_ids: Vec::new(),
+            source_to_ids: HashMap::default(),
+            id_to_source: HashMap::default(),
+            clock: system_clock(),
+            config: PeerStoreConfig::default(),
+        }
+    }
 }
 
 impl AddrManager {
+    /// Replace the clock used to check whether addresses are connectable
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Replace the configuration used to check whether addresses are connectable
+    pub fn set_config(&mut self, config: PeerStoreConfig) {
+        self.config = config;
+    }
+
     /// Add an address information to address manager
+    ///
+    /// A new address without `first_seen_at_ms` is stamped with its last connected time if it
+    /// was ever connected, otherwise with the current time. A known address keeps the first
+    /// seen time it was stored with.
     pub fn add(&mut self, mut addr_info: AddrInfo) {
         if let Some(key) = multiaddr_to_socketaddr(&addr_info.addr) {
             if let Some(&id) = self.addr_to_id.get(&key) {
-                let (exist_last_connected_at_ms, random_id_pos) = {
-                    let info = self.id_to_info.get(&id).expect("must exists");
-                    (info.last_connected_at_ms, info.random_id_pos)
-                };
+                let exist = self.id_to_info.get(&id).expect("must exists");
                 // Get time earlier than record time, return directly
-                if addr_info.last_connected_at_ms >= exist_last_connected_at_ms {
-                    addr_info.random_id_pos = random_id_pos;
+                if addr_info.last_connected_at_ms >= exist.last_connected_at_ms {
+                    addr_info.random_id_pos = exist.random_id_pos;
+                    // traffic counters are accumulated locally, keep them
+                    addr_info.bytes_sent = exist.bytes_sent;
+                    addr_info.bytes_received = exist.bytes_received;
+                    addr_info.first_seen_at_ms = exist.first_seen_at_ms;
                     self.id_to_info.insert(id, addr_info);
                 }
                 return;
             }
 
+            if addr_info.first_seen_at_ms == 0 {
+                addr_info.first_seen_at_ms = match addr_info.last_connected_at_ms {
+                    0 => self.clock.now_ms(),
+                    last_connected_at_ms => last_connected_at_ms,
+                };
+            }
             let id = self.next_id;
             self.addr_to_id.insert(key, id);
             addr_info.random_id_pos = self.random_ids.len();
@@ -40,15 +84,143 @@ impl AddrManager {
         }
     }
 
+    /// Add an address information learned from the peer `source`
+    ///
+    /// A source may contribute at most `addr_max_per_source` never connected addresses, the
+    /// oldest one it contributed is evicted to make room for a new one. Addresses already known
+    /// keep their original source, and addresses connected successfully no longer count
+    /// against the quota of their source.
+    pub fn add_addr_from(&mut self, addr_info: AddrInfo, source: PeerId) {
+        let key = match multiaddr_to_socketaddr(&addr_info.addr) {
+            Some(key) => key,
+            None => return,
+        };
+        if self.addr_to_id.contains_key(&key) {
+            self.add(addr_info);
+            return;
+        }
+
+        self.release_connected(&source);
+        while self.source_addr_count(&source) >= self.config.addr_max_per_source {
+            let oldest = match self.source_to_ids.get(&source).and_then(BTreeSet::first) {
+                Some(&id) => id,
+                // a zero quota accepts nothing
+                None => return,
+            };
+            let addr = self.id_to_info[&oldest].addr.clone();
+            self.remove(&addr);
+        }
+
+        let id = self.next_id;
+        self.add(addr_info);
+        self.source_to_ids
+            .entry(source.clone())
+            .or_default()
+            .insert(id);
+        self.id_to_source.insert(id, source);
+    }
+
+    /// The count of never connected addresses contributed by the peer `source`
+    pub fn source_addr_count(&self, source: &PeerId) -> usize {
+        self.source_to_ids.get(source).map_or(0, |ids| {
+            ids.iter()
+                .filter(|id| self.id_to_info[id].last_connected_at_ms == 0)
+                .count()
+        })
+    }
+
+    /// Stop tracking the addresses of `source` which have been connected
+    fn release_connected(&mut self, source: &PeerId) {
+        if let Some(ids) = self.source_to_ids.get_mut(source) {
+            let id_to_info = &self.id_to_info;
+            let id_to_source = &mut self.id_to_source;
+            ids.retain(|id| {
+                let connected = id_to_info[id].last_connected_at_ms > 0;
+                if connected {
+                    id_to_source.remove(id);
+                }
+                !connected
+            });
+        }
+    }
+
+    /// Drop the stale addresses and rebuild the indices from the remaining ones
+    ///
+    /// Stale addresses are the ones `AddrInfo::is_stale` reports, stale addresses for which
+    /// `keep` returns true survive. Ids are
+    /// reassigned in their original order and the indices are shrunk to fit, so a long
+    /// running manager does not hold on to the capacity of churned addresses. Returns the
+    /// number of removed addresses.
+    pub fn compact<F>(&mut self, keep: F) -> usize
+    where
+        F: Fn(&AddrInfo) -> bool,
+    {
+        let now_ms = self.clock.now_ms();
+        let mut old_ids: Vec<u64> = self.id_to_info.keys().copied().collect();
+        old_ids.sort_unstable();
+
+        let mut addr_to_id = HashMap::with_capacity(old_ids.len());
+        let mut id_to_info = HashMap::with_capacity(old_ids.len());
+        let mut random_ids = Vec::with_capacity(old_ids.len());
+        let mut source_to_ids: HashMap<PeerId, BTreeSet<u64>> = HashMap::default();
+        let mut id_to_source = HashMap::default();
+        let mut removed = 0;
+        for old_id in old_ids {
+            let mut addr_info = self.id_to_info.remove(&old_id).expect("exists");
+            let source = self.id_to_source.remove(&old_id);
+            if addr_info.is_stale(now_ms, &self.config) && !keep(&addr_info) {
+                removed += 1;
+                continue;
+            }
+
+            let id = random_ids.len() as u64;
+            let key = multiaddr_to_socketaddr(&addr_info.addr).expect("stored by socket addr");
+            addr_to_id.insert(key, id);
+            addr_info.random_id_pos = random_ids.len();
+            id_to_info.insert(id, addr_info);
+            random_ids.push(id);
+            if let Some(source) = source {
+                source_to_ids.entry(source.clone()).or_default().insert(id);
+                id_to_source.insert(id, source);
+            }
+        }
+        addr_to_id.shrink_to_fit();
+        id_to_info.shrink_to_fit();
+        random_ids.shrink_to_fit();
+        source_to_ids.shrink_to_fit();
+        id_to_source.shrink_to_fit();
+
+        self.next_id = random_ids.len() as u64;
+        self.addr_to_id = addr_to_id;
+        self.id_to_info = id_to_info;
+        self.random_ids = random_ids;
+        self.source_to_ids = source_to_ids;
+        self.id_to_source = id_to_source;
+        removed
+    }
+
     /// Randomly return addrs that worth to try or connect.
     pub fn fetch_random<F>(&mut self, count: usize, filter: F) -> Vec<AddrInfo>
     where
         F: Fn(&AddrInfo) -> bool,
+    {
+        self.fetch_random_with_rng(count, filter, &mut rand::thread_rng())
+    }
+
+    /// Randomly return addrs that worth to try or connect, using the given rng.
+    pub fn fetch_random_with_rng<F, R>(
+        &mut self,
+        count: usize,
+        filter: F,
+        rng: &mut R,
+    ) -> Vec<AddrInfo>
+    where
+        F: Fn(&AddrInfo) -> bool,
+        R: Rng,
     {
         let mut duplicate_ips = HashSet::new();
         let mut addr_infos = Vec::with_capacity(count);
-        let mut rng = rand::thread_rng();
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         for i in 0..self.random_ids.len() {
             // reuse the for loop to shuffle random ids
             // https://en.wikipedia.org/wiki/Fisher%E2%80%93Yates_shuffle
@@ -62,7 +234,7 @@ impl AddrManager {
                 // TODO remove this after fix the network tests.
                 let is_test_ip = ip.is_unspecified() || ip.is_loopback();
                 if (is_test_ip || is_unique_ip)
-                    && addr_info.is_connectable(now_ms)
+                    && addr_info.is_connectable(now_ms, &self.config)
                     && filter(&addr_info)
                 {
                     duplicate_ips.insert(ip);
@@ -76,6 +248,53 @@ impl AddrManager {
         addr_infos
     }
 
+    /// Randomly return addrs that worth to try or connect, the probability of an addr being
+    /// picked is proportional to its weight. Addrs weighted zero are never returned.
+    pub fn fetch_weighted_random<F, W, R>(
+        &self,
+        count: usize,
+        filter: F,
+        weight: W,
+        rng: &mut R,
+    ) -> Vec<AddrInfo>
+    where
+        F: Fn(&AddrInfo) -> bool,
+        W: Fn(&AddrInfo) -> u64,
+        R: Rng,
+    {
+        let now_ms = self.clock.now_ms();
+        // weighted sampling without replacement, pick the addrs with the largest `u^(1/w)`
+        // https://en.wikipedia.org/wiki/Reservoir_sampling#Algorithm_A-Res
+        let mut candidates: Vec<(f64, &AddrInfo)> = self
+            .random_ids
+            .iter()
+            .map(|id| &self.id_to_info[id])
+            .filter(|addr_info| addr_info.is_connectable(now_ms, &self.config) && filter(addr_info))
+            .filter_map(|addr_info| match weight(addr_info) {
+                0 => None,
+                w => Some((rng.gen::<f64>().powf(1.0 / w as f64), addr_info)),
+            })
+            .collect();
+        candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        let mut duplicate_ips = HashSet::new();
+        let mut addr_infos = Vec::with_capacity(count);
+        for (_, addr_info) in candidates {
+            if addr_infos.len() == count {
+                break;
+            }
+            if let Some(socket_addr) = multiaddr_to_socketaddr(&addr_info.addr) {
+                let ip = socket_addr.ip();
+                // A trick to make our tests work
+                let is_test_ip = ip.is_unspecified() || ip.is_loopback();
+                if is_test_ip || duplicate_ips.insert(ip) {
+                    addr_infos.push(addr_info.to_owned());
+                }
+            }
+        }
+        addr_infos
+    }
+
     /// The count of address in address manager
     pub fn count(&self) -> usize {
         self.addr_to_id.len()
@@ -86,6 +305,42 @@ impl AddrManager {
         self.id_to_info.values()
     }
 
+    /// Addresses using the transport `kind`, see `TransportKind::of`
+    ///
+    /// Addresses are indexed by ip and port, so onion addresses are never stored here.
+    pub fn addrs_by_transport(&self, kind: TransportKind) -> Vec<Multiaddr> {
+        self.addrs_iter()
+            .filter(|info| TransportKind::of(&info.addr) == kind)
+            .map(|info| info.addr.clone())
+            .collect()
+    }
+
+    /// Return up to `max` addresses connected within the last `since_ms` before `now_ms`
+    ///
+    /// Never connected addresses are skipped. Addresses are ordered by score, ties are broken
+    /// by the more recent connection then by address. The manager does not know the ban list,
+    /// use `PeerStore::recently_active_addrs` to exclude the addresses of banned networks.
+    pub fn recently_active_addrs(&self, since_ms: u64, now_ms: u64, max: usize) -> Vec<Multiaddr> {
+        let mut addrs: Vec<&AddrInfo> = self
+            .addrs_iter()
+            .filter(|info| {
+                info.last_connected_at_ms > 0
+                    && now_ms.saturating_sub(info.last_connected_at_ms) <= since_ms
+            })
+            .collect();
+        addrs.sort_unstable_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.last_connected_at_ms.cmp(&a.last_connected_at_ms))
+                .then_with(|| a.addr.to_vec().cmp(&b.addr.to_vec()))
+        });
+        addrs
+            .into_iter()
+            .take(max)
+            .map(|info| info.addr.clone())
+            .collect()
+    }
+
     /// Remove an address by ip and port
     pub fn remove(&mut self, addr: &Multiaddr) -> Option<AddrInfo> {
         multiaddr_to_socketaddr(addr).and_then(|addr| {
@@ -94,6 +349,14 @@ impl AddrManager {
                 // swap with last index, then remove the last index
                 self.swap_random_id(random_id_pos, self.random_ids.len() - 1);
                 self.random_ids.pop();
+                if let Some(source) = self.id_to_source.remove(&id) {
+                    if let Some(ids) = self.source_to_ids.get_mut(&source) {
+                        ids.remove(&id);
+                        if ids.is_empty() {
+                            self.source_to_ids.remove(&source);
+                        }
+                    }
+                }
                 self.id_to_info.remove(&id)
             })
         })
@@ -1,11 +1,11 @@
 //! Ban list
-use crate::peer_store::types::{ip_to_network, BannedAddr};
-use crate::peer_store::Multiaddr;
-use ckb_systemtime::unix_time_as_millis;
+use crate::peer_store::types::{ip_to_network, BanReason, BannedAddr};
+use crate::peer_store::{system_clock, Clock, Multiaddr};
 use ipnetwork::IpNetwork;
 use p2p::utils::multiaddr_to_socketaddr;
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::sync::Arc;
 
 pub(crate) const CLEAR_INTERVAL_COUNTER: usize = 1024;
 
@@ -13,6 +13,7 @@ pub(crate) const CLEAR_INTERVAL_COUNTER: usize = 1024;
 pub struct BanList {
     inner: HashMap<IpNetwork, BannedAddr>,
     insert_count: usize,
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for BanList {
@@ -27,9 +28,15 @@ impl BanList {
         BanList {
             inner: HashMap::default(),
             insert_count: 0,
+            clock: system_clock(),
         }
     }
 
+    /// Replace the clock used to check ban expiry
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
     /// Ban address
     pub fn ban(&mut self, banned_addr: BannedAddr) {
         self.inner.insert(banned_addr.address, banned_addr);
@@ -40,6 +47,22 @@ impl BanList {
         }
     }
 
+    /// Ban all addresses in a subnet
+    ///
+    /// The network is normalized to its prefix, so `10.0.0.5/24` bans `10.0.0.0/24`.
+    pub fn ban_cidr(&mut self, network: IpNetwork, timeout_ms: u64) {
+        let network = IpNetwork::new(network.network(), network.prefix()).expect("valid prefix");
+        let now_ms = self.clock.now_ms();
+        self.ban(BannedAddr {
+            address: network,
+            ban_until: now_ms.saturating_add(timeout_ms),
+            ban_reason: format!("ban subnet {network}"),
+            created_at: now_ms,
+            score: None,
+            reason: BanReason::Manual,
+        });
+    }
+
     /// Unban address
     pub fn unban_network(&mut self, ip_network: &IpNetwork) {
         self.inner.remove(ip_network);
@@ -60,7 +83,7 @@ impl BanList {
 
     /// Whether the ip is banned
     pub fn is_ip_banned(&self, ip: &IpAddr) -> bool {
-        let now_ms = unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         self.is_ip_banned_until(ip.to_owned(), now_ms)
     }
 
@@ -71,15 +94,60 @@ impl BanList {
             .unwrap_or_default()
     }
 
+    /// Get the ban on the ip, either of the exact address or of a subnet containing it
+    ///
+    /// When several bans match, the soonest expiring one is returned.
+    pub fn get_ip_ban(&self, ip: &IpAddr) -> Option<&BannedAddr> {
+        let now_ms = self.clock.now_ms();
+        self.inner
+            .iter()
+            .filter(|(ip_network, banned_addr)| {
+                banned_addr.ban_until.gt(&now_ms) && ip_network.contains(*ip)
+            })
+            .map(|(_, banned_addr)| banned_addr)
+            .min_by_key(|banned_addr| banned_addr.ban_until)
+    }
+
+    /// Get the ban on the address, see `get_ip_ban`
+    pub fn get_addr_ban(&self, addr: &Multiaddr) -> Option<&BannedAddr> {
+        multiaddr_to_socketaddr(addr).and_then(|socket_addr| self.get_ip_ban(&socket_addr.ip()))
+    }
+
+    /// Iterate the bans not expired yet
+    pub fn active_bans(&self) -> impl Iterator<Item = &BannedAddr> {
+        let now_ms = self.clock.now_ms();
+        self.inner
+            .values()
+            .filter(move |banned_addr| banned_addr.ban_until.gt(&now_ms))
+    }
+
     /// Get banned address list
     pub fn get_banned_addrs(&self) -> Vec<BannedAddr> {
         self.inner.values().map(ToOwned::to_owned).collect()
     }
 
+    /// Count the bans started within `window_ms` before `now_ms`
+    ///
+    /// Expired bans count until they are pruned, so a burst of short bans is still visible.
+    pub fn recent_ban_count(&self, window_ms: u64, now_ms: u64) -> usize {
+        let since_ms = now_ms.saturating_sub(window_ms);
+        self.inner
+            .values()
+            .filter(|banned_addr| (since_ms..=now_ms).contains(&banned_addr.created_at))
+            .count()
+    }
+
     fn clear_expires(&mut self) {
-        let now = unix_time_as_millis();
+        let now = self.clock.now_ms();
+        self.remove_expired(now);
+    }
+
+    /// Remove the bans expired at `now_ms`, returns the number of removed bans
+    pub fn remove_expired(&mut self, now_ms: u64) -> usize {
+        let count = self.inner.len();
         self.inner
-            .retain(|_, banned_addr| banned_addr.ban_until.gt(&now));
+            .retain(|_, banned_addr| banned_addr.ban_until.gt(&now_ms));
+        count - self.inner.len()
     }
 
     /// Get the numbers of banned address
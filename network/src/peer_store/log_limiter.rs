@@ -0,0 +1,67 @@
+//! Rate limited logging for event storms
+use crate::peer_store::LogRateLimitConfig;
+
+/// Coalesce repeated events into periodic summaries
+///
+/// The first `max_lines_per_window` events of a window are logged one by one, the rest are
+/// only counted and reported in a summary line once the window is over, either by the next
+/// event or by `flush`.
+pub(crate) struct RateLimitedLog {
+    event: &'static str,
+    config: LogRateLimitConfig,
+    window_start_ms: u64,
+    count: usize,
+}
+
+impl RateLimitedLog {
+    pub(crate) fn new(event: &'static str, config: LogRateLimitConfig) -> Self {
+        RateLimitedLog {
+            event,
+            config,
+            window_start_ms: 0,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: LogRateLimitConfig) {
+        self.config = config;
+    }
+
+    /// Events of the current window which were not logged one by one
+    pub(crate) fn suppressed(&self) -> usize {
+        self.count.saturating_sub(self.config.max_lines_per_window)
+    }
+
+    /// Record an event, return the lines should be logged
+    pub(crate) fn record(&mut self, now_ms: u64, line: String) -> Vec<String> {
+        let mut lines: Vec<String> = self.flush(now_ms).into_iter().collect();
+        if self.count == 0 {
+            self.window_start_ms = now_ms;
+        }
+        self.count += 1;
+        if self.count <= self.config.max_lines_per_window {
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Close the current window if it is over, return the summary of the suppressed events
+    ///
+    /// Should be called periodically, so the summary of a storm is logged even if no event
+    /// follows it.
+    pub(crate) fn flush(&mut self, now_ms: u64) -> Option<String> {
+        if self.count == 0 || now_ms.saturating_sub(self.window_start_ms) < self.config.window_ms {
+            return None;
+        }
+        let suppressed = self.suppressed();
+        self.count = 0;
+        (suppressed > 0).then(|| {
+            format!(
+                "{} {} more peers within {}s",
+                self.event,
+                suppressed,
+                self.config.window_ms / 1000
+            )
+        })
+    }
+}
@@ -9,17 +9,20 @@
 
 pub mod addr_manager;
 pub mod ban_list;
+pub(crate) mod log_limiter;
 mod peer_store_db;
 mod peer_store_impl;
 pub mod types;
 
 pub(crate) use crate::Behaviour;
 pub use crate::SessionType;
-use p2p::multiaddr::Multiaddr;
+use p2p::multiaddr::{Multiaddr, Protocol};
 pub(crate) use peer_store_impl::required_flags_filter;
 pub use peer_store_impl::PeerStore;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-/// peer store evict peers after reach this limitation
+/// Default limitation on the count of addresses, peer store evict peers after reach it
 pub(crate) const ADDR_COUNT_LIMIT: usize = 16384;
 /// Consider we never seen a peer if peer's last_connected_at beyond this timeout
 const ADDR_TIMEOUT_MS: u64 = 7 * 24 * 3600 * 1000;
@@ -28,8 +31,39 @@ pub(crate) const ADDR_TRY_TIMEOUT_MS: u64 = 3 * 24 * 3600 * 1000;
 /// When obtaining the list of selectable nodes for identify,
 /// the node that has just been disconnected needs to be excluded
 pub(crate) const DIAL_INTERVAL: u64 = 15 * 1000;
+/// The default time a peer is not dialed again after it disconnected
+pub const DISCONNECT_COOLDOWN_MS: u64 = 60 * 1000;
 const ADDR_MAX_RETRIES: u32 = 3;
 const ADDR_MAX_FAILURES: u32 = 10;
+/// Default backoff after the first failed attempt to an address, doubled by each further one
+const ADDR_BACKOFF_BASE_MS: u64 = 5 * 1000;
+/// Default limitation on the count of never connected addresses a single peer contributes
+const ADDR_MAX_PER_SOURCE: usize = 1000;
+/// Version of the peer store dump format written by this node
+pub const DUMP_VERSION: u8 = 1;
+
+/// Source of the current time used by the peer store
+///
+/// All expiry, decay and backoff logic of the peer store reads the time from it,
+/// so that nodes running on simulated time and tests can drive it deterministically.
+pub trait Clock: Send + Sync {
+    /// Current unix time in milliseconds
+    fn now_ms(&self) -> u64;
+}
+
+/// Clock reading the system time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        ckb_systemtime::unix_time_as_millis()
+    }
+}
+
+pub(crate) fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
 
 /// Alias score
 pub type Score = i32;
@@ -43,6 +77,10 @@ pub struct PeerScoreConfig {
     pub ban_score: Score,
     /// Ban time
     pub ban_timeout_ms: u64,
+    /// Interval of moving scores back toward the default score
+    pub decay_interval_ms: u64,
+    /// Score moved toward the default score per decay interval
+    pub decay_step: Score,
 }
 
 impl Default for PeerScoreConfig {
@@ -51,10 +89,129 @@ impl Default for PeerScoreConfig {
             default_score: 100,
             ban_score: 40,
             ban_timeout_ms: 24 * 3600 * 1000, // 1 day
+            decay_interval_ms: 3600 * 1000,   // 1 hour
+            decay_step: 1,
+        }
+    }
+}
+
+/// Score deltas applied when reporting behaviours
+///
+/// Behaviours without a delta fall back to the default delta if set, otherwise
+/// to `Behaviour::score`.
+#[derive(Clone, Debug, Default)]
+pub struct ScorePenalties {
+    deltas: HashMap<Behaviour, Score>,
+    default_delta: Option<Score>,
+}
+
+impl ScorePenalties {
+    /// Create a builder
+    pub fn new_builder() -> ScorePenaltiesBuilder {
+        ScorePenaltiesBuilder::default()
+    }
+
+    /// The score delta of a behaviour
+    pub fn delta(&self, behaviour: Behaviour) -> Score {
+        self.deltas
+            .get(&behaviour)
+            .copied()
+            .or(self.default_delta)
+            .unwrap_or_else(|| behaviour.score())
+    }
+}
+
+/// Builder of `ScorePenalties`, only the overridden behaviours need to be specified
+#[derive(Clone, Debug, Default)]
+pub struct ScorePenaltiesBuilder {
+    inner: ScorePenalties,
+}
+
+impl ScorePenaltiesBuilder {
+    /// Set the score delta of a behaviour
+    pub fn penalty(mut self, behaviour: Behaviour, delta: Score) -> Self {
+        self.inner.deltas.insert(behaviour, delta);
+        self
+    }
+
+    /// Set the score delta of the behaviours not specified
+    pub fn default_delta(mut self, delta: Score) -> Self {
+        self.inner.default_delta = Some(delta);
+        self
+    }
+
+    /// Build the penalties
+    pub fn build(self) -> ScorePenalties {
+        self.inner
+    }
+}
+
+/// PeerStore configuration
+#[derive(Copy, Clone, Debug)]
+pub struct PeerStoreConfig {
+    /// Peer store evict peers after the count of addresses reach this limitation
+    pub addr_count_limit: usize,
+    /// Consider we never seen a peer if peer's last_connected_at beyond this timeout
+    pub addr_timeout_ms: u64,
+    /// Give up an address never connected after this many attempts
+    pub addr_max_retries: u32,
+    /// Give up an address not connected within the timeout after this many attempts
+    pub addr_max_failures: u32,
+    /// Backoff after the first failed attempt to an address, see `AddrInfo::backoff_until_ms`
+    pub addr_backoff_base_ms: u64,
+    /// Never connected addresses a single peer may contribute, see `AddrManager::add_addr_from`
+    pub addr_max_per_source: usize,
+    /// Scoring configuration
+    pub score_config: PeerScoreConfig,
+    /// Rate limit of the ban and eviction logs
+    pub log_rate_limit: LogRateLimitConfig,
+}
+
+impl Default for PeerStoreConfig {
+    fn default() -> Self {
+        PeerStoreConfig {
+            addr_count_limit: ADDR_COUNT_LIMIT,
+            addr_timeout_ms: ADDR_TIMEOUT_MS,
+            addr_max_retries: ADDR_MAX_RETRIES,
+            addr_max_failures: ADDR_MAX_FAILURES,
+            addr_backoff_base_ms: ADDR_BACKOFF_BASE_MS,
+            addr_max_per_source: ADDR_MAX_PER_SOURCE,
+            score_config: PeerScoreConfig::default(),
+            log_rate_limit: LogRateLimitConfig::default(),
         }
     }
 }
 
+/// Rate limit of repeated log lines, extra lines in a window are coalesced into a summary
+#[derive(Copy, Clone, Debug)]
+pub struct LogRateLimitConfig {
+    /// Length of a window
+    pub window_ms: u64,
+    /// Lines logged one by one in a window
+    pub max_lines_per_window: usize,
+}
+
+impl Default for LogRateLimitConfig {
+    fn default() -> Self {
+        LogRateLimitConfig {
+            window_ms: 10 * 1000,
+            max_lines_per_window: 10,
+        }
+    }
+}
+
+/// How to handle a peer store dump written by a newer node version
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DumpVersionPolicy {
+    /// Refuse to load the dump
+    Reject,
+    /// Discard the dump and start with an empty store
+    Ignore,
+    /// Load the entries this version understands and skip unknown sections
+    #[default]
+    BestEffort,
+}
+
 /// Peer Status
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Status {
@@ -64,6 +221,55 @@ pub enum Status {
     Disconnected,
 }
 
+/// Transport of an address
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TransportKind {
+    /// Plain TCP
+    Tcp,
+    /// WebSocket
+    Ws,
+    /// WebSocket over TLS
+    Wss,
+    /// Tor onion service
+    Onion,
+    /// Anything else, e.g. an address without a TCP port
+    Other,
+}
+
+impl TransportKind {
+    /// Classify the address by its protocol stack
+    pub fn of(addr: &Multiaddr) -> Self {
+        let (mut tcp, mut tls, mut ws) = (false, false, false);
+        for proto in addr.iter() {
+            match proto {
+                Protocol::Onion3(_) => return TransportKind::Onion,
+                Protocol::Wss => return TransportKind::Wss,
+                Protocol::Tls(_) => tls = true,
+                Protocol::Ws => ws = true,
+                Protocol::Tcp(_) => tcp = true,
+                _ => {}
+            }
+        }
+        match (tcp, tls, ws) {
+            (true, true, true) => TransportKind::Wss,
+            (true, false, true) => TransportKind::Ws,
+            (true, false, false) => TransportKind::Tcp,
+            _ => TransportKind::Other,
+        }
+    }
+}
+
+/// Order of the peers listing
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PeerSort {
+    /// Highest score first
+    Score,
+    /// Most recently connected first
+    LastConnected,
+    /// Most failed attempts first
+    FailureCount,
+}
+
 /// Report result
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ReportResult {
@@ -84,3 +290,26 @@ impl ReportResult {
         self == ReportResult::Ok
     }
 }
+
+/// Report of a peer behaviour, see `PeerStore::report`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Report {
+    /// Whether the peer is banned by this report
+    pub result: ReportResult,
+    /// The peer score after the report, `None` if the address is not in the peer store
+    pub score: Option<Score>,
+    /// The reported behaviour
+    pub behaviour: Behaviour,
+}
+
+impl Report {
+    /// Whether ban
+    pub fn is_banned(self) -> bool {
+        self.result.is_banned()
+    }
+
+    /// Whether ok
+    pub fn is_ok(self) -> bool {
+        self.result.is_ok()
+    }
+}
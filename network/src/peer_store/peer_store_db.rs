@@ -4,46 +4,59 @@ use crate::{
         addr_manager::AddrManager,
         ban_list::BanList,
         types::{AddrInfo, BannedAddr},
-        PeerStore,
+        DumpVersionPolicy, Multiaddr, PeerStore, DUMP_VERSION,
     },
 };
-use ckb_logger::{debug, error};
+use ckb_logger::{debug, error, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
 use std::path::Path;
 use std::{
     fs::{copy, create_dir_all, remove_file, rename, File, OpenOptions},
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
 };
 
+const DEFAULT_PEER_STORE_DB: &str = "peer_store.db";
 const DEFAULT_ADDR_MANAGER_DB: &str = "addr_manager.db";
 const DEFAULT_BAN_LIST_DB: &str = "ban_list.db";
+const DEFAULT_WHITELIST_DB: &str = "whitelist.db";
+
+const VERSION_FIELD: &str = "version";
+const ADDRS_SECTION: &str = "addrs";
+const BANNED_ADDRS_SECTION: &str = "banned_addrs";
+const WHITELIST_SECTION: &str = "whitelist";
 
 impl AddrManager {
     /// Load address list from disk
     pub fn load<R: Read>(r: R) -> Result<Self, Error> {
-        let addrs: Vec<AddrInfo> = serde_json::from_reader(r).map_err(PeerStoreError::Serde)?;
+        Self::load_with_policy(r, DumpVersionPolicy::default())
+    }
+
+    /// Load address list from disk, `policy` decides how to handle a dump from a newer version
+    pub fn load_with_policy<R: Read>(r: R, policy: DumpVersionPolicy) -> Result<Self, Error> {
+        let addrs: Vec<AddrInfo> = load_section(r, ADDRS_SECTION, policy)?;
         let mut addr_manager = AddrManager::default();
         addrs.into_iter().for_each(|addr| addr_manager.add(addr));
         Ok(addr_manager)
     }
 
     /// Dump address list to disk
-    pub fn dump(&self, mut file: File) -> Result<(), Error> {
+    pub fn dump(&self, file: File) -> Result<(), Error> {
         let addrs: Vec<_> = self.addrs_iter().collect();
         debug!("Dump {} addrs", addrs.len());
-        // empty file and dump the json string to it
-        file.set_len(0)
-            .and_then(|_| serde_json::to_string(&addrs).map_err(Into::into))
-            .and_then(|json_string| file.write_all(json_string.as_bytes()))
-            .and_then(|_| file.sync_all())
-            .map_err(Into::into)
+        dump_section(file, ADDRS_SECTION, &addrs)
     }
 }
 
 impl BanList {
     /// Load ban list from disk
     pub fn load<R: Read>(r: R) -> Result<Self, Error> {
-        let banned_addrs: Vec<BannedAddr> =
-            serde_json::from_reader(r).map_err(PeerStoreError::Serde)?;
+        Self::load_with_policy(r, DumpVersionPolicy::default())
+    }
+
+    /// Load ban list from disk, `policy` decides how to handle a dump from a newer version
+    pub fn load_with_policy<R: Read>(r: R, policy: DumpVersionPolicy) -> Result<Self, Error> {
+        let banned_addrs: Vec<BannedAddr> = load_section(r, BANNED_ADDRS_SECTION, policy)?;
         let mut ban_list = BanList::default();
         banned_addrs
             .into_iter()
@@ -52,93 +65,346 @@ impl BanList {
     }
 
     /// Dump ban list to disk
-    pub fn dump(&self, mut file: File) -> Result<(), Error> {
+    pub fn dump(&self, file: File) -> Result<(), Error> {
         let banned_addrs = self.get_banned_addrs();
         debug!("Dump {} banned addrs", banned_addrs.len());
-        // empty file and dump the json string to it
-        file.set_len(0)
-            .and_then(|_| serde_json::to_string(&banned_addrs).map_err(Into::into))
-            .and_then(|json_string| file.write_all(json_string.as_bytes()))
-            .and_then(|_| file.sync_all())
-            .map_err(Into::into)
+        dump_section(file, BANNED_ADDRS_SECTION, &banned_addrs)
     }
 }
 
 impl PeerStore {
     /// Init peer store from disk
     pub fn load_from_dir_or_default<P: AsRef<Path>>(path: P) -> Self {
+        Self::load_from_dir(path, DumpVersionPolicy::default()).unwrap_or_default()
+    }
+
+    /// Init peer store from disk with the given policy for dumps written by a newer version
+    ///
+    /// Missing or broken db files are ignored and an empty list is used instead,
+    /// only a dump rejected by `policy` is returned as an error.
+    ///
+    /// The addresses, bans and whitelist are read from a single db file, so they are always
+    /// consistent with each other. Directories written before that file existed are loaded
+    /// from the separate per-list db files.
+    pub fn load_from_dir<P: AsRef<Path>>(
+        path: P,
+        policy: DumpVersionPolicy,
+    ) -> Result<Self, Error> {
+        let peer_store_path = path.as_ref().join(DEFAULT_PEER_STORE_DB);
+        let file = match File::open(&peer_store_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Self::load_from_legacy_dir(path, policy);
+            }
+            Err(err) => {
+                debug!(
+                    "Failed to open peer store db, file: {:?}, error: {:?}",
+                    peer_store_path, err
+                );
+                return Ok(PeerStore::default());
+            }
+        };
+
+        let load = || -> Result<Self, Error> {
+            let (addr_manager, ban_list, whitelist) =
+                load_peer_store_db(std::io::BufReader::new(file), policy)?;
+            let mut peer_store = PeerStore::new(addr_manager, ban_list);
+            whitelist
+                .into_iter()
+                .for_each(|addr| peer_store.add_whitelisted(addr));
+            Ok(peer_store)
+        };
+        match load() {
+            Ok(peer_store) => Ok(peer_store),
+            Err(err @ Error::PeerStore(PeerStoreError::UnsupportedVersion(_))) => Err(err),
+            Err(err) => {
+                warn!(
+                    "Failed to load peer store db, start with an empty store, \
+                     file: {:?}, error: {:?}",
+                    peer_store_path, err
+                );
+                Ok(PeerStore::default())
+            }
+        }
+    }
+
+    /// Replace the addresses and bans with the ones of the peer store db file at `path`
+    ///
+    /// The file is fully loaded before anything is replaced, so a missing or malformed file
+    /// leaves the store untouched and returns the error. Connected peers, the whitelist and the
+    /// configuration are kept, whitelisted addresses missing from the file are added back.
+    /// Callers sharing the store behind the network state lock see the swap at once.
+    pub fn reload_from_path(&mut self, path: &Path) -> Result<(), Error> {
+        let file = File::open(path)?;
+        let (addr_manager, ban_list, _) =
+            load_peer_store_db(std::io::BufReader::new(file), DumpVersionPolicy::default())?;
+        self.replace_ban_list(ban_list);
+        self.replace_addrs(addr_manager);
+        let whitelist: Vec<Multiaddr> = self.whitelist_iter().cloned().collect();
+        whitelist
+            .into_iter()
+            .for_each(|addr| self.add_whitelisted(addr));
+        Ok(())
+    }
+
+    /// Load from the per-list db files written by previous versions
+    fn load_from_legacy_dir<P: AsRef<Path>>(
+        path: P,
+        policy: DumpVersionPolicy,
+    ) -> Result<Self, Error> {
         let addr_manager_path = path.as_ref().join(DEFAULT_ADDR_MANAGER_DB);
         let ban_list_path = path.as_ref().join(DEFAULT_BAN_LIST_DB);
 
-        let addr_manager = File::open(&addr_manager_path)
-            .map_err(|err| {
+        let addr_manager = match File::open(&addr_manager_path) {
+            Ok(file) => {
+                match AddrManager::load_with_policy(std::io::BufReader::new(file), policy) {
+                    Ok(addr_manager) => addr_manager,
+                    Err(err @ Error::PeerStore(PeerStoreError::UnsupportedVersion(_))) => {
+                        return Err(err)
+                    }
+                    Err(err) => {
+                        error!(
+                            "Failed to load AddrManager db, file: {:?}, error: {:?}",
+                            addr_manager_path, err
+                        );
+                        AddrManager::default()
+                    }
+                }
+            }
+            Err(err) => {
                 debug!(
                     "Failed to open AddrManager db, file: {:?}, error: {:?}",
                     addr_manager_path, err
-                )
-            })
-            .and_then(|file| {
-                AddrManager::load(std::io::BufReader::new(file)).map_err(|err| {
+                );
+                AddrManager::default()
+            }
+        };
+
+        let ban_list = match File::open(&ban_list_path) {
+            Ok(file) => match BanList::load_with_policy(std::io::BufReader::new(file), policy) {
+                Ok(ban_list) => ban_list,
+                Err(err @ Error::PeerStore(PeerStoreError::UnsupportedVersion(_))) => {
+                    return Err(err)
+                }
+                Err(err) => {
                     error!(
-                        "Failed to load AddrManager db, file: {:?}, error: {:?}",
-                        addr_manager_path, err
-                    )
-                })
-            })
-            .unwrap_or_default();
-
-        let ban_list = File::open(&ban_list_path)
-            .map_err(|err| {
+                        "Failed to load BanList db, file: {:?}, error: {:?}",
+                        ban_list_path, err
+                    );
+                    BanList::default()
+                }
+            },
+            Err(err) => {
                 debug!(
                     "Failed to open BanList db, file: {:?}, error: {:?}",
                     ban_list_path, err
-                )
-            })
-            .and_then(|file| {
-                BanList::load(std::io::BufReader::new(file)).map_err(|err| {
-                    error!(
-                        "Failed to load BanList db, file: {:?}, error: {:?}",
-                        ban_list_path, err
-                    )
-                })
-            })
-            .unwrap_or_default();
+                );
+                BanList::default()
+            }
+        };
 
-        PeerStore::new(addr_manager, ban_list)
+        let whitelist_path = path.as_ref().join(DEFAULT_WHITELIST_DB);
+        let whitelist: Vec<Multiaddr> = match File::open(&whitelist_path) {
+            Ok(file) => {
+                match load_section(std::io::BufReader::new(file), WHITELIST_SECTION, policy) {
+                    Ok(whitelist) => whitelist,
+                    Err(err @ Error::PeerStore(PeerStoreError::UnsupportedVersion(_))) => {
+                        return Err(err)
+                    }
+                    Err(err) => {
+                        error!(
+                            "Failed to load whitelist db, file: {:?}, error: {:?}",
+                            whitelist_path, err
+                        );
+                        Vec::new()
+                    }
+                }
+            }
+            Err(err) => {
+                debug!(
+                    "Failed to open whitelist db, file: {:?}, error: {:?}",
+                    whitelist_path, err
+                );
+                Vec::new()
+            }
+        };
+
+        let mut peer_store = PeerStore::new(addr_manager, ban_list);
+        whitelist
+            .into_iter()
+            .for_each(|addr| peer_store.add_whitelisted(addr));
+        Ok(peer_store)
     }
 
     /// Dump all info to disk
+    ///
+    /// Everything is written to a temporary file first, then renamed over the db file, so a
+    /// process killed in the middle of a dump leaves the previous dump intact.
     pub fn dump_to_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
         // create dir
         create_dir_all(&path)?;
         // dump file to a temporary sub-directory
         let tmp_dir = path.as_ref().join("tmp");
         create_dir_all(&tmp_dir)?;
-        let tmp_addr_manager = tmp_dir.join(DEFAULT_ADDR_MANAGER_DB);
-        let tmp_ban_list = tmp_dir.join(DEFAULT_BAN_LIST_DB);
-        self.addr_manager().dump(
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(false)
-                .open(&tmp_addr_manager)?,
-        )?;
-        move_file(
-            tmp_addr_manager,
-            path.as_ref().join(DEFAULT_ADDR_MANAGER_DB),
-        )?;
-        self.ban_list().dump(
+        let tmp_peer_store = tmp_dir.join(DEFAULT_PEER_STORE_DB);
+
+        let addrs: Vec<_> = self.addr_manager().addrs_iter().collect();
+        let banned_addrs = self.ban_list().get_banned_addrs();
+        let whitelist: Vec<_> = self.whitelist_iter().collect();
+        debug!(
+            "Dump {} addrs, {} banned addrs and {} whitelisted addrs",
+            addrs.len(),
+            banned_addrs.len(),
+            whitelist.len()
+        );
+        dump_sections(
             OpenOptions::new()
                 .write(true)
                 .create(true)
                 .append(false)
-                .open(&tmp_ban_list)?,
+                .open(&tmp_peer_store)?,
+            vec![
+                (
+                    ADDRS_SECTION,
+                    serde_json::to_value(&addrs).map_err(PeerStoreError::Serde)?,
+                ),
+                (
+                    BANNED_ADDRS_SECTION,
+                    serde_json::to_value(&banned_addrs).map_err(PeerStoreError::Serde)?,
+                ),
+                (
+                    WHITELIST_SECTION,
+                    serde_json::to_value(&whitelist).map_err(PeerStoreError::Serde)?,
+                ),
+            ],
         )?;
-        move_file(tmp_ban_list, path.as_ref().join(DEFAULT_BAN_LIST_DB))?;
+        move_file(tmp_peer_store, path.as_ref().join(DEFAULT_PEER_STORE_DB))?;
+
+        // the per-list db files are superseded by the peer store db
+        for legacy_db in [
+            DEFAULT_ADDR_MANAGER_DB,
+            DEFAULT_BAN_LIST_DB,
+            DEFAULT_WHITELIST_DB,
+        ] {
+            let legacy_path = path.as_ref().join(legacy_db);
+            if legacy_path.exists() {
+                if let Err(err) = remove_file(&legacy_path) {
+                    debug!(
+                        "Failed to remove legacy db, file: {:?}, error: {:?}",
+                        legacy_path, err
+                    );
+                }
+            }
+        }
         Ok(())
     }
 }
 
+/// Read the address list, ban list and whitelist from a peer store db file
+fn load_peer_store_db<R: Read>(
+    r: R,
+    policy: DumpVersionPolicy,
+) -> Result<(AddrManager, BanList, Vec<Multiaddr>), Error> {
+    let mut dump: Value = serde_json::from_reader(r).map_err(PeerStoreError::Serde)?;
+    let addrs: Vec<AddrInfo> = take_section(&mut dump, ADDRS_SECTION, policy)?;
+    let banned_addrs: Vec<BannedAddr> = take_section(&mut dump, BANNED_ADDRS_SECTION, policy)?;
+    let whitelist: Vec<Multiaddr> = take_section(&mut dump, WHITELIST_SECTION, policy)?;
+
+    let mut addr_manager = AddrManager::default();
+    addrs.into_iter().for_each(|addr| addr_manager.add(addr));
+    let mut ban_list = BanList::default();
+    banned_addrs
+        .into_iter()
+        .for_each(|banned_addr| ban_list.ban(banned_addr));
+    Ok((addr_manager, ban_list, whitelist))
+}
+
+/// Read the entries of `section` from a dump.
+///
+/// Dumps written before the format was versioned are a bare json array, they are always loaded.
+fn load_section<R: Read, T: DeserializeOwned>(
+    r: R,
+    section: &str,
+    policy: DumpVersionPolicy,
+) -> Result<Vec<T>, Error> {
+    let mut dump = serde_json::from_reader(r).map_err(PeerStoreError::Serde)?;
+    take_section(&mut dump, section, policy)
+}
+
+/// Take the entries of `section` out of a parsed dump, see `load_section`
+fn take_section<T: DeserializeOwned>(
+    dump: &mut Value,
+    section: &str,
+    policy: DumpVersionPolicy,
+) -> Result<Vec<T>, Error> {
+    if dump.is_array() {
+        return serde_json::from_value(dump.take())
+            .map_err(|err| PeerStoreError::Serde(err).into());
+    }
+    let dump = match dump {
+        Value::Object(dump) => dump,
+        _ => {
+            return Err(PeerStoreError::Serde(serde::de::Error::custom(
+                "expect a json array or object",
+            ))
+            .into())
+        }
+    };
+    let version = dump
+        .get(VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+    let entries = dump.remove(section).unwrap_or(Value::Array(Vec::new()));
+    if version <= u64::from(DUMP_VERSION) {
+        return serde_json::from_value(entries).map_err(|err| PeerStoreError::Serde(err).into());
+    }
+
+    match policy {
+        DumpVersionPolicy::Reject => Err(PeerStoreError::UnsupportedVersion(version).into()),
+        DumpVersionPolicy::Ignore => {
+            warn!(
+                "Ignore {} written by a newer version {}, current version {}",
+                section, version, DUMP_VERSION
+            );
+            Ok(Vec::new())
+        }
+        DumpVersionPolicy::BestEffort => {
+            warn!(
+                "Load {} written by a newer version {} in best effort, current version {}",
+                section, version, DUMP_VERSION
+            );
+            // skip entries that can not be understood by this version
+            let entries: Vec<Value> =
+                serde_json::from_value(entries).map_err(PeerStoreError::Serde)?;
+            Ok(entries
+                .into_iter()
+                .filter_map(|entry| serde_json::from_value(entry).ok())
+                .collect())
+        }
+    }
+}
+
+/// Write `entries` as `section` of a versioned dump
+fn dump_section<T: Serialize>(file: File, section: &str, entries: &T) -> Result<(), Error> {
+    let entries = serde_json::to_value(entries).map_err(PeerStoreError::Serde)?;
+    dump_sections(file, vec![(section, entries)])
+}
+
+/// Write `sections` as a single versioned dump
+fn dump_sections(mut file: File, sections: Vec<(&str, Value)>) -> Result<(), Error> {
+    let mut dump = Map::new();
+    dump.insert(VERSION_FIELD.to_owned(), DUMP_VERSION.into());
+    for (section, entries) in sections {
+        dump.insert(section.to_owned(), entries);
+    }
+    // empty file and dump the json string to it
+    file.set_len(0)
+        .and_then(|_| serde_json::to_string(&dump).map_err(Into::into))
+        .and_then(|json_string| file.write_all(json_string.as_bytes()))
+        .and_then(|_| file.sync_all())
+        .map_err(Into::into)
+}
+
 /// This function use `copy` then `remove_file` as a fallback when `rename` failed,
 /// this maybe happen when src and dst on different file systems.
 fn move_file<P: AsRef<Path>>(src: P, dst: P) -> Result<(), Error> {
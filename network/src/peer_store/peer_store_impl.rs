@@ -5,42 +5,118 @@ use crate::{
     peer_store::{
         addr_manager::AddrManager,
         ban_list::BanList,
-        types::{ip_to_network, AddrInfo, BannedAddr, PeerInfo},
-        Behaviour, Multiaddr, PeerScoreConfig, ReportResult, Status, ADDR_COUNT_LIMIT,
-        ADDR_TIMEOUT_MS, ADDR_TRY_TIMEOUT_MS, DIAL_INTERVAL,
+        log_limiter::RateLimitedLog,
+        system_clock,
+        types::{
+            ip_to_network, AddrInfo, BanEvent, BanReason, BannedAddr, ClearStats, ExportedAddr,
+            PeerInfo, PeerStoreStats,
+        },
+        Behaviour, Clock, Multiaddr, PeerSort, PeerStoreConfig, Report, ReportResult, Score,
+        ScorePenalties, Status, ADDR_TRY_TIMEOUT_MS, DIAL_INTERVAL, DISCONNECT_COOLDOWN_MS,
     },
     Flags, PeerId, SessionType,
 };
+use ckb_logger::{debug, info, warn};
+use futures::channel::mpsc::Sender;
 use ipnetwork::IpNetwork;
-use rand::prelude::IteratorRandom;
+use rand::{prelude::IteratorRandom, Rng};
 use std::collections::{hash_map::Entry, HashMap};
+use std::io::{BufRead, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
 
 /// Peer store
 ///
 /// | -- choose to identify --| --- choose to feeler --- | --      delete     -- |
 /// | 1      | 2     | 3      | 4    | 5    | 6   | 7    | More than seven days  |
-#[derive(Default)]
 pub struct PeerStore {
     addr_manager: AddrManager,
     ban_list: BanList,
     connected_peers: HashMap<PeerId, PeerInfo>,
-    score_config: PeerScoreConfig,
+    config: PeerStoreConfig,
+    clock: Arc<dyn Clock>,
+    /// Peers disconnected within the cooldown, with their disconnected time
+    recently_disconnected: HashMap<PeerId, u64>,
+    disconnect_cooldown_ms: u64,
+    shutdown_dumped: bool,
+    /// Addresses never evicted, keyed by ip and port
+    whitelist: HashMap<SocketAddr, Multiaddr>,
+    ban_observer: Option<Sender<BanEvent>>,
+    score_penalties: ScorePenalties,
+    ban_log: RateLimitedLog,
+    eviction_log: RateLimitedLog,
+}
+
+impl Default for PeerStore {
+    fn default() -> Self {
+        PeerStore::new(Default::default(), Default::default())
+    }
 }
 
 impl PeerStore {
     /// New with address list and ban list
     pub fn new(addr_manager: AddrManager, ban_list: BanList) -> Self {
+        let mut peer_store = PeerStore::with_config(Default::default());
+        peer_store.addr_manager = addr_manager;
+        peer_store.ban_list = ban_list;
+        peer_store
+    }
+
+    /// New empty peer store with the given configuration
+    pub fn with_config(config: PeerStoreConfig) -> Self {
+        let mut addr_manager = AddrManager::default();
+        addr_manager.set_config(config);
         PeerStore {
             addr_manager,
-            ban_list,
+            ban_list: Default::default(),
             connected_peers: Default::default(),
-            score_config: Default::default(),
+            config,
+            clock: system_clock(),
+            recently_disconnected: Default::default(),
+            disconnect_cooldown_ms: DISCONNECT_COOLDOWN_MS,
+            shutdown_dumped: false,
+            whitelist: Default::default(),
+            ban_observer: None,
+            score_penalties: Default::default(),
+            ban_log: RateLimitedLog::new("banned", config.log_rate_limit),
+            eviction_log: RateLimitedLog::new("evicted", config.log_rate_limit),
         }
     }
 
+    /// Replace the configuration, including the one used by the address list
+    pub fn set_config(&mut self, config: PeerStoreConfig) {
+        self.addr_manager.set_config(config);
+        self.ban_log.set_config(config.log_rate_limit);
+        self.eviction_log.set_config(config.log_rate_limit);
+        self.config = config;
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &PeerStoreConfig {
+        &self.config
+    }
+
+    /// Set the time a peer is not dialed again after it disconnected
+    pub fn set_disconnect_cooldown_ms(&mut self, cooldown_ms: u64) {
+        self.disconnect_cooldown_ms = cooldown_ms;
+    }
+
+    /// Replace the clock used for all time based logic, including the address list and ban list
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.addr_manager.set_clock(Arc::clone(&clock));
+        self.ban_list.set_clock(Arc::clone(&clock));
+        self.clock = clock;
+    }
+
+    /// Current time of the peer store clock
+    pub fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
+    }
+
     /// this method will assume peer is connected, which implies address is "verified".
     pub fn add_connected_peer(&mut self, addr: Multiaddr, session_type: SessionType) {
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         match self
             .connected_peers
             .entry(extract_peer_id(&addr).expect("connected addr should have peer id"))
@@ -65,12 +141,25 @@ impl PeerStore {
             return Ok(());
         }
         self.check_purge()?;
-        let score = self.score_config.default_score;
+        let score = self.config.score_config.default_score;
         self.addr_manager
             .add(AddrInfo::new(addr, 0, score, flags.bits()));
         Ok(())
     }
 
+    /// Add discovered peer address learned from the peer `source`, see
+    /// `AddrManager::add_addr_from`
+    pub fn add_addr_from(&mut self, addr: Multiaddr, flags: Flags, source: PeerId) -> Result<()> {
+        if self.ban_list.is_addr_banned(&addr) {
+            return Ok(());
+        }
+        self.check_purge()?;
+        let score = self.config.score_config.default_score;
+        self.addr_manager
+            .add_addr_from(AddrInfo::new(addr, 0, score, flags.bits()), source);
+        Ok(())
+    }
+
     #[cfg(feature = "fuzz")]
     pub fn add_addr_fuzz(
         &mut self,
@@ -83,7 +172,7 @@ impl PeerStore {
             return Ok(());
         }
         self.check_purge()?;
-        let score = self.score_config.default_score;
+        let score = self.config.score_config.default_score;
         let mut addr_info = AddrInfo::new(addr, last_connected_at_ms, score, flags.bits());
         addr_info.attempts_count = attempts_count;
 
@@ -96,10 +185,10 @@ impl PeerStore {
         if self.ban_list.is_addr_banned(&addr) {
             return;
         }
-        let score = self.score_config.default_score;
+        let score = self.config.score_config.default_score;
         self.addr_manager.add(AddrInfo::new(
             addr,
-            ckb_systemtime::unix_time_as_millis(),
+            self.clock.now_ms(),
             score,
             flags.bits(),
         ));
@@ -111,7 +200,7 @@ impl PeerStore {
             return;
         }
         if let Some(info) = self.addr_manager.get_mut(&addr) {
-            info.last_connected_at_ms = ckb_systemtime::unix_time_as_millis()
+            info.last_connected_at_ms = self.clock.now_ms()
         }
     }
 
@@ -125,26 +214,250 @@ impl PeerStore {
         &mut self.addr_manager
     }
 
+    /// Import a line based address list, return how many addresses were added
+    ///
+    /// Every line is a multiaddr with a peer id, optionally followed by a score. Empty lines and
+    /// lines starting with `#` are ignored, invalid lines, known and banned addresses are skipped.
+    pub fn import_addr_list<R: BufRead>(&mut self, reader: R) -> usize {
+        let mut added = 0;
+        for (index, line) in reader.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    warn!("Stop importing address list at line {}: {}", index + 1, err);
+                    break;
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (addr, score) = match parse_addr_list_line(line) {
+                Some(parsed) => parsed,
+                None => {
+                    warn!("Skip invalid address list line {}: {}", index + 1, line);
+                    continue;
+                }
+            };
+            if self.ban_list.is_addr_banned(&addr) || self.addr_manager.get(&addr).is_some() {
+                continue;
+            }
+            if let Err(err) = self.check_purge() {
+                warn!("Stop importing address list: {}", err);
+                break;
+            }
+            let score = score.unwrap_or(self.config.score_config.default_score);
+            self.addr_manager
+                .add(AddrInfo::new(addr, 0, score, Flags::COMPATIBILITY.bits()));
+            added += 1;
+        }
+        added
+    }
+
+    /// Add an address never evicted when the address list is full
+    ///
+    /// A whitelisted address can still be banned, it stays in the whitelist while banned.
+    pub fn add_whitelisted(&mut self, addr: Multiaddr) {
+        let socket_addr = match multiaddr_to_socketaddr(&addr) {
+            Some(socket_addr) => socket_addr,
+            None => return,
+        };
+        if !self.ban_list.is_addr_banned(&addr) && self.addr_manager.get(&addr).is_none() {
+            let score = self.config.score_config.default_score;
+            self.addr_manager.add(AddrInfo::new(
+                addr.clone(),
+                0,
+                score,
+                Flags::COMPATIBILITY.bits(),
+            ));
+        }
+        self.whitelist.insert(socket_addr, addr);
+    }
+
+    /// Whether the address is whitelisted
+    pub fn is_whitelisted(&self, addr: &Multiaddr) -> bool {
+        multiaddr_to_socketaddr(addr)
+            .map(|socket_addr| self.whitelist.contains_key(&socket_addr))
+            .unwrap_or_default()
+    }
+
+    /// Whitelisted addresses iterator
+    pub fn whitelist_iter(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.whitelist.values()
+    }
+
+    /// Swap in a freshly built address table, return the replaced one
+    ///
+    /// The ban list is kept as is, and banned addresses in the new table are dropped.
+    pub fn replace_addrs(&mut self, mut addr_manager: AddrManager) -> AddrManager {
+        let banned: Vec<Multiaddr> = addr_manager
+            .addrs_iter()
+            .filter(|info| self.ban_list.is_addr_banned(&info.addr))
+            .map(|info| info.addr.clone())
+            .collect();
+        for addr in banned.iter() {
+            addr_manager.remove(addr);
+        }
+        addr_manager.set_clock(Arc::clone(&self.clock));
+        addr_manager.set_config(self.config);
+        std::mem::replace(&mut self.addr_manager, addr_manager)
+    }
+
+    /// Swap in a freshly loaded ban list, return the replaced one
+    ///
+    /// Addresses banned by the new list are dropped from the address table.
+    pub fn replace_ban_list(&mut self, mut ban_list: BanList) -> BanList {
+        ban_list.set_clock(Arc::clone(&self.clock));
+        let banned: Vec<Multiaddr> = self
+            .addr_manager
+            .addrs_iter()
+            .filter(|info| ban_list.is_addr_banned(&info.addr))
+            .map(|info| info.addr.clone())
+            .collect();
+        for addr in banned.iter() {
+            self.addr_manager.remove(addr);
+        }
+        std::mem::replace(&mut self.ban_list, ban_list)
+    }
+
     /// Report peer behaviours
-    pub fn report(&mut self, addr: &Multiaddr, behaviour: Behaviour) -> ReportResult {
-        if let Some(peer_addr) = self.addr_manager.get_mut(addr) {
-            let score = peer_addr.score.saturating_add(behaviour.score());
-            peer_addr.score = score;
-            if score < self.score_config.ban_score {
-                self.ban_addr(
+    ///
+    /// The returned report carries the updated score, so callers can tell how close the peer is
+    /// to being banned.
+    pub fn report(&mut self, addr: &Multiaddr, behaviour: Behaviour) -> Report {
+        let report = self.simulate_report(addr, behaviour);
+        if let Some(score) = report.score {
+            if let Some(peer_addr) = self.addr_manager.get_mut(addr) {
+                peer_addr.score = score;
+            }
+            if report.is_banned() {
+                let already_banned = self.ban_list.is_addr_banned(addr);
+                let ban_timeout_ms = self.config.score_config.ban_timeout_ms;
+                self.ban_addr_with_score(
                     addr,
-                    self.score_config.ban_timeout_ms,
+                    ban_timeout_ms,
+                    BanReason::ScoreBelowThreshold,
                     format!("report behaviour {behaviour:?}"),
+                    Some(score),
                 );
-                return ReportResult::Banned;
+                // the ban just given is the only one on the addr, report its expiry as recorded
+                let ban_until = self.ban_list.get_addr_ban(addr).map(|ban| ban.ban_until);
+                if !already_banned {
+                    if let Some(ban_until) = ban_until {
+                        self.notify_ban(BanEvent {
+                            addr: addr.clone(),
+                            behaviour,
+                            score,
+                            ban_until,
+                        });
+                    }
+                }
             }
         }
-        ReportResult::Ok
+        report
+    }
+
+    /// Compute the report of a peer behaviour without applying it, see `report`
+    pub fn simulate_report(&self, addr: &Multiaddr, behaviour: Behaviour) -> Report {
+        let score = self.addr_manager.get(addr).map(|peer_addr| {
+            peer_addr
+                .score
+                .saturating_add(self.score_penalties.delta(behaviour))
+        });
+        let result = match score {
+            Some(score) if score < self.config.score_config.ban_score => ReportResult::Banned,
+            _ => ReportResult::Ok,
+        };
+        Report {
+            result,
+            score,
+            behaviour,
+        }
+    }
+
+    /// Set the score deltas applied when reporting behaviours
+    pub fn set_score_penalties(&mut self, score_penalties: ScorePenalties) {
+        self.score_penalties = score_penalties;
+    }
+
+    /// Set a channel receiving an event whenever a reported peer gets banned
+    ///
+    /// Events are sent without blocking, they are dropped if the channel is full or closed.
+    pub fn set_ban_observer(&mut self, tx: Sender<BanEvent>) {
+        self.ban_observer = Some(tx);
+    }
+
+    fn notify_ban(&mut self, event: BanEvent) {
+        if let Some(tx) = self.ban_observer.as_mut() {
+            if let Err(err) = tx.try_send(event) {
+                if err.is_disconnected() {
+                    self.ban_observer = None;
+                } else {
+                    debug!("Drop ban event, the observer channel is full");
+                }
+            }
+        }
+    }
+
+    /// Move the scores of all addresses back toward the default score
+    ///
+    /// Scores move by `decay_step` for every full `decay_interval_ms` elapsed since the last
+    /// decay of the address, so repeated calls within an interval are no-ops.
+    /// Banned addresses are left untouched until their ban expires, and their decay restarts
+    /// from the end of the ban instead of catching up on the banned intervals.
+    pub fn decay_scores(&mut self, now_ms: u64) {
+        let score_config = self.config.score_config;
+        let addrs: Vec<(Multiaddr, bool)> = self
+            .addr_manager
+            .addrs_iter()
+            .map(|info| (info.addr.clone(), self.ban_list.is_addr_banned(&info.addr)))
+            .collect();
+        for (addr, banned) in addrs {
+            if let Some(info) = self.addr_manager.get_mut(&addr) {
+                if banned {
+                    info.last_decay_at_ms = now_ms;
+                    continue;
+                }
+                info.decay_score(
+                    now_ms,
+                    score_config.default_score,
+                    score_config.decay_step,
+                    score_config.decay_interval_ms,
+                );
+            }
+        }
+    }
+
+    /// Record bytes transferred with a peer, the counters are accumulated on its address info
+    pub fn record_traffic(&mut self, peer_id: &PeerId, sent: u64, received: u64) {
+        let addr = match self.connected_peers.get(peer_id) {
+            Some(peer) => peer.connected_addr.clone(),
+            None => match self
+                .addr_manager
+                .addrs_iter()
+                .find(|info| extract_peer_id(&info.addr).as_ref() == Some(peer_id))
+            {
+                Some(info) => info.addr.clone(),
+                None => return,
+            },
+        };
+        if let Some(info) = self.addr_manager.get_mut(&addr) {
+            info.record_traffic(sent, received);
+        }
     }
 
     /// Remove peer id
+    ///
+    /// The peer will not be returned for dialing until the disconnect cooldown passed.
     pub fn remove_disconnected_peer(&mut self, addr: &Multiaddr) -> Option<PeerInfo> {
-        extract_peer_id(addr).and_then(|peer_id| self.connected_peers.remove(&peer_id))
+        let peer_id = extract_peer_id(addr)?;
+        let peer = self.connected_peers.remove(&peer_id)?;
+        let now_ms = self.clock.now_ms();
+        let cooldown_ms = self.disconnect_cooldown_ms;
+        self.recently_disconnected
+            .retain(|_, disconnected_at| is_cooling_down(*disconnected_at, now_ms, cooldown_ms));
+        self.recently_disconnected.insert(peer_id, now_ms);
+        Some(peer)
     }
 
     /// Get peer status
@@ -156,24 +469,65 @@ impl PeerStore {
         }
     }
 
+    /// Whether any connected peer has an ip in the given subnet
+    pub fn has_connected_in_subnet(&self, net: IpNetwork) -> bool {
+        self.connected_peers.values().any(|peer| {
+            multiaddr_to_socketaddr(&peer.connected_addr)
+                .map(|socket_addr| net.contains(socket_addr.ip()))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Count the connected peers of each network group, as `(group, inbound, outbound)`
+    ///
+    /// Groups are ordered by their total count, the most crowded first, then by group. Inbound
+    /// slots taken by a few groups are a sign of an eclipse attack.
+    pub fn netgroup_distribution(&self) -> Vec<(Group, usize, usize)> {
+        let mut counts: HashMap<Group, (usize, usize)> = HashMap::default();
+        for peer in self.connected_peers.values() {
+            let count = counts.entry(Group::from(&peer.connected_addr)).or_default();
+            if peer.session_type.is_inbound() {
+                count.0 += 1;
+            } else {
+                count.1 += 1;
+            }
+        }
+        let mut distribution: Vec<_> = counts
+            .into_iter()
+            .map(|(group, (inbound, outbound))| (group, inbound, outbound))
+            .collect();
+        distribution
+            .sort_unstable_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then_with(|| a.0.cmp(&b.0)));
+        distribution
+    }
+
     /// Get peers for outbound connection, this method randomly return recently connected peer addrs
     pub fn fetch_addrs_to_attempt(&mut self, count: usize, required_flags: Flags) -> Vec<AddrInfo> {
         // Get info:
         // 1. Not already connected
         // 2. Connected within 3 days
+        // 3. Not disconnected within the cooldown
+        // 4. Not banned, whitelisted addrs are kept listed while banned
 
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         let peers = &self.connected_peers;
+        let ban_list = &self.ban_list;
+        let cooling_down = cooling_down_filter(
+            &self.recently_disconnected,
+            now_ms,
+            self.disconnect_cooldown_ms,
+        );
         let addr_expired_ms = now_ms.saturating_sub(ADDR_TRY_TIMEOUT_MS);
         // get addrs that can attempt.
         self.addr_manager
             .fetch_random(count, |peer_addr: &AddrInfo| {
                 extract_peer_id(&peer_addr.addr)
-                    .map(|peer_id| !peers.contains_key(&peer_id))
+                    .map(|peer_id| !peers.contains_key(&peer_id) && !cooling_down(&peer_id))
                     .unwrap_or_default()
                     && peer_addr.connected(|t| {
                         t > addr_expired_ms && t <= now_ms.saturating_sub(DIAL_INTERVAL)
                     })
+                    && !ban_list.is_addr_banned(&peer_addr.addr)
                     && required_flags_filter(
                         required_flags,
                         Flags::from_bits_truncate(peer_addr.flags),
@@ -188,60 +542,417 @@ impl PeerStore {
         // 1. Not already connected
         // 2. Not already tried in a minute
         // 3. Not connected within 3 days
+        // 4. Not disconnected within the cooldown
+        // 5. Not banned
 
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         let addr_expired_ms = now_ms.saturating_sub(ADDR_TRY_TIMEOUT_MS);
         let peers = &self.connected_peers;
+        let ban_list = &self.ban_list;
+        let cooling_down = cooling_down_filter(
+            &self.recently_disconnected,
+            now_ms,
+            self.disconnect_cooldown_ms,
+        );
         self.addr_manager
             .fetch_random(count, |peer_addr: &AddrInfo| {
                 extract_peer_id(&peer_addr.addr)
-                    .map(|peer_id| !peers.contains_key(&peer_id))
+                    .map(|peer_id| !peers.contains_key(&peer_id) && !cooling_down(&peer_id))
                     .unwrap_or_default()
                     && !peer_addr.tried_in_last_minute(now_ms)
                     && !peer_addr.connected(|t| t > addr_expired_ms)
+                    && !ban_list.is_addr_banned(&peer_addr.addr)
             })
     }
 
     /// Return valid addrs that success connected, used for discovery.
     pub fn fetch_random_addrs(&mut self, count: usize, required_flags: Flags) -> Vec<AddrInfo> {
+        self.fetch_random_addrs_with_rng(count, required_flags, false, &mut rand::thread_rng())
+    }
+
+    /// Return valid addrs that success connected, using the given rng.
+    ///
+    /// When `weighted` is true, the probability of picking an addr is proportional to its score
+    /// above the ban score, and addrs at or below the ban score are never returned.
+    /// Otherwise addrs are picked uniformly.
+    pub fn fetch_random_addrs_with_rng<R: Rng>(
+        &mut self,
+        count: usize,
+        required_flags: Flags,
+        weighted: bool,
+        rng: &mut R,
+    ) -> Vec<AddrInfo> {
         // Get info:
         // 1. Connected within 7 days
+        // 2. Not disconnected within the cooldown
+        // 3. Not banned
 
-        let now_ms = ckb_systemtime::unix_time_as_millis();
-        let addr_expired_ms = now_ms.saturating_sub(ADDR_TIMEOUT_MS);
+        let now_ms = self.clock.now_ms();
+        let addr_expired_ms = now_ms.saturating_sub(self.config.addr_timeout_ms);
+        let cooling_down = cooling_down_filter(
+            &self.recently_disconnected,
+            now_ms,
+            self.disconnect_cooldown_ms,
+        );
+        let ban_list = &self.ban_list;
+        let filter = |peer_addr: &AddrInfo| {
+            required_flags_filter(required_flags, Flags::from_bits_truncate(peer_addr.flags))
+                && peer_addr.connected(|t| t > addr_expired_ms)
+                && !extract_peer_id(&peer_addr.addr)
+                    .map(|peer_id| cooling_down(&peer_id))
+                    .unwrap_or_default()
+                && !ban_list.is_addr_banned(&peer_addr.addr)
+        };
         // get success connected addrs.
+        if weighted {
+            let ban_score = self.config.score_config.ban_score;
+            self.addr_manager.fetch_weighted_random(
+                count,
+                filter,
+                |peer_addr| {
+                    i64::from(peer_addr.score)
+                        .saturating_sub(i64::from(ban_score))
+                        .max(0) as u64
+                },
+                rng,
+            )
+        } else {
+            self.addr_manager.fetch_random_with_rng(count, filter, rng)
+        }
+    }
+
+    /// Return up to `n` of the best known addresses to hand out on discovery, banned ones excluded
+    ///
+    /// Addresses are ordered by score, ties are broken by the more recent connection then by
+    /// address, so the order is deterministic. Only the top `n` are sorted, the rest are
+    /// partitioned away in linear time.
+    pub fn top_addrs(&self, n: usize) -> Vec<Multiaddr> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut addrs: Vec<&AddrInfo> = self
+            .addr_manager
+            .addrs_iter()
+            .filter(|info| !self.ban_list.is_addr_banned(&info.addr))
+            .collect();
+        let compare = |a: &&AddrInfo, b: &&AddrInfo| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.last_connected_at_ms.cmp(&a.last_connected_at_ms))
+                .then_with(|| a.addr.to_vec().cmp(&b.addr.to_vec()))
+        };
+        if addrs.len() > n {
+            addrs.select_nth_unstable_by(n - 1, compare);
+            addrs.truncate(n);
+        }
+        addrs.sort_unstable_by(compare);
+        addrs.into_iter().map(|info| info.addr.clone()).collect()
+    }
+
+    /// Whether `addr` may be dialed at `now_ms`
+    ///
+    /// It may not if it is banned, if it has failed `addr_max_failures` times in a row, or if it
+    /// is still backing off from its last failure, see `AddrInfo::backoff_until_ms`. An addr
+    /// unknown to the address manager has no failure and may be dialed unless banned.
+    pub fn is_connectable(&self, addr: &Multiaddr, now_ms: u64) -> bool {
+        if self.ban_list.is_addr_banned(addr) {
+            return false;
+        }
+        match self.addr_manager.get(addr) {
+            Some(info) => {
+                info.attempts_count < self.config.addr_max_failures
+                    && now_ms >= info.backoff_until_ms(&self.config)
+            }
+            None => true,
+        }
+    }
+
+    /// Return up to `max` addresses connected within the last `since_ms`, banned ones excluded
+    ///
+    /// A cheap subset to advertise in peer exchange, see `AddrManager::recently_active_addrs`
+    /// for the order.
+    pub fn recently_active_addrs(&self, since_ms: u64, max: usize) -> Vec<Multiaddr> {
         self.addr_manager
-            .fetch_random(count, |peer_addr: &AddrInfo| {
-                required_flags_filter(required_flags, Flags::from_bits_truncate(peer_addr.flags))
-                    && peer_addr.connected(|t| t > addr_expired_ms)
-            })
+            .recently_active_addrs(since_ms, self.clock.now_ms(), usize::MAX)
+            .into_iter()
+            .filter(|addr| !self.ban_list.is_addr_banned(addr))
+            .take(max)
+            .collect()
     }
 
-    /// Ban an addr
+    /// List a page of the known addresses, return the page and the total count
+    ///
+    /// Ties are broken by peer id then address, so the order is stable and pages never overlap.
+    pub fn list_peers(
+        &self,
+        offset: usize,
+        limit: usize,
+        sort: PeerSort,
+    ) -> (Vec<AddrInfo>, usize) {
+        let mut addrs: Vec<(Option<PeerId>, &AddrInfo)> = self
+            .addr_manager
+            .addrs_iter()
+            .map(|info| (extract_peer_id(&info.addr), info))
+            .collect();
+        let total = addrs.len();
+        addrs.sort_by(|(a_peer_id, a), (b_peer_id, b)| {
+            let ordering = match sort {
+                PeerSort::Score => b.score.cmp(&a.score),
+                PeerSort::LastConnected => b.last_connected_at_ms.cmp(&a.last_connected_at_ms),
+                PeerSort::FailureCount => b.attempts_count.cmp(&a.attempts_count),
+            };
+            ordering
+                .then_with(|| {
+                    let a_peer_id = a_peer_id.as_ref().map(PeerId::as_bytes);
+                    let b_peer_id = b_peer_id.as_ref().map(PeerId::as_bytes);
+                    a_peer_id.cmp(&b_peer_id)
+                })
+                .then_with(|| a.addr.to_vec().cmp(&b.addr.to_vec()))
+        });
+        let page = addrs
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, info)| info.to_owned())
+            .collect();
+        (page, total)
+    }
+
+    /// Dump all info to disk on graceful shutdown
+    ///
+    /// Only the first call writes the dump, later calls are no-ops. It's safe to call right
+    /// after a periodic dump since the files are replaced as a whole.
+    pub fn shutdown_dump<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if self.shutdown_dumped {
+            return Ok(());
+        }
+        self.dump_to_dir(path)?;
+        self.shutdown_dumped = true;
+        Ok(())
+    }
+
+    /// Export the known addresses as JSON
+    pub fn dump_to_writer<W: Write>(&self, w: W) -> Result<()> {
+        let addrs: Vec<ExportedAddr> = self.addr_manager.addrs_iter().map(Into::into).collect();
+        serde_json::to_writer(w, &addrs).map_err(|err| PeerStoreError::Serde(err).into())
+    }
+
+    /// Create a peer store from addresses exported by `dump_to_writer`
+    ///
+    /// Malformed entries are logged and skipped.
+    pub fn load_from_reader<R: Read>(r: R) -> Result<Self> {
+        let mut peer_store = PeerStore::default();
+        for warning in peer_store.import_from_reader(r)? {
+            warn!("Skip peer store import entry: {}", warning);
+        }
+        Ok(peer_store)
+    }
+
+    /// Merge addresses exported by `dump_to_writer` into the store
+    ///
+    /// An address already known keeps the higher score and the most recent connected time.
+    /// Banned addresses are skipped silently, malformed multiaddrs are skipped and returned
+    /// as warnings.
+    pub fn import_from_reader<R: Read>(&mut self, r: R) -> Result<Vec<String>> {
+        let addrs: Vec<ExportedAddr> = serde_json::from_reader(r).map_err(PeerStoreError::Serde)?;
+        let mut warnings = Vec::new();
+        for exported in addrs {
+            let addr: Multiaddr = match exported.addr.parse() {
+                Ok(addr) => addr,
+                Err(err) => {
+                    warnings.push(format!("malformed multiaddr {}: {}", exported.addr, err));
+                    continue;
+                }
+            };
+            if self.ban_list.is_addr_banned(&addr) {
+                continue;
+            }
+            if let Some(info) = self.addr_manager.get_mut(&addr) {
+                info.score = info.score.max(exported.score);
+                if exported.last_connected_at_ms > info.last_connected_at_ms {
+                    info.last_connected_at_ms = exported.last_connected_at_ms;
+                    info.attempts_count = exported.attempts_count;
+                }
+                continue;
+            }
+            self.check_purge()?;
+            let mut info = AddrInfo::new(
+                addr,
+                exported.last_connected_at_ms,
+                exported.score,
+                exported.flags,
+            );
+            info.attempts_count = exported.attempts_count;
+            self.addr_manager.add(info);
+        }
+        Ok(warnings)
+    }
+
+    /// Collect the statistics of the addresses and bans
+    pub fn stats(&self) -> PeerStoreStats {
+        let score_config = self.config.score_config;
+        let mut stats = PeerStoreStats::default();
+        for info in self.addr_manager.addrs_iter() {
+            stats.addr_count += 1;
+            let distribution = &mut stats.score_distribution;
+            if info.score <= score_config.ban_score {
+                stats.low_score_count += 1;
+                distribution.at_or_below_ban += 1;
+            } else if info.score < score_config.default_score {
+                distribution.below_default += 1;
+            } else if info.score == score_config.default_score {
+                distribution.default += 1;
+            } else {
+                distribution.above_default += 1;
+            }
+        }
+        for banned_addr in self.ban_list.active_bans() {
+            stats.active_ban_count += 1;
+            let single_ip_prefix = match banned_addr.address {
+                IpNetwork::V4(_) => 32,
+                IpNetwork::V6(_) => 128,
+            };
+            if banned_addr.address.prefix() < single_ip_prefix {
+                stats.cidr_ban_count += 1;
+            }
+        }
+        stats
+    }
+
+    /// Ban an addr for a protocol violation, `ban_reason` is kept as the note of the ban
     pub(crate) fn ban_addr(&mut self, addr: &Multiaddr, timeout_ms: u64, ban_reason: String) {
+        self.ban_addr_with_score(
+            addr,
+            timeout_ms,
+            BanReason::ProtocolViolation,
+            ban_reason,
+            None,
+        )
+    }
+
+    /// Ban an addr, `score` is recorded in the ban if the addr is banned for a low score
+    ///
+    /// The addr is dropped from the address list unless whitelisted. A whitelisted addr stays
+    /// listed and is not fetched for dialing while banned, so it is dialable again once the ban
+    /// expires.
+    fn ban_addr_with_score(
+        &mut self,
+        addr: &Multiaddr,
+        timeout_ms: u64,
+        reason: BanReason,
+        ban_reason: String,
+        score: Option<Score>,
+    ) {
         if let Some(addr) = multiaddr_to_socketaddr(addr) {
             let network = ip_to_network(addr.ip());
-            self.ban_network(network, timeout_ms, ban_reason)
+            self.ban_network_with_score(network, timeout_ms, reason, ban_reason, score)
+        }
+        if !self.is_whitelisted(addr) {
+            self.addr_manager.remove(addr);
         }
-        self.addr_manager.remove(addr);
     }
 
+    /// Ban a network on request of the operator, `ban_reason` is kept as the note of the ban
     pub(crate) fn ban_network(&mut self, network: IpNetwork, timeout_ms: u64, ban_reason: String) {
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        self.ban_network_with_score(network, timeout_ms, BanReason::Manual, ban_reason, None)
+    }
+
+    fn ban_network_with_score(
+        &mut self,
+        network: IpNetwork,
+        timeout_ms: u64,
+        reason: BanReason,
+        ban_reason: String,
+        score: Option<Score>,
+    ) {
+        let now_ms = self.clock.now_ms();
+        let line = format!(
+            "Ban {} for {} seconds, reason: {}",
+            network,
+            timeout_ms / 1000,
+            ban_reason
+        );
+        for line in self.ban_log.record(now_ms, line) {
+            info!("{}", line);
+        }
         let ban_addr = BannedAddr {
             address: network,
             ban_until: now_ms + timeout_ms,
             created_at: now_ms,
             ban_reason,
+            score,
+            reason,
         };
         self.mut_ban_list().ban(ban_addr);
     }
 
+    /// Re-evaluate the scores against the current `PeerScoreConfig`, after it was changed by
+    /// `set_config`
+    ///
+    /// Addrs scored below the current `ban_score` are banned for a fresh `ban_timeout_ms`, as
+    /// `report` would have done. Bans given for a low score which is no longer below
+    /// `ban_score` are lifted; bans for any other reason are kept. The addrs of lifted bans were
+    /// dropped from the address list when banned, they come back through discovery with the
+    /// default score.
+    pub fn reconcile_scores(&mut self) {
+        let ban_score = self.config.score_config.ban_score;
+        let ban_timeout_ms = self.config.score_config.ban_timeout_ms;
+
+        let lifted: Vec<_> = self
+            .ban_list
+            .get_banned_addrs()
+            .into_iter()
+            .filter(|banned| {
+                banned.reason == BanReason::ScoreBelowThreshold
+                    && banned.score.map_or(false, |score| score >= ban_score)
+            })
+            .collect();
+        for banned in lifted {
+            info!(
+                "Unban {}, its score {:?} is no longer below the ban score {}",
+                banned.address, banned.score, ban_score
+            );
+            self.ban_list.unban_network(&banned.address);
+        }
+
+        let low_scored: Vec<_> = self
+            .addr_manager
+            .addrs_iter()
+            .filter(|info| info.score < ban_score && !self.ban_list.is_addr_banned(&info.addr))
+            .map(|info| (info.addr.clone(), info.score))
+            .collect();
+        for (addr, score) in low_scored {
+            self.ban_addr_with_score(
+                &addr,
+                ban_timeout_ms,
+                BanReason::ScoreBelowThreshold,
+                format!("score {score} is below the ban score {ban_score}"),
+                Some(score),
+            );
+        }
+    }
+
+    /// The bans not expired yet, oldest first, with why and when each was given
+    pub fn list_bans(&self) -> Vec<BannedAddr> {
+        let mut bans: Vec<BannedAddr> = self.ban_list.active_bans().cloned().collect();
+        bans.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+        });
+        bans
+    }
+
     /// Whether the address is banned
     pub fn is_addr_banned(&self, addr: &Multiaddr) -> bool {
         self.ban_list().is_addr_banned(addr)
     }
 
+    /// Count the bans started within `window_ms` before `now_ms`, see `BanList::recent_ban_count`
+    pub fn recent_ban_count(&self, window_ms: u64, now_ms: u64) -> usize {
+        self.ban_list.recent_ban_count(window_ms, now_ms)
+    }
+
     /// Get ban list
     pub fn ban_list(&self) -> &BanList {
         &self.ban_list
@@ -257,10 +968,106 @@ impl PeerStore {
         std::mem::take(&mut self.ban_list);
     }
 
+    /// Log the summaries of the ban and eviction storms over at `now_ms`
+    ///
+    /// A summary is otherwise only logged when the next ban or eviction happens, so this is
+    /// called on a timer to report a storm which just stopped. Returns the logged summaries.
+    pub fn flush_logs(&mut self, now_ms: u64) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(line) = self.ban_log.flush(now_ms) {
+            info!("{}", line);
+            lines.push(line);
+        }
+        if let Some(line) = self.eviction_log.flush(now_ms) {
+            debug!("{}", line);
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// The rate limiter of the ban lines
+    pub(crate) fn ban_log(&self) -> &RateLimitedLog {
+        &self.ban_log
+    }
+
+    fn evict_addr(&mut self, addr: &Multiaddr, now_ms: u64) {
+        self.addr_manager.remove(addr);
+        for line in self
+            .eviction_log
+            .record(now_ms, format!("Evict addr {addr}"))
+        {
+            debug!("{}", line);
+        }
+    }
+
+    /// Remove the addrs never connected after all retries, see `AddrInfo::is_dead`
+    ///
+    /// Addrs connected at least once are left to the usual timeout. Returns the number of
+    /// removed addrs.
+    pub fn prune_dead_addrs(&mut self, now_ms: u64) -> usize {
+        let dead_addrs: Vec<_> = self
+            .addr_manager
+            .addrs_iter()
+            .filter(|info| info.is_dead(&self.config) && !self.is_whitelisted(&info.addr))
+            .map(|info| info.addr.clone())
+            .collect();
+        for addr in &dead_addrs {
+            self.evict_addr(addr, now_ms);
+        }
+        dead_addrs.len()
+    }
+
+    /// Eagerly remove the expired bans and the stale addrs, instead of waiting for a lookup or
+    /// an insert to prune them
+    ///
+    /// Stale addrs are the ones `AddrInfo::is_stale` reports, as in `compact_addrs`.
+    /// Whitelisted addrs and addrs of connected peers are kept. Meant to be called on a timer,
+    /// so an idle node reclaims the memory too.
+    pub fn clear_expired(&mut self, now_ms: u64) -> ClearStats {
+        let expired_bans = self.ban_list.remove_expired(now_ms);
+        let connected: Vec<_> = self
+            .connected_peers
+            .values()
+            .filter_map(|peer| multiaddr_to_socketaddr(&peer.connected_addr))
+            .collect();
+        let stale_addrs: Vec<_> = self
+            .addr_manager
+            .addrs_iter()
+            .filter(|info| {
+                info.is_stale(now_ms, &self.config)
+                    && !self.is_whitelisted(&info.addr)
+                    && multiaddr_to_socketaddr(&info.addr)
+                        .map_or(true, |socket_addr| !connected.contains(&socket_addr))
+            })
+            .map(|info| info.addr.clone())
+            .collect();
+        for addr in &stale_addrs {
+            self.evict_addr(addr, now_ms);
+        }
+        ClearStats {
+            expired_bans,
+            stale_addrs: stale_addrs.len(),
+        }
+    }
+
+    /// Compact the addr manager, see `AddrManager::compact`
+    ///
+    /// Whitelisted addrs and addrs of banned networks are never dropped, and the ban list is
+    /// left as is. Returns the number of removed addrs.
+    pub fn compact_addrs(&mut self) -> usize {
+        let whitelist = &self.whitelist;
+        let ban_list = &self.ban_list;
+        self.addr_manager.compact(|info| {
+            multiaddr_to_socketaddr(&info.addr)
+                .map_or(false, |socket_addr| whitelist.contains_key(&socket_addr))
+                || ban_list.is_addr_banned(&info.addr)
+        })
+    }
+
     /// Check and try delete addrs if reach limit
     /// return Err if peer_store is full and can't be purge
     fn check_purge(&mut self) -> Result<()> {
-        if self.addr_manager.count() < ADDR_COUNT_LIMIT {
+        if self.addr_manager.count() < self.config.addr_count_limit {
             return Ok(());
         }
 
@@ -272,12 +1079,14 @@ impl PeerStore {
         //  2.2. Sort according to the amount of data in the same network segment
         //  2.3. In the network segment with more than 4 peer, randomly evict 2 peer
 
-        let now_ms = ckb_systemtime::unix_time_as_millis();
+        let now_ms = self.clock.now_ms();
         let candidate_peers: Vec<_> = self
             .addr_manager
             .addrs_iter()
             .filter_map(|addr| {
-                if !addr.is_connectable(now_ms) {
+                if (addr.is_dead(&self.config) || !addr.is_connectable(now_ms, &self.config))
+                    && !self.is_whitelisted(&addr.addr)
+                {
                     Some(addr.addr.clone())
                 } else {
                     None
@@ -286,13 +1095,17 @@ impl PeerStore {
             .collect();
 
         for key in candidate_peers.iter() {
-            self.addr_manager.remove(key);
+            self.evict_addr(key, now_ms);
         }
 
         if candidate_peers.is_empty() {
             let candidate_peers: Vec<_> = {
                 let mut peers_by_network_group: HashMap<Group, Vec<_>> = HashMap::default();
-                for addr in self.addr_manager.addrs_iter() {
+                for addr in self
+                    .addr_manager
+                    .addrs_iter()
+                    .filter(|addr| !self.is_whitelisted(&addr.addr))
+                {
                     peers_by_network_group
                         .entry((&addr.addr).into())
                         .or_default()
@@ -328,17 +1141,64 @@ impl PeerStore {
             };
 
             for key in candidate_peers.iter() {
-                self.addr_manager.remove(key);
+                self.evict_addr(key, now_ms);
             }
 
             if candidate_peers.is_empty() {
-                return Err(PeerStoreError::EvictionFailed.into());
+                // only evict a whitelisted addr if every stored addr is whitelisted
+                let all_whitelisted = self
+                    .addr_manager
+                    .addrs_iter()
+                    .all(|addr| self.is_whitelisted(&addr.addr));
+                let victim = self
+                    .addr_manager
+                    .addrs_iter()
+                    .min_by_key(|addr| addr.score)
+                    .map(|addr| addr.addr.clone())
+                    .filter(|_| all_whitelisted);
+                match victim {
+                    Some(addr) => self.evict_addr(&addr, now_ms),
+                    None => return Err(PeerStoreError::EvictionFailed.into()),
+                }
             }
         }
         Ok(())
     }
 }
 
+/// Parse a `<multiaddr> [score]` line of an address list
+fn parse_addr_list_line(line: &str) -> Option<(Multiaddr, Option<Score>)> {
+    let mut parts = line.split_whitespace();
+    let addr: Multiaddr = parts.next()?.parse().ok()?;
+    let score = match parts.next() {
+        Some(score) => Some(score.parse().ok()?),
+        None => None,
+    };
+    if parts.next().is_some() || extract_peer_id(&addr).is_none() {
+        return None;
+    }
+    multiaddr_to_socketaddr(&addr)?;
+    Some((addr, score))
+}
+
+/// Return a filter telling whether a peer is disconnected within the cooldown
+fn cooling_down_filter(
+    recently_disconnected: &HashMap<PeerId, u64>,
+    now_ms: u64,
+    cooldown_ms: u64,
+) -> impl Fn(&PeerId) -> bool + '_ {
+    move |peer_id| {
+        recently_disconnected
+            .get(peer_id)
+            .map(|disconnected_at| is_cooling_down(*disconnected_at, now_ms, cooldown_ms))
+            .unwrap_or_default()
+    }
+}
+
+fn is_cooling_down(disconnected_at_ms: u64, now_ms: u64, cooldown_ms: u64) -> bool {
+    now_ms.saturating_sub(disconnected_at_ms) < cooldown_ms
+}
+
 pub(crate) fn required_flags_filter(required: Flags, t: Flags) -> bool {
     if required == Flags::RELAY | Flags::DISCOVERY | Flags::SYNC {
         t.contains(required) || t.contains(Flags::COMPATIBILITY)
@@ -1,6 +1,6 @@
 //! Type used on peer store
 use crate::{
-    peer_store::{Score, SessionType, ADDR_MAX_FAILURES, ADDR_MAX_RETRIES, ADDR_TIMEOUT_MS},
+    peer_store::{Behaviour, PeerStoreConfig, Score, SessionType},
     Flags,
 };
 use ipnetwork::IpNetwork;
@@ -52,6 +52,21 @@ pub struct AddrInfo {
     /// Flags
     #[serde(default = "default_flags")]
     pub flags: u64,
+    /// Cumulative bytes sent to this peer
+    #[serde(default)]
+    pub bytes_sent: u64,
+    /// Cumulative bytes received from this peer
+    #[serde(default)]
+    pub bytes_received: u64,
+    /// Last time the score decayed
+    #[serde(default)]
+    pub last_decay_at_ms: u64,
+    /// The time this addr was first added to the address manager, kept on later updates
+    ///
+    /// Dumps written before this field existed leave it zero, `AddrManager::add` then falls
+    /// back to the last connected time.
+    #[serde(default)]
+    pub first_seen_at_ms: u64,
 }
 
 fn default_flags() -> u64 {
@@ -69,6 +84,10 @@ impl AddrInfo {
             attempts_count: 0,
             random_id_pos: 0,
             flags,
+            bytes_sent: 0,
+            bytes_received: 0,
+            last_decay_at_ms: 0,
+            first_seen_at_ms: 0,
         }
     }
 
@@ -82,25 +101,59 @@ impl AddrInfo {
         self.last_tried_at_ms >= now_ms.saturating_sub(60_000)
     }
 
-    /// Whether connectable peer
-    pub fn is_connectable(&self, now_ms: u64) -> bool {
+    /// Whether the addr was never connected and has used up all `config.addr_max_retries`
+    ///
+    /// Such addr is given up even if it was tried in the last minute.
+    pub fn is_dead(&self, config: &PeerStoreConfig) -> bool {
+        self.last_connected_at_ms == 0 && self.attempts_count >= config.addr_max_retries
+    }
+
+    /// Whether the addr was never connected, first seen more than `config.addr_timeout_ms`
+    /// before `now_ms` and not tried in the last minute
+    ///
+    /// Stale addrs are the ones dropped by `PeerStore::compact_addrs` and
+    /// `PeerStore::clear_expired`.
+    pub fn is_stale(&self, now_ms: u64, config: &PeerStoreConfig) -> bool {
+        self.last_connected_at_ms == 0
+            && now_ms.saturating_sub(self.first_seen_at_ms) > config.addr_timeout_ms
+            && !self.tried_in_last_minute(now_ms)
+    }
+
+    /// Whether connectable peer, the retry and failure thresholds are read from `config`
+    pub fn is_connectable(&self, now_ms: u64, config: &PeerStoreConfig) -> bool {
         // do not remove addr tried in last minute
         if self.tried_in_last_minute(now_ms) {
             return true;
         }
         // we give up if never connect to this addr
-        if self.last_connected_at_ms == 0 && self.attempts_count >= ADDR_MAX_RETRIES {
+        if self.is_dead(config) {
             return false;
         }
         // consider addr is not connectable if failed too many times
-        if now_ms.saturating_sub(self.last_connected_at_ms) > ADDR_TIMEOUT_MS
-            && (self.attempts_count >= ADDR_MAX_FAILURES)
+        if now_ms.saturating_sub(self.last_connected_at_ms) > config.addr_timeout_ms
+            && (self.attempts_count >= config.addr_max_failures)
         {
             return false;
         }
         true
     }
 
+    /// The time until which the addr is not dialed again after its recent failed attempts
+    ///
+    /// Attempts are reset on connection, so all of them have failed. The window starts at
+    /// `config.addr_backoff_base_ms` after the first failure, doubles with every further one
+    /// and is counted from the last try. Zero if there is no failed attempt.
+    pub fn backoff_until_ms(&self, config: &PeerStoreConfig) -> u64 {
+        match self.attempts_count {
+            0 => 0,
+            attempts => {
+                let factor = 1u64 << (attempts - 1).min(63);
+                self.last_tried_at_ms
+                    .saturating_add(config.addr_backoff_base_ms.saturating_mul(factor))
+            }
+        }
+    }
+
     /// Try dail count
     pub fn mark_tried(&mut self, tried_at_ms: u64) {
         self.last_tried_at_ms = tried_at_ms;
@@ -114,12 +167,142 @@ impl AddrInfo {
         self.attempts_count = 0;
     }
 
+    /// Accumulate bytes transferred with this peer
+    pub fn record_traffic(&mut self, sent: u64, received: u64) {
+        self.bytes_sent = self.bytes_sent.saturating_add(sent);
+        self.bytes_received = self.bytes_received.saturating_add(received);
+    }
+
+    /// Move the score toward `default_score` by `step` for every full `interval_ms` elapsed since
+    /// the last decay
+    ///
+    /// The first call only starts the decay timer.
+    pub fn decay_score(
+        &mut self,
+        now_ms: u64,
+        default_score: Score,
+        step: Score,
+        interval_ms: u64,
+    ) {
+        if self.last_decay_at_ms == 0 || interval_ms == 0 {
+            self.last_decay_at_ms = now_ms;
+            return;
+        }
+        let intervals = now_ms.saturating_sub(self.last_decay_at_ms) / interval_ms;
+        if intervals == 0 {
+            return;
+        }
+        self.last_decay_at_ms += intervals * interval_ms;
+        let delta = Score::try_from(intervals)
+            .unwrap_or(Score::MAX)
+            .saturating_mul(step);
+        self.score = if self.score < default_score {
+            self.score.saturating_add(delta).min(default_score)
+        } else {
+            self.score.saturating_sub(delta).max(default_score)
+        };
+    }
+
     /// Change address flags
     pub fn flags(&mut self, flags: Flags) {
         self.flags = flags.bits();
     }
 }
 
+/// Address entry of the peer store JSON export
+///
+/// The multiaddr is kept as a string, so a malformed entry doesn't fail the whole import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedAddr {
+    /// Multiaddr
+    pub addr: String,
+    /// Last connected time
+    pub last_connected_at_ms: u64,
+    /// Score about this addr
+    pub score: Score,
+    /// Attempts count
+    pub attempts_count: u32,
+    /// Flags
+    #[serde(default = "default_flags")]
+    pub flags: u64,
+}
+
+impl From<&AddrInfo> for ExportedAddr {
+    fn from(info: &AddrInfo) -> Self {
+        ExportedAddr {
+            addr: info.addr.to_string(),
+            last_connected_at_ms: info.last_connected_at_ms,
+            score: info.score,
+            attempts_count: info.attempts_count,
+            flags: info.flags,
+        }
+    }
+}
+
+/// Peer store statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PeerStoreStats {
+    /// The count of known addresses
+    pub addr_count: usize,
+    /// The count of addresses with a score at or below the ban score
+    pub low_score_count: usize,
+    /// The count of bans not expired yet
+    pub active_ban_count: usize,
+    /// The count of active bans on a subnet rather than a single ip
+    pub cidr_ban_count: usize,
+    /// Distribution of the address scores
+    pub score_distribution: ScoreDistribution,
+}
+
+/// Entries removed by `PeerStore::clear_expired`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ClearStats {
+    /// The count of removed bans which had expired
+    pub expired_bans: usize,
+    /// The count of removed addresses never connected within the address timeout
+    pub stale_addrs: usize,
+}
+
+/// Address counts bucketed by score, relative to the scoring configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScoreDistribution {
+    /// Scores at or below the ban score
+    pub at_or_below_ban: usize,
+    /// Scores above the ban score but below the default score
+    pub below_default: usize,
+    /// Scores equal to the default score
+    pub default: usize,
+    /// Scores above the default score
+    pub above_default: usize,
+}
+
+/// Event sent to the ban observer when a reported peer gets banned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BanEvent {
+    /// The banned address
+    pub addr: Multiaddr,
+    /// The behaviour triggered the ban
+    pub behaviour: Behaviour,
+    /// The score after the behaviour is applied
+    pub score: Score,
+    /// Ban until time
+    pub ban_until: u64,
+}
+
+/// Why an address was banned
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum BanReason {
+    /// Its score dropped below `PeerScoreConfig::ban_score`
+    ScoreBelowThreshold,
+    /// Banned by the operator, e.g. by the `set_ban` RPC
+    Manual,
+    /// Banned by a protocol for misbehaving
+    ProtocolViolation,
+    /// Any other reason, and bans persisted before the reason was recorded
+    #[default]
+    Other,
+}
+
 /// Banned addr info
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct BannedAddr {
@@ -127,10 +310,16 @@ pub struct BannedAddr {
     pub address: IpNetwork,
     /// Ban until time
     pub ban_until: u64,
-    /// Ban reason
+    /// Free-text note on the ban, see `reason` for its category
     pub ban_reason: String,
     /// Ban time
     pub created_at: u64,
+    /// The score that got the address banned, `None` if it was not banned for a low score
+    #[serde(default)]
+    pub score: Option<Score>,
+    /// Why the address was banned
+    #[serde(default)]
+    pub reason: BanReason,
 }
 
 /// Convert multiaddr to IpNetwork
@@ -10,7 +10,7 @@ use p2p::{
     context::{ProtocolContext, ProtocolContextMutRef, SessionContext},
     multiaddr::Multiaddr,
     traits::ServiceProtocol,
-    utils::{is_reachable, multiaddr_to_socketaddr},
+    utils::{extract_peer_id, is_reachable, multiaddr_to_socketaddr},
     SessionId,
 };
 use rand::seq::SliceRandom;
@@ -341,15 +341,23 @@ impl AddressManager for DiscoveryAddressManager {
         self.add_new_addrs(session_id, vec![addr])
     }
 
-    fn add_new_addrs(&mut self, _session_id: SessionId, addrs: Vec<(Multiaddr, Flags)>) {
+    fn add_new_addrs(&mut self, session_id: SessionId, addrs: Vec<(Multiaddr, Flags)>) {
         if addrs.is_empty() {
             return;
         }
 
+        let source = self.network_state.with_peer_registry(|reg| {
+            reg.get_peer(session_id)
+                .and_then(|peer| extract_peer_id(&peer.connected_addr))
+        });
         for (addr, flags) in addrs.into_iter().filter(|addr| self.is_valid_addr(&addr.0)) {
             trace!("Add discovered address:{:?}", addr);
             self.network_state.with_peer_store_mut(|peer_store| {
-                if let Err(err) = peer_store.add_addr(addr.clone(), flags) {
+                let result = match source.clone() {
+                    Some(source) => peer_store.add_addr_from(addr.clone(), flags, source),
+                    None => peer_store.add_addr(addr.clone(), flags),
+                };
+                if let Err(err) = result {
                     debug!(
                         "Failed to add discovered address to peer_store {:?} {:?}",
                         err, addr
@@ -40,7 +40,12 @@ impl DumpPeerStoreService {
 impl Drop for DumpPeerStoreService {
     fn drop(&mut self) {
         debug!("Dump peer store before exiting");
-        self.dump_peer_store();
+        let path = self.network_state.config.peer_store_path();
+        self.network_state.with_peer_store_mut(|peer_store| {
+            if let Err(err) = peer_store.shutdown_dump(&path) {
+                warn!("Dump peer store error, path: {:?} error: {}", path, err);
+            }
+        });
     }
 }
 
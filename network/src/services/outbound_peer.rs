@@ -147,6 +147,13 @@ impl OutboundPeerService {
         self.network_state
             .try_dial_observed_addrs(&self.p2p_control);
     }
+
+    fn flush_peer_store_logs(&self) {
+        self.network_state.with_peer_store_mut(|peer_store| {
+            let now_ms = peer_store.now_ms();
+            peer_store.flush_logs(now_ms);
+        });
+    }
 }
 
 impl Future for OutboundPeerService {
@@ -171,6 +178,8 @@ impl Future for OutboundPeerService {
             self.try_dial_peers();
             // try dial observed addrs
             self.try_dial_observed();
+            // report the ban and eviction storms just over
+            self.flush_peer_store_logs();
         }
         Poll::Pending
     }
@@ -1,10 +1,12 @@
+use super::MockClock;
 use crate::{
     multiaddr::Multiaddr,
-    peer_store::{addr_manager::AddrManager, types::AddrInfo},
+    peer_store::{addr_manager::AddrManager, types::AddrInfo, PeerStoreConfig, TransportKind},
     PeerId,
 };
 use proptest::prelude::*;
 use std::net::Ipv4Addr;
+use std::sync::Arc;
 
 const MAX_FETCHED_ADDRS: usize = 1000;
 const RANDOM_REMOVE_ADDRS: usize = 500;
@@ -40,3 +42,177 @@ proptest! {
         assert_eq!(addrs.len(), count);
     }
 }
+
+#[test]
+fn test_addrs_by_transport() {
+    let peer_id = PeerId::random().to_base58();
+    let tcp: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{peer_id}")
+        .parse()
+        .unwrap();
+    let ws: Multiaddr = format!("/ip4/127.0.0.2/tcp/42/ws/p2p/{peer_id}")
+        .parse()
+        .unwrap();
+    let wss: Multiaddr = format!("/ip6/::1/tcp/443/wss/p2p/{peer_id}")
+        .parse()
+        .unwrap();
+    let tls_ws: Multiaddr = format!("/ip4/127.0.0.3/tcp/443/tls/example.com/ws/p2p/{peer_id}")
+        .parse()
+        .unwrap();
+    let onion: Multiaddr = "/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:1234"
+        .parse()
+        .unwrap();
+    let no_port: Multiaddr = "/ip4/127.0.0.4".parse().unwrap();
+
+    assert_eq!(TransportKind::of(&tcp), TransportKind::Tcp);
+    assert_eq!(TransportKind::of(&ws), TransportKind::Ws);
+    assert_eq!(TransportKind::of(&wss), TransportKind::Wss);
+    assert_eq!(TransportKind::of(&tls_ws), TransportKind::Wss);
+    assert_eq!(TransportKind::of(&onion), TransportKind::Onion);
+    assert_eq!(TransportKind::of(&no_port), TransportKind::Other);
+
+    let mut addr_manager = AddrManager::default();
+    for addr in [&tcp, &ws, &wss, &tls_ws] {
+        addr_manager.add(AddrInfo::new(addr.clone(), 0, 0, 0));
+    }
+    assert_eq!(
+        addr_manager.addrs_by_transport(TransportKind::Tcp),
+        vec![tcp]
+    );
+    assert_eq!(addr_manager.addrs_by_transport(TransportKind::Ws), vec![ws]);
+    let mut wss_addrs = addr_manager.addrs_by_transport(TransportKind::Wss);
+    wss_addrs.sort_by_key(|addr| addr.to_string());
+    let mut expected = vec![wss, tls_ws];
+    expected.sort_by_key(|addr| addr.to_string());
+    assert_eq!(wss_addrs, expected);
+    assert!(addr_manager
+        .addrs_by_transport(TransportKind::Onion)
+        .is_empty());
+    assert_eq!(addr_manager.count(), 4);
+}
+
+#[test]
+fn test_add_addr_from_enforces_source_quota() {
+    fn new_addr(id: u8) -> AddrInfo {
+        let addr: Multiaddr = format!("/ip4/10.0.0.{id}/tcp/42").parse().unwrap();
+        AddrInfo::new(addr, 0, 0, 0)
+    }
+
+    let mut addr_manager = AddrManager::default();
+    addr_manager.set_config(PeerStoreConfig {
+        addr_max_per_source: 2,
+        ..Default::default()
+    });
+    let spammer = PeerId::random();
+    let honest = PeerId::random();
+
+    addr_manager.add_addr_from(new_addr(1), spammer.clone());
+    addr_manager.add_addr_from(new_addr(2), spammer.clone());
+    addr_manager.add_addr_from(new_addr(3), honest.clone());
+    assert_eq!(addr_manager.source_addr_count(&spammer), 2);
+    assert_eq!(addr_manager.source_addr_count(&honest), 1);
+
+    // the oldest addr of the source is evicted
+    addr_manager.add_addr_from(new_addr(4), spammer.clone());
+    assert_eq!(addr_manager.source_addr_count(&spammer), 2);
+    assert!(addr_manager.get(&new_addr(1).addr).is_none());
+    assert!(addr_manager.get(&new_addr(2).addr).is_some());
+    assert!(addr_manager.get(&new_addr(3).addr).is_some());
+    assert_eq!(addr_manager.count(), 3);
+
+    // a connected addr is exempt from the quota
+    addr_manager
+        .get_mut(&new_addr(2).addr)
+        .unwrap()
+        .last_connected_at_ms = 1;
+    assert_eq!(addr_manager.source_addr_count(&spammer), 1);
+    addr_manager.add_addr_from(new_addr(5), spammer.clone());
+    addr_manager.add_addr_from(new_addr(6), spammer.clone());
+    assert!(addr_manager.get(&new_addr(2).addr).is_some());
+    assert!(addr_manager.get(&new_addr(4).addr).is_none());
+    assert_eq!(addr_manager.source_addr_count(&spammer), 2);
+    assert_eq!(addr_manager.count(), 4);
+}
+
+#[test]
+fn test_first_seen_at_is_kept_on_reinsert() {
+    let addr: Multiaddr = "/ip4/10.0.0.1/tcp/42".parse().unwrap();
+    let clock = Arc::new(MockClock::default());
+    let mut addr_manager = AddrManager::default();
+    addr_manager.set_clock(clock.clone());
+
+    clock.set(1_000);
+    addr_manager.add(AddrInfo::new(addr.clone(), 0, 0, 0));
+    assert_eq!(addr_manager.get(&addr).unwrap().first_seen_at_ms, 1_000);
+
+    // a newer record replaces the entry but not its first seen time
+    clock.set(5_000);
+    addr_manager.add(AddrInfo::new(addr.clone(), 4_000, 0, 0));
+    let addr_info = addr_manager.get(&addr).unwrap();
+    assert_eq!(addr_info.last_connected_at_ms, 4_000);
+    assert_eq!(addr_info.first_seen_at_ms, 1_000);
+
+    // re-added after removal, it is seen for the first time again
+    addr_manager.remove(&addr);
+    addr_manager.add(AddrInfo::new(addr.clone(), 0, 0, 0));
+    assert_eq!(addr_manager.get(&addr).unwrap().first_seen_at_ms, 5_000);
+
+    // survives a dump and load
+    let mut dumped = tempfile::tempfile().unwrap();
+    addr_manager.dump(dumped.try_clone().unwrap()).unwrap();
+    std::io::Seek::rewind(&mut dumped).unwrap();
+    let loaded = AddrManager::load(std::io::BufReader::new(dumped)).unwrap();
+    assert_eq!(loaded.get(&addr).unwrap().first_seen_at_ms, 5_000);
+
+    // dumps without the field fall back to the last connected time
+    let legacy = r#"[{"addr":"/ip4/10.0.0.1/tcp/42","score":60,"last_connected_at_ms":4000,"last_tried_at_ms":0,"attempts_count":0,"random_id_pos":0,"flags":1}]"#;
+    let loaded = AddrManager::load(legacy.as_bytes()).unwrap();
+    assert_eq!(loaded.get(&addr).unwrap().first_seen_at_ms, 4_000);
+}
+
+#[test]
+fn test_recently_active_addrs() {
+    let addr = |i: u8| -> Multiaddr { format!("/ip4/10.0.0.{i}/tcp/42").parse().unwrap() };
+    let mut addr_manager = AddrManager::default();
+    // (last_connected_at_ms, score)
+    for (i, (last_connected_at_ms, score)) in [
+        (9_000, 10),
+        (8_000, 50),
+        (5_000, 100),
+        (0, 200),
+        (9_500, 50),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        addr_manager.add(AddrInfo::new(
+            addr(i as u8 + 1),
+            last_connected_at_ms,
+            score,
+            1,
+        ));
+    }
+
+    // never connected addresses are skipped whatever the window
+    assert_eq!(
+        addr_manager.recently_active_addrs(u64::MAX, 10_000, 10),
+        vec![addr(3), addr(5), addr(2), addr(1)]
+    );
+    assert_eq!(
+        addr_manager.recently_active_addrs(2_000, 10_000, 10),
+        vec![addr(5), addr(2), addr(1)]
+    );
+    assert_eq!(
+        addr_manager.recently_active_addrs(1_000, 10_000, 10),
+        vec![addr(5), addr(1)]
+    );
+    assert!(addr_manager
+        .recently_active_addrs(100, 10_000, 10)
+        .is_empty());
+    assert_eq!(
+        addr_manager.recently_active_addrs(2_000, 10_000, 2),
+        vec![addr(5), addr(2)]
+    );
+    assert!(addr_manager
+        .recently_active_addrs(u64::MAX, 10_000, 0)
+        .is_empty());
+}
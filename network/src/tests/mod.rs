@@ -24,3 +24,19 @@ fn random_addr_v6() -> crate::multiaddr::Multiaddr {
     ));
     multi_addr
 }
+
+/// A clock that only moves when told to
+#[derive(Default)]
+struct MockClock(std::sync::atomic::AtomicU64);
+
+impl MockClock {
+    fn set(&self, now_ms: u64) {
+        self.0.store(now_ms, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl crate::peer_store::Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
@@ -1,14 +1,24 @@
-use super::{random_addr, random_addr_v6};
+use super::{random_addr, random_addr_v6, MockClock};
 use crate::{
     extract_peer_id,
     multiaddr::Multiaddr,
     peer_store::{
-        ban_list::CLEAR_INTERVAL_COUNTER, types::multiaddr_to_ip_network, PeerStore, Status,
-        ADDR_COUNT_LIMIT, ADDR_TRY_TIMEOUT_MS,
+        addr_manager::AddrManager,
+        ban_list::CLEAR_INTERVAL_COUNTER,
+        log_limiter::RateLimitedLog,
+        types::{
+            multiaddr_to_ip_network, AddrInfo, BanEvent, BanReason, BannedAddr, ClearStats,
+            PeerStoreStats, ScoreDistribution,
+        },
+        LogRateLimitConfig, PeerSort, PeerStore, PeerStoreConfig, ReportResult, ScorePenalties,
+        Status, ADDR_COUNT_LIMIT, ADDR_TRY_TIMEOUT_MS,
     },
-    Behaviour, Flags, PeerId, SessionType,
+    Behaviour, Flags, Group, PeerId, SessionType,
 };
+use ipnetwork::IpNetwork;
+use rand::{rngs::StdRng, SeedableRng};
 use std::collections::HashSet;
+use std::sync::Arc;
 
 #[test]
 fn test_add_connected_peer() {
@@ -71,6 +81,72 @@ fn test_report() {
     assert!(peer_store.addr_manager().get(&addr).is_none())
 }
 
+#[test]
+fn test_report_score() {
+    let mut peer_store: PeerStore = Default::default();
+    let addr = random_addr_v6();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+
+    for behaviour in [Behaviour::TestGood, Behaviour::TestBad, Behaviour::TestBad] {
+        let report = peer_store.report(&addr, behaviour);
+        assert!(report.is_ok());
+        assert_eq!(report.behaviour, behaviour);
+        assert_eq!(
+            report.score,
+            Some(peer_store.addr_manager().get(&addr).unwrap().score)
+        );
+    }
+
+    let mut report = peer_store.report(&addr, Behaviour::TestBad);
+    while report.is_ok() {
+        report = peer_store.report(&addr, Behaviour::TestBad);
+    }
+    assert_eq!(report.result, ReportResult::Banned);
+    assert!(report.score.unwrap() < peer_store.config().score_config.ban_score);
+
+    let unknown = peer_store.report(&random_addr_v6(), Behaviour::TestBad);
+    assert!(unknown.is_ok());
+    assert_eq!(unknown.score, None);
+}
+
+#[test]
+fn test_simulate_report() {
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_score_penalties(
+        ScorePenalties::new_builder()
+            .penalty(Behaviour::TestBad, -50)
+            .build(),
+    );
+    let addr = random_addr_v6();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+
+    let simulated = peer_store.simulate_report(&addr, Behaviour::TestBad);
+    assert_eq!(simulated.score, Some(50));
+    assert!(simulated.is_ok());
+    // nothing is applied
+    assert_eq!(peer_store.addr_manager().get(&addr).unwrap().score, 100);
+    assert_eq!(
+        peer_store.simulate_report(&addr, Behaviour::TestBad),
+        simulated
+    );
+
+    // the real report agrees with the simulation
+    assert_eq!(peer_store.report(&addr, Behaviour::TestBad), simulated);
+    let simulated = peer_store.simulate_report(&addr, Behaviour::TestBad);
+    assert!(simulated.is_banned());
+    assert!(!peer_store.is_addr_banned(&addr));
+    assert_eq!(peer_store.report(&addr, Behaviour::TestBad), simulated);
+    assert!(peer_store.is_addr_banned(&addr));
+
+    let unknown = peer_store.simulate_report(&random_addr_v6(), Behaviour::TestBad);
+    assert!(unknown.is_ok());
+    assert_eq!(unknown.score, None);
+}
+
 #[test]
 fn test_update_status() {
     let mut peer_store: PeerStore = Default::default();
@@ -121,6 +197,122 @@ fn test_ban_peer() {
     assert_eq!(peer_store.ban_list().count(), 1)
 }
 
+#[test]
+fn test_ban_expiry_with_mock_clock() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+
+    let addr = random_addr();
+    peer_store.ban_addr(&addr, 10_000, "no reason".into());
+    assert!(peer_store.is_addr_banned(&addr));
+    assert_eq!(
+        peer_store.ban_list().get_banned_addrs()[0].ban_until,
+        11_000
+    );
+
+    clock.set(10_999);
+    assert!(peer_store.is_addr_banned(&addr));
+
+    clock.set(11_000);
+    assert!(!peer_store.is_addr_banned(&addr));
+}
+
+#[test]
+fn test_recent_ban_count() {
+    let clock = Arc::new(MockClock::default());
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+
+    // bans started at 1s, 10s, 19s and 20s, the first one already expired at 20s
+    let mut addrs = Vec::new();
+    for (i, (created_at, timeout_ms)) in [(1_000, 5_000), (10_000, 60_000), (19_000, 60_000)]
+        .into_iter()
+        .enumerate()
+    {
+        clock.set(created_at);
+        let addr: Multiaddr = format!(
+            "/ip4/192.168.0.{}/tcp/42/p2p/{}",
+            i + 1,
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap();
+        peer_store.ban_addr(&addr, timeout_ms, "no reason".into());
+        addrs.push(addr);
+    }
+    clock.set(20_000);
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.0.0/24".parse().unwrap(), 60_000);
+
+    assert_eq!(peer_store.recent_ban_count(0, 20_000), 1);
+    assert_eq!(peer_store.recent_ban_count(1_000, 20_000), 2);
+    assert_eq!(peer_store.recent_ban_count(10_000, 20_000), 3);
+    assert_eq!(peer_store.recent_ban_count(19_000, 20_000), 4);
+    assert_eq!(peer_store.recent_ban_count(u64::MAX, 20_000), 4);
+    // bans started after `now_ms` are outside the window
+    assert_eq!(peer_store.recent_ban_count(10_000, 15_000), 1);
+
+    // removed bans drop out
+    let network = multiaddr_to_ip_network(&addrs[0]).unwrap();
+    peer_store.mut_ban_list().unban_network(&network);
+    assert_eq!(peer_store.recent_ban_count(u64::MAX, 20_000), 3);
+}
+
+#[test]
+fn test_ban_cidr() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+
+    let addr: Multiaddr = format!("/ip4/10.0.0.5/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    let other_addr: Multiaddr =
+        format!("/ip4/10.0.1.5/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap();
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.0.77/24".parse().unwrap(), 20_000);
+    assert!(peer_store.is_addr_banned(&addr));
+    assert!(!peer_store.is_addr_banned(&other_addr));
+    assert_eq!(
+        peer_store.ban_list().get_banned_addrs()[0].address,
+        "10.0.0.0/24".parse::<IpNetwork>().unwrap()
+    );
+
+    // the soonest expiring ban is returned
+    peer_store.ban_addr(&addr, 10_000, "no reason".into());
+    assert_eq!(
+        peer_store.ban_list().get_addr_ban(&addr).unwrap().ban_until,
+        11_000
+    );
+    clock.set(11_000);
+    assert_eq!(
+        peer_store.ban_list().get_addr_ban(&addr).unwrap().ban_until,
+        21_000
+    );
+    clock.set(21_000);
+    assert!(peer_store.ban_list().get_addr_ban(&addr).is_none());
+    assert!(!peer_store.is_addr_banned(&addr));
+
+    let addr_v6: Multiaddr = format!(
+        "/ip6/2001:db8::1/tcp/42/p2p/{}",
+        PeerId::random().to_base58()
+    )
+    .parse()
+    .unwrap();
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("2001:db8::/32".parse().unwrap(), 10_000);
+    assert!(peer_store.is_addr_banned(&addr_v6));
+    assert!(!peer_store.is_addr_banned(&random_addr_v6()));
+}
+
 #[test]
 fn test_attempt_ban() {
     let _faketime_guard = ckb_systemtime::faketime();
@@ -186,6 +378,49 @@ fn test_fetch_addrs_to_attempt() {
         .is_empty());
 }
 
+#[test]
+fn test_fetch_addrs_to_attempt_after_disconnect_cooldown() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000_000);
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+    peer_store.set_disconnect_cooldown_ms(60_000);
+
+    let addr = random_addr();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&addr)
+        .unwrap()
+        .mark_connected(900_000);
+    peer_store.add_connected_peer(addr.clone(), SessionType::Outbound);
+    peer_store.remove_disconnected_peer(&addr);
+
+    // excluded during the cooldown
+    clock.set(1_030_000);
+    assert!(peer_store
+        .fetch_addrs_to_attempt(1, Flags::COMPATIBILITY)
+        .is_empty());
+    assert!(peer_store
+        .fetch_random_addrs(1, Flags::COMPATIBILITY)
+        .is_empty());
+
+    // eligible after the cooldown
+    clock.set(1_060_000);
+    assert_eq!(
+        peer_store
+            .fetch_addrs_to_attempt(1, Flags::COMPATIBILITY)
+            .len(),
+        1
+    );
+    assert_eq!(
+        peer_store.fetch_random_addrs(1, Flags::COMPATIBILITY).len(),
+        1
+    );
+}
+
 #[test]
 fn test_fetch_addrs_to_attempt_or_feeler() {
     let _faketime_guard = ckb_systemtime::faketime();
@@ -529,13 +764,13 @@ fn test_eviction() {
         paddr.mark_tried(tried_ms);
         paddr.mark_tried(tried_ms);
         paddr.mark_tried(tried_ms);
-        assert!(!paddr.is_connectable(now));
+        assert!(!paddr.is_connectable(now, &PeerStoreConfig::default()));
     }
     if let Some(paddr) = peer_store.mut_addr_manager().get_mut(&evict_addr_2) {
         paddr.mark_tried(tried_ms);
         paddr.mark_tried(tried_ms);
         paddr.mark_tried(tried_ms);
-        assert!(!paddr.is_connectable(now));
+        assert!(!paddr.is_connectable(now, &PeerStoreConfig::default()));
     }
     // should evict evict_addr and accept new_peer
     let new_peer_addr: Multiaddr =
@@ -570,6 +805,93 @@ fn test_eviction() {
     assert!(peer_store.mut_addr_manager().get(&new_peer_addr).is_some());
 }
 
+#[test]
+fn test_eviction_with_config() {
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        addr_count_limit: 2,
+        addr_max_retries: 1,
+        ..Default::default()
+    });
+    let now = ckb_systemtime::unix_time_as_millis();
+    let addr1 = random_addr();
+    let addr2 = random_addr();
+    peer_store
+        .add_addr(addr1.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    peer_store
+        .add_addr(addr2.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+
+    // a single failed attempt is enough to give up the address
+    let config = *peer_store.config();
+    let paddr = peer_store.mut_addr_manager().get_mut(&addr1).unwrap();
+    paddr.mark_tried(now - 61_000);
+    assert!(paddr.is_connectable(now, &PeerStoreConfig::default()));
+    assert!(!paddr.is_connectable(now, &config));
+
+    let addr3 = random_addr();
+    peer_store
+        .add_addr(addr3.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    assert!(peer_store.addr_manager().get(&addr1).is_none());
+    assert!(peer_store.addr_manager().get(&addr2).is_some());
+    assert!(peer_store.addr_manager().get(&addr3).is_some());
+}
+
+#[test]
+fn test_prune_dead_addrs() {
+    let now = ckb_systemtime::unix_time_as_millis();
+    let never_connected = random_addr();
+    let once_good = random_addr();
+    // both addrs are exhausted and were tried just now, only one ever connected
+    let new_peer_store = |config: PeerStoreConfig| {
+        let mut peer_store = PeerStore::with_config(config);
+        for addr in [&never_connected, &once_good] {
+            peer_store
+                .add_addr(addr.clone(), Flags::COMPATIBILITY)
+                .unwrap();
+        }
+        peer_store
+            .mut_addr_manager()
+            .get_mut(&once_good)
+            .unwrap()
+            .mark_connected(now - config.addr_timeout_ms - 1);
+        for addr in [&never_connected, &once_good] {
+            let paddr = peer_store.mut_addr_manager().get_mut(addr).unwrap();
+            for _ in 0..config.addr_max_retries {
+                paddr.mark_tried(now);
+            }
+            assert!(paddr.is_connectable(now, &config));
+        }
+        peer_store
+    };
+
+    let config = PeerStoreConfig::default();
+    let mut peer_store = new_peer_store(config);
+    let addr_info = |peer_store: &PeerStore, addr| peer_store.addr_manager().get(addr).cloned();
+    assert!(addr_info(&peer_store, &never_connected)
+        .unwrap()
+        .is_dead(&config));
+    assert!(!addr_info(&peer_store, &once_good).unwrap().is_dead(&config));
+    assert_eq!(peer_store.prune_dead_addrs(now), 1);
+    assert!(addr_info(&peer_store, &never_connected).is_none());
+    assert!(addr_info(&peer_store, &once_good).is_some());
+    assert_eq!(peer_store.prune_dead_addrs(now), 0);
+
+    // the eviction path gives up the never connected addr first
+    let mut peer_store = new_peer_store(PeerStoreConfig {
+        addr_count_limit: 2,
+        ..Default::default()
+    });
+    let addr = random_addr();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    assert!(addr_info(&peer_store, &never_connected).is_none());
+    assert!(addr_info(&peer_store, &once_good).is_some());
+    assert!(addr_info(&peer_store, &addr).is_some());
+}
+
 #[test]
 fn test_addr_unique() {
     let mut peer_store = PeerStore::default();
@@ -588,3 +910,1008 @@ fn test_addr_unique() {
 
     assert_eq!(peer_store.addr_manager().addrs_iter().count(), 2);
 }
+
+#[test]
+fn test_has_connected_in_subnet() {
+    let mut peer_store = PeerStore::default();
+    let addr: Multiaddr = format!(
+        "/ip4/192.168.1.10/tcp/42/p2p/{}",
+        PeerId::random().to_base58()
+    )
+    .parse()
+    .unwrap();
+    let subnet: IpNetwork = "192.168.1.0/24".parse().unwrap();
+    let other_subnet: IpNetwork = "192.168.2.0/24".parse().unwrap();
+    assert!(!peer_store.has_connected_in_subnet(subnet));
+
+    peer_store.add_connected_peer(addr.clone(), SessionType::Outbound);
+    assert!(peer_store.has_connected_in_subnet(subnet));
+    assert!(!peer_store.has_connected_in_subnet(other_subnet));
+
+    peer_store.remove_disconnected_peer(&addr);
+    assert!(!peer_store.has_connected_in_subnet(subnet));
+}
+
+#[test]
+fn test_list_peers_pagination() {
+    let mut peer_store = PeerStore::default();
+    for i in 0..25u8 {
+        let addr: Multiaddr = format!(
+            "/ip4/10.0.{}.1/tcp/42/p2p/{}",
+            i,
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap();
+        peer_store
+            .add_addr(addr.clone(), Flags::COMPATIBILITY)
+            .unwrap();
+        // only a few distinct scores, so most of the ordering comes from the tie-break
+        peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = i32::from(i % 3);
+    }
+
+    for sort in [
+        PeerSort::Score,
+        PeerSort::LastConnected,
+        PeerSort::FailureCount,
+    ] {
+        let mut seen = HashSet::new();
+        let mut offset = 0;
+        loop {
+            let (page, total) = peer_store.list_peers(offset, 10, sort);
+            assert_eq!(total, 25);
+            if page.is_empty() {
+                break;
+            }
+            for info in page.iter() {
+                assert!(seen.insert(info.addr.clone()));
+            }
+            offset += page.len();
+        }
+        assert_eq!(seen.len(), 25);
+    }
+
+    let (page, _) = peer_store.list_peers(0, 25, PeerSort::Score);
+    assert!(page.windows(2).all(|w| w[0].score >= w[1].score));
+    assert_eq!(peer_store.list_peers(0, 25, PeerSort::Score).0, page);
+}
+
+#[test]
+fn test_top_addrs() {
+    let mut peer_store = PeerStore::default();
+    let mut addrs = Vec::new();
+    // (score, last_connected_at_ms)
+    for (i, (score, last_connected_at_ms)) in [(100, 10), (100, 30), (50, 99), (100, 20), (200, 40)]
+        .into_iter()
+        .enumerate()
+    {
+        let addr: Multiaddr = format!(
+            "/ip4/10.0.{}.1/tcp/42/p2p/{}",
+            i,
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap();
+        peer_store
+            .add_addr(addr.clone(), Flags::COMPATIBILITY)
+            .unwrap();
+        let info = peer_store.mut_addr_manager().get_mut(&addr).unwrap();
+        info.score = score;
+        info.last_connected_at_ms = last_connected_at_ms;
+        addrs.push(addr);
+    }
+    // the best scored one is banned
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.4.0/24".parse().unwrap(), 60_000);
+
+    assert!(peer_store.top_addrs(0).is_empty());
+    assert_eq!(
+        peer_store.top_addrs(3),
+        vec![addrs[1].clone(), addrs[3].clone(), addrs[0].clone()]
+    );
+    assert_eq!(
+        peer_store.top_addrs(10),
+        vec![
+            addrs[1].clone(),
+            addrs[3].clone(),
+            addrs[0].clone(),
+            addrs[2].clone()
+        ]
+    );
+}
+
+#[test]
+fn test_replace_addrs() {
+    let mut peer_store = PeerStore::default();
+    let old_addr = random_addr();
+    peer_store
+        .add_addr(old_addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    let banned_addr: Multiaddr =
+        format!("/ip4/10.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap();
+    peer_store.ban_addr(&banned_addr, 10_000, "test".into());
+
+    let new_addr = random_addr_v6();
+    let mut addr_manager = AddrManager::default();
+    addr_manager.add(AddrInfo::new(new_addr.clone(), 0, 100, 0));
+    addr_manager.add(AddrInfo::new(banned_addr.clone(), 0, 100, 0));
+    let replaced = peer_store.replace_addrs(addr_manager);
+
+    assert!(replaced.get(&old_addr).is_some());
+    assert!(peer_store.addr_manager().get(&old_addr).is_none());
+    assert!(peer_store.addr_manager().get(&new_addr).is_some());
+    assert!(peer_store.addr_manager().get(&banned_addr).is_none());
+    assert!(peer_store.is_addr_banned(&banned_addr));
+    assert_eq!(peer_store.ban_list().count(), 1);
+}
+
+#[test]
+fn test_import_addr_list() {
+    let mut peer_store = PeerStore::default();
+    let addr1 = random_addr();
+    let addr2 = random_addr_v6();
+    let known_addr = random_addr();
+    peer_store
+        .add_addr(known_addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    let list = format!(
+        "# seed list\n{}\n\n  {} 50  \n{}\nnot a multiaddr\n{}\n",
+        addr1, addr2, known_addr, addr1
+    );
+
+    assert_eq!(peer_store.import_addr_list(list.as_bytes()), 2);
+    assert_eq!(peer_store.addr_manager().count(), 3);
+    assert_eq!(
+        peer_store.addr_manager().get(&addr1).unwrap().score,
+        peer_store.config().score_config.default_score
+    );
+    assert_eq!(peer_store.addr_manager().get(&addr2).unwrap().score, 50);
+}
+
+#[test]
+fn test_decay_scores() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+    let hour = 3600 * 1000;
+    let default_score = peer_store.config().score_config.default_score;
+
+    let low_addr = random_addr();
+    let high_addr = random_addr_v6();
+    let banned_addr: Multiaddr =
+        format!("/ip4/10.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap();
+    for (addr, score) in [
+        (&low_addr, default_score - 10),
+        (&high_addr, default_score + 2),
+        (&banned_addr, default_score - 10),
+    ] {
+        peer_store
+            .add_addr(addr.clone(), Flags::COMPATIBILITY)
+            .unwrap();
+        peer_store.mut_addr_manager().get_mut(addr).unwrap().score = score;
+    }
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.0.0/24".parse().unwrap(), 10 * hour);
+    let score = |peer_store: &PeerStore, addr: &Multiaddr| {
+        peer_store.addr_manager().get(addr).unwrap().score
+    };
+
+    // the first call starts the timer
+    peer_store.decay_scores(1_000);
+    assert_eq!(score(&peer_store, &low_addr), default_score - 10);
+
+    peer_store.decay_scores(1_000 + hour * 5 / 2);
+    assert_eq!(score(&peer_store, &low_addr), default_score - 8);
+    assert_eq!(score(&peer_store, &high_addr), default_score);
+    assert_eq!(score(&peer_store, &banned_addr), default_score - 10);
+
+    // idempotent within an interval, the remainder is kept
+    peer_store.decay_scores(1_000 + hour * 5 / 2);
+    assert_eq!(score(&peer_store, &low_addr), default_score - 8);
+    peer_store.decay_scores(1_000 + hour * 3);
+    assert_eq!(score(&peer_store, &low_addr), default_score - 7);
+
+    // never moves past the default score
+    peer_store.decay_scores(1_000 + hour * 100);
+    assert_eq!(score(&peer_store, &low_addr), default_score);
+
+    // banned addresses decay once the ban expired
+    clock.set(1_000 + hour * 100);
+    peer_store.decay_scores(1_000 + hour * 100);
+    peer_store.decay_scores(1_000 + hour * 101);
+    assert_eq!(score(&peer_store, &banned_addr), default_score - 9);
+}
+
+#[test]
+fn test_decay_scores_restart_after_ban() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store: PeerStore = Default::default();
+    peer_store.set_clock(clock.clone());
+    let hour = 3600 * 1000;
+    let default_score = peer_store.config().score_config.default_score;
+
+    let addr: Multiaddr = format!("/ip4/10.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = default_score - 10;
+    let score = |peer_store: &PeerStore| peer_store.addr_manager().get(&addr).unwrap().score;
+
+    // the timer is started before the ban
+    peer_store.decay_scores(1_000);
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.0.0/24".parse().unwrap(), 5 * hour);
+    peer_store.decay_scores(1_000 + hour * 5);
+    assert!(peer_store.addr_manager().get(&addr).is_some());
+    assert_eq!(score(&peer_store), default_score - 10);
+
+    // the banned intervals are not applied at once after the ban
+    clock.set(1_000 + hour * 5 + 1);
+    peer_store.decay_scores(1_000 + hour * 5 + 1);
+    assert_eq!(score(&peer_store), default_score - 10);
+    peer_store.decay_scores(1_000 + hour * 6 + 1);
+    assert_eq!(score(&peer_store), default_score - 9);
+}
+
+#[test]
+fn test_stats() {
+    let mut peer_store = PeerStore::default();
+    let score_config = peer_store.config().score_config;
+    let scores = [
+        score_config.ban_score,
+        score_config.ban_score + 1,
+        score_config.default_score,
+        score_config.default_score,
+        score_config.default_score + 1,
+    ];
+    for (i, score) in scores.iter().enumerate() {
+        let addr: Multiaddr = format!(
+            "/ip4/10.0.{}.1/tcp/42/p2p/{}",
+            i,
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap();
+        peer_store
+            .add_addr(addr.clone(), Flags::COMPATIBILITY)
+            .unwrap();
+        peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = *score;
+    }
+    peer_store.ban_addr(&random_addr_v6(), 10_000, "no reason".into());
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("192.168.0.0/16".parse().unwrap(), 10_000);
+
+    assert_eq!(
+        peer_store.stats(),
+        PeerStoreStats {
+            addr_count: 5,
+            low_score_count: 1,
+            active_ban_count: 2,
+            cidr_ban_count: 1,
+            score_distribution: ScoreDistribution {
+                at_or_below_ban: 1,
+                below_default: 1,
+                default: 2,
+                above_default: 1,
+            },
+        }
+    );
+}
+
+#[test]
+fn test_rate_limited_log() {
+    let mut log = RateLimitedLog::new(
+        "banned",
+        LogRateLimitConfig {
+            window_ms: 10_000,
+            max_lines_per_window: 3,
+        },
+    );
+    let mut logged = Vec::new();
+    for i in 0..8 {
+        logged.extend(log.record(1_000 + i, format!("ban {i}")));
+    }
+    // beyond the threshold, individual lines are suppressed
+    assert_eq!(logged, vec!["ban 0", "ban 1", "ban 2"]);
+
+    // and reported in a summary once the window is over, counting only the suppressed ones
+    assert_eq!(
+        log.record(11_000, "ban 8".to_owned()),
+        vec!["banned 5 more peers within 10s", "ban 8"]
+    );
+    // no summary if nothing was suppressed
+    assert_eq!(log.record(21_000, "ban 9".to_owned()), vec!["ban 9"]);
+
+    // a storm which just stops is reported by flush, once its window is over
+    for i in 10..15 {
+        log.record(40_000 + i, format!("ban {i}"));
+    }
+    assert_eq!(log.flush(45_000), None);
+    assert_eq!(
+        log.flush(3_600_000),
+        Some("banned 2 more peers within 10s".to_owned())
+    );
+    assert_eq!(log.flush(3_600_000), None);
+    assert_eq!(log.record(3_600_001, "ban 15".to_owned()), vec!["ban 15"]);
+}
+
+#[test]
+fn test_ban_log_storm() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        log_rate_limit: LogRateLimitConfig {
+            window_ms: 10_000,
+            max_lines_per_window: 3,
+        },
+        ..Default::default()
+    });
+    peer_store.set_clock(clock.clone());
+    let ban = |peer_store: &mut PeerStore, i: u8| {
+        let addr: Multiaddr = format!("/ip4/10.0.{i}.1/tcp/42").parse().unwrap();
+        peer_store.ban_addr(&addr, 60_000, format!("ban {i}"));
+    };
+
+    for i in 0..3 {
+        ban(&mut peer_store, i);
+    }
+    assert_eq!(peer_store.ban_log().suppressed(), 0);
+    // beyond the threshold, the bans are still applied but their lines are suppressed
+    for i in 3..8 {
+        ban(&mut peer_store, i);
+    }
+    assert_eq!(peer_store.ban_list().count(), 8);
+    assert_eq!(peer_store.ban_log().suppressed(), 5);
+
+    // the summary waits for the window to be over
+    assert!(peer_store.flush_logs(10_999).is_empty());
+    assert_eq!(peer_store.ban_log().suppressed(), 5);
+    assert_eq!(
+        peer_store.flush_logs(11_000),
+        vec!["banned 5 more peers within 10s"]
+    );
+    assert_eq!(peer_store.ban_log().suppressed(), 0);
+    assert!(peer_store.flush_logs(11_000).is_empty());
+
+    // the next storm starts a new window
+    clock.set(20_000);
+    for i in 8..12 {
+        ban(&mut peer_store, i);
+    }
+    assert_eq!(peer_store.ban_log().suppressed(), 1);
+}
+
+#[test]
+fn test_fetch_weighted_random_addrs() {
+    let build_peer_store = || {
+        let mut peer_store = PeerStore::default();
+        let ban_score = peer_store.config().score_config.ban_score;
+        let mut addrs = Vec::new();
+        for (i, score) in [ban_score - 5, ban_score, ban_score + 1, ban_score + 100]
+            .into_iter()
+            .enumerate()
+        {
+            let addr: Multiaddr = format!(
+                "/ip4/10.0.{}.1/tcp/42/p2p/{}",
+                i,
+                PeerId::random().to_base58()
+            )
+            .parse()
+            .unwrap();
+            peer_store.add_outbound_addr(addr.clone(), Flags::COMPATIBILITY);
+            peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = score;
+            addrs.push(addr);
+        }
+        (peer_store, addrs)
+    };
+    let (mut peer_store, addrs) = build_peer_store();
+
+    // addrs at or below the ban score are never returned
+    let mut rng = StdRng::seed_from_u64(42);
+    let fetched: HashSet<_> = peer_store
+        .fetch_random_addrs_with_rng(4, Flags::COMPATIBILITY, true, &mut rng)
+        .into_iter()
+        .map(|info| info.addr)
+        .collect();
+    assert_eq!(
+        fetched,
+        vec![addrs[2].clone(), addrs[3].clone()]
+            .into_iter()
+            .collect()
+    );
+
+    // the higher score is picked way more often
+    let mut rng = StdRng::seed_from_u64(42);
+    let picks: Vec<_> = (0..1000)
+        .map(|_| {
+            peer_store.fetch_random_addrs_with_rng(1, Flags::COMPATIBILITY, true, &mut rng)[0]
+                .addr
+                .clone()
+        })
+        .collect();
+    let high_picks = picks.iter().filter(|addr| **addr == addrs[3]).count();
+    assert!(high_picks > 900, "high score picked {high_picks} times");
+
+    // deterministic when seeded
+    let (mut peer_store2, addrs2) = build_peer_store();
+    let mut rng = StdRng::seed_from_u64(42);
+    let picks2: Vec<_> = (0..1000)
+        .map(|_| {
+            peer_store2.fetch_random_addrs_with_rng(1, Flags::COMPATIBILITY, true, &mut rng)[0]
+                .addr
+                .clone()
+        })
+        .collect();
+    let index_of = |addrs: &[Multiaddr], addr: &Multiaddr| addrs.iter().position(|a| a == addr);
+    assert_eq!(
+        picks
+            .iter()
+            .map(|addr| index_of(&addrs, addr))
+            .collect::<Vec<_>>(),
+        picks2
+            .iter()
+            .map(|addr| index_of(&addrs2, addr))
+            .collect::<Vec<_>>()
+    );
+
+    // the uniform path returns all of them
+    assert_eq!(
+        peer_store
+            .fetch_random_addrs_with_rng(4, Flags::COMPATIBILITY, false, &mut rng)
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn test_whitelisted_never_evicted() {
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        addr_count_limit: 3,
+        ..Default::default()
+    });
+    let now = ckb_systemtime::unix_time_as_millis();
+    let whitelisted = random_addr();
+    let evicted = random_addr();
+    peer_store.add_whitelisted(whitelisted.clone());
+    peer_store
+        .add_addr(evicted.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    peer_store
+        .add_addr(random_addr(), Flags::COMPATIBILITY)
+        .unwrap();
+    // both are terrible, the whitelisted one has the lowest score
+    for addr in [&whitelisted, &evicted] {
+        let paddr = peer_store.mut_addr_manager().get_mut(addr).unwrap();
+        paddr.mark_tried(now - 61_000);
+        paddr.mark_tried(now - 61_000);
+        paddr.mark_tried(now - 61_000);
+    }
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&whitelisted)
+        .unwrap()
+        .score = 0;
+
+    peer_store
+        .add_addr(random_addr(), Flags::COMPATIBILITY)
+        .unwrap();
+    assert!(peer_store.addr_manager().get(&whitelisted).is_some());
+    assert!(peer_store.addr_manager().get(&evicted).is_none());
+
+    // whitelisted addrs can still be banned, and stay whitelisted
+    peer_store.ban_addr(&whitelisted, 10_000, "no reason".into());
+    assert!(peer_store.addr_manager().get(&whitelisted).is_some());
+    assert!(peer_store.is_whitelisted(&whitelisted));
+}
+
+#[test]
+fn test_whitelisted_dialable_after_ban_expires() {
+    let clock = Arc::new(MockClock::default());
+    let mut peer_store = PeerStore::default();
+    peer_store.set_clock(clock.clone());
+    let whitelisted = random_addr();
+
+    clock.set(1_000_000);
+    peer_store.add_whitelisted(whitelisted.clone());
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&whitelisted)
+        .unwrap()
+        .mark_connected(900_000);
+    let attempt = |peer_store: &mut PeerStore| {
+        peer_store
+            .fetch_addrs_to_attempt(2, Flags::COMPATIBILITY)
+            .into_iter()
+            .map(|info| info.addr)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(attempt(&mut peer_store), vec![whitelisted.clone()]);
+
+    peer_store.ban_addr(&whitelisted, 10_000, "no reason".into());
+    assert!(attempt(&mut peer_store).is_empty());
+    assert!(peer_store
+        .fetch_random_addrs(2, Flags::COMPATIBILITY)
+        .is_empty());
+
+    clock.set(1_010_001);
+    assert!(!peer_store.is_addr_banned(&whitelisted));
+    assert_eq!(attempt(&mut peer_store), vec![whitelisted]);
+}
+
+#[test]
+fn test_evict_whitelisted_when_all_whitelisted() {
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        addr_count_limit: 2,
+        ..Default::default()
+    });
+    let whitelisted1 = random_addr();
+    let whitelisted2 = random_addr_v6();
+    peer_store.add_whitelisted(whitelisted1.clone());
+    peer_store.add_whitelisted(whitelisted2.clone());
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&whitelisted1)
+        .unwrap()
+        .score = 0;
+
+    let addr = random_addr();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    assert!(peer_store.addr_manager().get(&whitelisted1).is_none());
+    assert!(peer_store.addr_manager().get(&whitelisted2).is_some());
+    assert!(peer_store.addr_manager().get(&addr).is_some());
+}
+
+#[test]
+fn test_ban_observer() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let mut peer_store = PeerStore::default();
+    peer_store.set_clock(clock);
+    let (tx, mut rx) = futures::channel::mpsc::channel(1);
+    peer_store.set_ban_observer(tx);
+
+    let addr = random_addr_v6();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    for _ in 0..6 {
+        assert!(peer_store.report(&addr, Behaviour::TestBad).is_ok());
+    }
+    assert!(rx.try_next().is_err());
+    assert!(peer_store.report(&addr, Behaviour::TestBad).is_banned());
+    let ban_timeout_ms = peer_store.config().score_config.ban_timeout_ms;
+    assert_eq!(
+        rx.try_next().unwrap(),
+        Some(BanEvent {
+            addr: addr.clone(),
+            behaviour: Behaviour::TestBad,
+            score: peer_store.config().score_config.default_score - 70,
+            ban_until: 1_000 + ban_timeout_ms,
+        })
+    );
+    assert_eq!(
+        Some(1_000 + ban_timeout_ms),
+        peer_store
+            .ban_list()
+            .get_addr_ban(&addr)
+            .map(|ban| ban.ban_until)
+    );
+
+    // fired once per ban, not on subsequent reports
+    peer_store.report(&addr, Behaviour::TestBad);
+    assert!(rx.try_next().is_err());
+
+    // a closed channel doesn't stall reports
+    drop(rx);
+    let addr = random_addr();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    for _ in 0..6 {
+        peer_store.report(&addr, Behaviour::TestBad);
+    }
+    assert!(peer_store.report(&addr, Behaviour::TestBad).is_banned());
+}
+
+#[test]
+fn test_score_penalties() {
+    let penalties = ScorePenalties::new_builder()
+        .penalty(Behaviour::TestBad, -50)
+        .build();
+    assert_eq!(penalties.delta(Behaviour::TestBad), -50);
+    // unlisted behaviours fall back to their own score
+    assert_eq!(
+        penalties.delta(Behaviour::TestGood),
+        Behaviour::TestGood.score()
+    );
+    let penalties = ScorePenalties::new_builder().default_delta(-1).build();
+    assert_eq!(penalties.delta(Behaviour::TestGood), -1);
+
+    let mut peer_store = PeerStore::default();
+    peer_store.set_score_penalties(
+        ScorePenalties::new_builder()
+            .penalty(Behaviour::TestBad, -50)
+            .build(),
+    );
+    let addr = random_addr_v6();
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    assert!(peer_store.report(&addr, Behaviour::TestGood).is_ok());
+    assert_eq!(peer_store.addr_manager().get(&addr).unwrap().score, 110);
+    assert!(peer_store.report(&addr, Behaviour::TestBad).is_ok());
+    assert_eq!(peer_store.addr_manager().get(&addr).unwrap().score, 60);
+    assert!(peer_store.report(&addr, Behaviour::TestBad).is_banned());
+}
+
+#[test]
+fn test_is_connectable_backs_off() {
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        addr_backoff_base_ms: 1_000,
+        addr_max_failures: 4,
+        ..Default::default()
+    });
+    let addr: Multiaddr = "/ip4/10.0.0.1/tcp/42".parse().unwrap();
+    // unknown addrs have no failure
+    assert!(peer_store.is_connectable(&addr, 0));
+    peer_store
+        .add_addr(addr.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+
+    // the window doubles with every failure, counted from the last try
+    let mut tried_at_ms = 10_000;
+    for backoff_ms in [1_000, 2_000, 4_000] {
+        peer_store
+            .mut_addr_manager()
+            .get_mut(&addr)
+            .unwrap()
+            .mark_tried(tried_at_ms);
+        assert!(!peer_store.is_connectable(&addr, tried_at_ms + backoff_ms - 1));
+        assert!(peer_store.is_connectable(&addr, tried_at_ms + backoff_ms));
+        tried_at_ms += backoff_ms;
+    }
+
+    // a connection resets the failures
+    let info = peer_store.mut_addr_manager().get_mut(&addr).unwrap();
+    info.mark_connected(tried_at_ms);
+    assert!(peer_store.is_connectable(&addr, tried_at_ms));
+
+    // given up after too many failures, whatever the time
+    for _ in 0..4 {
+        peer_store
+            .mut_addr_manager()
+            .get_mut(&addr)
+            .unwrap()
+            .mark_tried(tried_at_ms);
+    }
+    assert!(!peer_store.is_connectable(&addr, u64::MAX));
+
+    // banned addrs are never connectable
+    let banned: Multiaddr = "/ip4/10.0.1.1/tcp/42".parse().unwrap();
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.1.0/24".parse().unwrap(), u64::MAX);
+    assert!(!peer_store.is_connectable(&banned, 0));
+}
+
+#[test]
+fn test_recently_active_addrs() {
+    let clock = Arc::new(MockClock::default());
+    let mut peer_store = PeerStore::default();
+    peer_store.set_clock(clock.clone());
+    let addr = |i: u8| -> Multiaddr { format!("/ip4/10.0.{i}.1/tcp/42").parse().unwrap() };
+    for (i, score) in [(1, 10), (2, 100), (3, 50)] {
+        peer_store.add_addr(addr(i), Flags::COMPATIBILITY).unwrap();
+        let info = peer_store.mut_addr_manager().get_mut(&addr(i)).unwrap();
+        info.score = score;
+        info.last_connected_at_ms = 1_000;
+    }
+    clock.set(2_000);
+    // the best scored one is banned
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.2.0/24".parse().unwrap(), 60_000);
+
+    assert_eq!(
+        peer_store.recently_active_addrs(1_000, 10),
+        vec![addr(3), addr(1)]
+    );
+    assert_eq!(peer_store.recently_active_addrs(1_000, 1), vec![addr(3)]);
+    assert!(peer_store.recently_active_addrs(999, 10).is_empty());
+}
+
+#[test]
+fn test_compact_addrs() {
+    let clock = Arc::new(MockClock::default());
+    let config = PeerStoreConfig::default();
+    let mut peer_store = PeerStore::with_config(config);
+    peer_store.set_clock(clock.clone());
+    let addr = |i: u8| -> Multiaddr { format!("/ip4/10.0.0.{i}/tcp/42").parse().unwrap() };
+    let source = PeerId::random();
+
+    // 1 stale, 2 connected once, 3 stale and never tried, 4 stale but whitelisted,
+    // 5 stale but banned, 6 stale from a source, 7 tried lately from a source
+    clock.set(1_000);
+    for i in 1..=3 {
+        peer_store.add_addr(addr(i), Flags::COMPATIBILITY).unwrap();
+    }
+    peer_store.add_whitelisted(addr(4));
+    peer_store
+        .mut_ban_list()
+        .ban_cidr("10.0.0.5/32".parse().unwrap(), u64::MAX / 2);
+    peer_store
+        .mut_addr_manager()
+        .add(AddrInfo::new(addr(5), 0, 0, Flags::COMPATIBILITY.bits()));
+    for i in 6..=7 {
+        peer_store
+            .add_addr_from(addr(i), Flags::COMPATIBILITY, source.clone())
+            .unwrap();
+    }
+    for i in [1, 2, 4, 5, 6] {
+        peer_store
+            .mut_addr_manager()
+            .get_mut(&addr(i))
+            .unwrap()
+            .mark_tried(1_000);
+    }
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&addr(2))
+        .unwrap()
+        .mark_connected(1_000);
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&addr(7))
+        .unwrap()
+        .mark_tried(1_000 + config.addr_timeout_ms);
+
+    // nothing is over the timeout yet
+    clock.set(1_000 + config.addr_timeout_ms);
+    assert_eq!(peer_store.compact_addrs(), 0);
+    assert_eq!(peer_store.addr_manager().count(), 7);
+
+    clock.set(1_001 + config.addr_timeout_ms);
+    assert_eq!(peer_store.compact_addrs(), 3);
+    let addr_manager = peer_store.mut_addr_manager();
+    assert_eq!(addr_manager.count(), 4);
+    for i in [1, 3, 6] {
+        assert!(addr_manager.get(&addr(i)).is_none());
+    }
+    for i in [2, 4, 5, 7] {
+        assert!(addr_manager.get(&addr(i)).is_some());
+    }
+    assert_eq!(addr_manager.source_addr_count(&source), 1);
+
+    // the rebuilt indices stay consistent
+    assert_eq!(addr_manager.fetch_random(10, |_| true).len(), 4);
+    addr_manager.remove(&addr(7));
+    assert_eq!(addr_manager.source_addr_count(&source), 0);
+    assert_eq!(addr_manager.fetch_random(10, |_| true).len(), 3);
+    addr_manager.add(AddrInfo::new(addr(8), 0, 0, Flags::COMPATIBILITY.bits()));
+    assert_eq!(addr_manager.count(), 4);
+    assert!(peer_store.is_addr_banned(&addr(5)));
+}
+
+#[test]
+fn test_reconcile_scores() {
+    let clock = Arc::new(MockClock::default());
+    clock.set(1_000);
+    let config_with_ban_score = |ban_score| {
+        let mut config = PeerStoreConfig::default();
+        config.score_config.ban_score = ban_score;
+        config
+    };
+    let mut peer_store = PeerStore::with_config(config_with_ban_score(20));
+    peer_store.set_clock(clock.clone());
+    let addr = |i: u8| -> Multiaddr { format!("/ip4/10.0.0.{i}/tcp/42").parse().unwrap() };
+    for (i, score) in [(1, 45), (2, 30), (3, 15), (4, 100)] {
+        peer_store.add_addr(addr(i), Flags::COMPATIBILITY).unwrap();
+        peer_store
+            .mut_addr_manager()
+            .get_mut(&addr(i))
+            .unwrap()
+            .score = score;
+    }
+    peer_store.ban_addr(&addr(4), 60_000, "no reason".into());
+    // a manual ban recording a score is not a ban for the score
+    peer_store.mut_ban_list().ban(BannedAddr {
+        address: multiaddr_to_ip_network(&addr(5)).unwrap(),
+        ban_until: 1_000 + 60_000,
+        ban_reason: "manual".into(),
+        created_at: 1_000,
+        score: Some(50),
+        reason: BanReason::Manual,
+    });
+    let ban_of = |peer_store: &PeerStore, i| {
+        let network = multiaddr_to_ip_network(&addr(i)).unwrap();
+        peer_store
+            .ban_list()
+            .get_banned_addrs()
+            .into_iter()
+            .find(|banned| banned.address == network)
+    };
+
+    // the score of 3 is already below the ban score
+    peer_store.reconcile_scores();
+    let ban_timeout_ms = peer_store.config().score_config.ban_timeout_ms;
+    let ban = ban_of(&peer_store, 3).unwrap();
+    assert_eq!(ban.score, Some(15));
+    assert_eq!(ban.ban_until, 1_000 + ban_timeout_ms);
+    assert!(peer_store.addr_manager().get(&addr(3)).is_none());
+    assert_eq!(ban_of(&peer_store, 4).unwrap().score, None);
+    assert!(peer_store.is_addr_banned(&addr(5)));
+    assert_eq!(peer_store.ban_list().count(), 3);
+
+    // a raised ban score bans 2 as well
+    clock.set(2_000);
+    peer_store.set_config(config_with_ban_score(40));
+    peer_store.reconcile_scores();
+    let ban = ban_of(&peer_store, 2).unwrap();
+    assert_eq!(ban.score, Some(30));
+    assert_eq!(ban.ban_until, 2_000 + ban_timeout_ms);
+    assert_eq!(
+        ban_of(&peer_store, 3).unwrap().ban_until,
+        1_000 + ban_timeout_ms
+    );
+    assert!(!peer_store.is_addr_banned(&addr(1)));
+    assert_eq!(peer_store.ban_list().count(), 4);
+
+    // a lowered ban score lifts the bans given for scores no longer below it
+    peer_store.set_config(config_with_ban_score(10));
+    peer_store.reconcile_scores();
+    assert!(!peer_store.is_addr_banned(&addr(2)));
+    assert!(!peer_store.is_addr_banned(&addr(3)));
+    assert!(peer_store.is_addr_banned(&addr(4)));
+    assert!(peer_store.is_addr_banned(&addr(5)));
+    assert_eq!(peer_store.ban_list().count(), 2);
+    assert!(peer_store.addr_manager().get(&addr(1)).is_some());
+}
+
+#[test]
+fn test_list_bans() {
+    let clock = Arc::new(MockClock::default());
+    let mut peer_store = PeerStore::default();
+    peer_store.set_clock(clock.clone());
+    let addr = |i: u8| -> Multiaddr { format!("/ip4/10.0.{i}.1/tcp/42").parse().unwrap() };
+
+    clock.set(1_000);
+    peer_store.ban_addr(&addr(1), 60_000, "invalid message".into());
+    clock.set(2_000);
+    peer_store.ban_network("10.0.2.0/24".parse().unwrap(), 60_000, "spam".into());
+    clock.set(3_000);
+    peer_store.add_addr(addr(3), Flags::COMPATIBILITY).unwrap();
+    while !peer_store.report(&addr(3), Behaviour::TestBad).is_banned() {}
+    // expired bans are not listed
+    peer_store.ban_addr(&addr(4), 1, "expired".into());
+    clock.set(4_000);
+
+    let bans = peer_store.list_bans();
+    assert_eq!(
+        bans.iter()
+            .map(|ban| (ban.created_at, ban.reason, ban.ban_reason.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (1_000, BanReason::ProtocolViolation, "invalid message"),
+            (2_000, BanReason::Manual, "spam"),
+            (
+                3_000,
+                BanReason::ScoreBelowThreshold,
+                "report behaviour TestBad"
+            ),
+        ]
+    );
+    assert!(bans[2].score.is_some());
+}
+
+#[test]
+fn test_clear_expired() {
+    let clock = Arc::new(MockClock::default());
+    let mut peer_store = PeerStore::with_config(PeerStoreConfig {
+        addr_timeout_ms: 100_000,
+        ..Default::default()
+    });
+    peer_store.set_clock(clock.clone());
+    let addr = |i: u8| -> Multiaddr {
+        format!(
+            "/ip4/10.0.{i}.1/tcp/42/p2p/{}",
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap()
+    };
+    let (stale, whitelisted, connected_peer, once_connected, tried, fresh) =
+        (addr(1), addr(2), addr(3), addr(4), addr(5), addr(6));
+
+    clock.set(1_000);
+    for addr in [&stale, &connected_peer, &once_connected, &tried] {
+        peer_store
+            .add_addr(addr.clone(), Flags::COMPATIBILITY)
+            .unwrap();
+    }
+    peer_store.add_whitelisted(whitelisted.clone());
+    peer_store.add_connected_peer(connected_peer.clone(), SessionType::Outbound);
+    peer_store.ban_addr(&addr(7), 10, "expired".into());
+    peer_store.ban_network("10.0.8.0/24".parse().unwrap(), 1_000_000, "live".into());
+
+    clock.set(150_000);
+    peer_store
+        .add_addr(fresh.clone(), Flags::COMPATIBILITY)
+        .unwrap();
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&once_connected)
+        .unwrap()
+        .mark_connected(150_000);
+
+    let now = 200_000;
+    clock.set(now);
+    peer_store
+        .mut_addr_manager()
+        .get_mut(&tried)
+        .unwrap()
+        .mark_tried(now - 1_000);
+    assert_eq!(peer_store.ban_list().count(), 2);
+
+    assert_eq!(
+        peer_store.clear_expired(now),
+        ClearStats {
+            expired_bans: 1,
+            stale_addrs: 1,
+        }
+    );
+    assert!(peer_store.addr_manager().get(&stale).is_none());
+    for addr in [
+        &whitelisted,
+        &connected_peer,
+        &once_connected,
+        &tried,
+        &fresh,
+    ] {
+        assert!(peer_store.addr_manager().get(addr).is_some());
+    }
+    assert_eq!(peer_store.ban_list().count(), 1);
+    assert!(peer_store.is_addr_banned(&addr(8)));
+
+    // nothing left to clear
+    assert_eq!(peer_store.clear_expired(now), ClearStats::default());
+}
+
+#[test]
+fn test_netgroup_distribution() {
+    let mut peer_store = PeerStore::default();
+    assert!(peer_store.netgroup_distribution().is_empty());
+    let addr = |ip: &str| -> Multiaddr {
+        format!("/ip4/{ip}/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap()
+    };
+    for (ip, session_type) in [
+        ("10.1.0.1", SessionType::Inbound),
+        ("10.1.2.1", SessionType::Inbound),
+        ("10.1.3.1", SessionType::Inbound),
+        ("10.1.4.1", SessionType::Outbound),
+        ("10.2.0.1", SessionType::Outbound),
+        ("10.2.0.2", SessionType::Inbound),
+    ] {
+        peer_store.add_connected_peer(addr(ip), session_type);
+    }
+
+    assert_eq!(
+        peer_store.netgroup_distribution(),
+        vec![(Group::IP4([10, 1]), 3, 1), (Group::IP4([10, 2]), 1, 1)]
+    );
+}
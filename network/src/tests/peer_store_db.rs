@@ -1,10 +1,12 @@
 use crate::{
+    extract_peer_id,
     multiaddr::Multiaddr,
     peer_store::{
-        types::{multiaddr_to_ip_network, AddrInfo, BannedAddr},
-        PeerStore,
+        ban_list::BanList,
+        types::{multiaddr_to_ip_network, AddrInfo, BanReason, BannedAddr, ExportedAddr},
+        DumpVersionPolicy, PeerStore, Status, DUMP_VERSION,
     },
-    PeerId,
+    Flags, PeerId, SessionType,
 };
 
 use std::fs::File;
@@ -45,18 +47,24 @@ fn test_peer_store_persistent() {
         ban_until: now_ms + 10_000,
         ban_reason: "test1".into(),
         created_at: now_ms,
+        score: None,
+        reason: BanReason::Other,
     };
     let ban2 = BannedAddr {
         address: addr4,
         ban_until: now_ms + 20_000,
         ban_reason: "test2".into(),
         created_at: now_ms + 1,
+        score: None,
+        reason: BanReason::Other,
     };
     let ban3 = BannedAddr {
         address: addr5,
         ban_until: now_ms + 30_000,
         ban_reason: "test3".into(),
         created_at: now_ms + 2,
+        score: None,
+        reason: BanReason::Other,
     };
     ban_list.ban(ban1.clone());
     ban_list.ban(ban2.clone());
@@ -90,6 +98,139 @@ fn test_peer_store_persistent() {
     );
 }
 
+#[test]
+fn test_peer_store_persistent_traffic() {
+    let mut peer_store = PeerStore::default();
+    let peer_id = PeerId::random();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{}", peer_id.to_base58())
+        .parse()
+        .unwrap();
+    peer_store.add_outbound_addr(addr.clone(), Flags::COMPATIBILITY);
+    peer_store.add_connected_peer(addr.clone(), SessionType::Outbound);
+    peer_store.record_traffic(&peer_id, 100, 1000);
+    peer_store.record_traffic(&peer_id, 20, 200);
+
+    // dump and load
+    let dir = tempfile::tempdir().unwrap();
+    peer_store.dump_to_dir(dir.path()).unwrap();
+    let peer_store2 = PeerStore::load_from_dir_or_default(dir.path());
+
+    let addr_info = peer_store2.addr_manager().get(&addr).unwrap();
+    assert_eq!(addr_info.bytes_sent, 120);
+    assert_eq!(addr_info.bytes_received, 1200);
+}
+
+#[test]
+fn test_peer_store_shutdown_dump() {
+    let mut peer_store = PeerStore::default();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    peer_store.add_outbound_addr(addr.clone(), Flags::COMPATIBILITY);
+
+    // a periodic dump just ran
+    let dir = tempfile::tempdir().unwrap();
+    peer_store.dump_to_dir(dir.path()).unwrap();
+
+    peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = 42;
+    let network = multiaddr_to_ip_network(&"/ip4/127.0.0.2/tcp/42".parse().unwrap()).unwrap();
+    peer_store.ban_network(network, 10_000, "test".into());
+    peer_store.shutdown_dump(dir.path()).unwrap();
+
+    let peer_store2 = PeerStore::load_from_dir_or_default(dir.path());
+    assert_eq!(peer_store2.addr_manager().get(&addr).unwrap().score, 42);
+    assert_eq!(
+        peer_store2
+            .ban_list()
+            .get_banned_addrs()
+            .into_iter()
+            .map(|banned_addr| banned_addr.address)
+            .collect::<Vec<_>>(),
+        vec![network]
+    );
+
+    // the shutdown dump is written only once
+    peer_store.mut_addr_manager().get_mut(&addr).unwrap().score = 1;
+    peer_store.shutdown_dump(dir.path()).unwrap();
+    let peer_store3 = PeerStore::load_from_dir_or_default(dir.path());
+    assert_eq!(peer_store3.addr_manager().get(&addr).unwrap().score, 42);
+}
+
+#[test]
+fn test_peer_store_json_export_import() {
+    let addr1: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    let addr2: Multiaddr = format!("/ip4/127.0.0.2/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    let banned_addr: Multiaddr =
+        format!("/ip4/127.0.0.3/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap();
+
+    let mut peer_store = PeerStore::default();
+    peer_store
+        .mut_addr_manager()
+        .add(AddrInfo::new(addr1.clone(), 200, 50, 0));
+    peer_store
+        .mut_addr_manager()
+        .add(AddrInfo::new(addr2.clone(), 300, 60, 0));
+    peer_store
+        .mut_addr_manager()
+        .add(AddrInfo::new(banned_addr.clone(), 400, 70, 0));
+    let mut exported = Vec::new();
+    peer_store.dump_to_writer(&mut exported).unwrap();
+
+    // a fresh store loads every entry
+    let loaded = PeerStore::load_from_reader(exported.as_slice()).unwrap();
+    assert_eq!(loaded.addr_manager().count(), 3);
+
+    let mut entries: Vec<ExportedAddr> = serde_json::from_slice(&exported).unwrap();
+    entries.push(ExportedAddr {
+        addr: "not a multiaddr".to_owned(),
+        last_connected_at_ms: 0,
+        score: 100,
+        attempts_count: 0,
+        flags: 0,
+    });
+    let exported = serde_json::to_vec(&entries).unwrap();
+
+    let mut peer_store2 = PeerStore::default();
+    peer_store2
+        .mut_addr_manager()
+        .add(AddrInfo::new(addr1.clone(), 100, 80, 0));
+    let network = multiaddr_to_ip_network(&banned_addr).unwrap();
+    peer_store2.ban_network(network, 10_000, "test".into());
+
+    let warnings = peer_store2.import_from_reader(exported.as_slice()).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(peer_store2.addr_manager().count(), 2);
+    // merged, keeps the higher score and the most recent connected time
+    let info1 = peer_store2.addr_manager().get(&addr1).unwrap();
+    assert_eq!(info1.score, 80);
+    assert_eq!(info1.last_connected_at_ms, 200);
+    let info2 = peer_store2.addr_manager().get(&addr2).unwrap();
+    assert_eq!(info2.score, 60);
+    assert_eq!(info2.last_connected_at_ms, 300);
+    assert!(peer_store2.addr_manager().get(&banned_addr).is_none());
+}
+
+#[test]
+fn test_peer_store_persistent_whitelist() {
+    let mut peer_store = PeerStore::default();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    peer_store.add_whitelisted(addr.clone());
+
+    let dir = tempfile::tempdir().unwrap();
+    peer_store.dump_to_dir(dir.path()).unwrap();
+    let peer_store2 = PeerStore::load_from_dir_or_default(dir.path());
+    assert!(peer_store2.is_whitelisted(&addr));
+    assert!(peer_store2.addr_manager().get(&addr).is_some());
+}
+
 #[test]
 fn test_peer_store_load_from_dir_should_not_panic() {
     // should return an empty store when dir does not exist
@@ -178,6 +319,8 @@ fn test_peer_store_dump_with_broken_tmp_file_should_be_ok() {
         ban_until: now_ms + 10_000,
         ban_reason: "test".into(),
         created_at: now_ms,
+        score: None,
+        reason: BanReason::Other,
     });
     peer_store.dump_to_dir(dir.as_ref()).unwrap();
 
@@ -186,3 +329,184 @@ fn test_peer_store_dump_with_broken_tmp_file_should_be_ok() {
     assert_eq!(1, peer_store.ban_list().count());
     assert_eq!(3, peer_store.addr_manager().count());
 }
+
+#[test]
+fn test_peer_store_load_dump_from_newer_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let newer_version = DUMP_VERSION + 1;
+    // write a dump with an unknown trailing section
+    {
+        let file_path = dir.path().join("addr_manager.db");
+        let mut file = File::create(file_path).unwrap();
+        let json = format!(
+            r#"{{"version":{},"addrs":[{{"addr":"/ip4/127.0.0.1/tcp/42","score":60,"last_connected_at_ms":0,"last_tried_at_ms":0,"attempts_count":0,"random_id_pos":0,"flags":1,"unknown_field":1}},{{"addr":"/ip4/127.0.0.2/tcp/42","score":60,"last_connected_at_ms":0,"last_tried_at_ms":0,"attempts_count":0,"random_id_pos":1,"flags":1}}],"unknown_section":{{"foo":"bar"}}}}"#,
+            newer_version
+        );
+        writeln!(file, "{json}").unwrap();
+        file.sync_all().unwrap();
+    }
+    {
+        let file_path = dir.path().join("ban_list.db");
+        let mut file = File::create(file_path).unwrap();
+        let json = format!(
+            r#"{{"version":{},"banned_addrs":[{{"address":"192.168.0.2/32","ban_until":472792659688893,"ban_reason":"test","created_at":1612678877739}}],"unknown_section":[1,2,3]}}"#,
+            newer_version
+        );
+        writeln!(file, "{json}").unwrap();
+        file.sync_all().unwrap();
+    }
+
+    // best effort should load the known sections
+    let peer_store = PeerStore::load_from_dir(dir.path(), DumpVersionPolicy::BestEffort).unwrap();
+    assert_eq!(2, peer_store.addr_manager().count());
+    assert_eq!(1, peer_store.ban_list().count());
+
+    // ignore should start fresh
+    let peer_store = PeerStore::load_from_dir(dir.path(), DumpVersionPolicy::Ignore).unwrap();
+    assert_eq!(0, peer_store.addr_manager().count());
+    assert_eq!(0, peer_store.ban_list().count());
+
+    // reject should return an error
+    assert!(PeerStore::load_from_dir(dir.path(), DumpVersionPolicy::Reject).is_err());
+}
+
+#[test]
+fn test_peer_store_dump_is_atomic() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut peer_store = PeerStore::default();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/42/p2p/{}", PeerId::random().to_base58())
+        .parse()
+        .unwrap();
+    let banned_addr: Multiaddr =
+        format!("/ip4/127.0.0.2/tcp/42/p2p/{}", PeerId::random().to_base58())
+            .parse()
+            .unwrap();
+    peer_store
+        .mut_addr_manager()
+        .add(AddrInfo::new(addr.clone(), 0, 60, 0));
+    let now_ms = ckb_systemtime::unix_time_as_millis();
+    peer_store.mut_ban_list().ban(BannedAddr {
+        address: multiaddr_to_ip_network(&banned_addr).unwrap(),
+        ban_until: now_ms + 100_000,
+        ban_reason: "test".into(),
+        created_at: now_ms,
+        score: None,
+        reason: BanReason::Other,
+    });
+    peer_store.dump_to_dir(dir.path()).unwrap();
+    assert!(dir.path().join("peer_store.db").exists());
+    assert!(!dir.path().join("addr_manager.db").exists());
+    assert!(!dir.path().join("ban_list.db").exists());
+
+    // a dump killed halfway only leaves a partial tmp file, the previous dump is still loaded
+    let content = std::fs::read(dir.path().join("peer_store.db")).unwrap();
+    std::fs::write(
+        dir.path().join("tmp/peer_store.db"),
+        &content[..content.len() / 2],
+    )
+    .unwrap();
+    let peer_store = PeerStore::load_from_dir_or_default(dir.path());
+    assert!(peer_store.addr_manager().get(&addr).is_some());
+    assert!(peer_store.is_addr_banned(&banned_addr));
+
+    // a torn db file falls back to an empty store instead of panicking
+    std::fs::write(
+        dir.path().join("peer_store.db"),
+        &content[..content.len() / 2],
+    )
+    .unwrap();
+    let peer_store = PeerStore::load_from_dir(dir.path(), DumpVersionPolicy::Reject).unwrap();
+    assert_eq!(0, peer_store.addr_manager().count());
+    assert_eq!(0, peer_store.ban_list().count());
+
+    // and the next dump recovers the db file
+    peer_store.dump_to_dir(dir.path()).unwrap();
+    assert!(PeerStore::load_from_dir(dir.path(), DumpVersionPolicy::Reject).is_ok());
+}
+
+#[test]
+fn test_peer_store_reload_from_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let addr = |i: u8| -> Multiaddr {
+        format!(
+            "/ip4/127.0.0.{i}/tcp/42/p2p/{}",
+            PeerId::random().to_base58()
+        )
+        .parse()
+        .unwrap()
+    };
+    let (old_addr, new_addr, banned_addr, connected_addr) = (addr(1), addr(2), addr(3), addr(4));
+
+    // the replacement store
+    let mut replacement = PeerStore::default();
+    replacement
+        .mut_addr_manager()
+        .add(AddrInfo::new(new_addr.clone(), 0, 60, 0));
+    let now_ms = ckb_systemtime::unix_time_as_millis();
+    replacement.mut_ban_list().ban(BannedAddr {
+        address: multiaddr_to_ip_network(&banned_addr).unwrap(),
+        ban_until: now_ms + 100_000,
+        ban_reason: "test".into(),
+        created_at: now_ms,
+        score: None,
+        reason: BanReason::Other,
+    });
+    replacement.dump_to_dir(dir.path()).unwrap();
+    let path = dir.path().join("peer_store.db");
+
+    let mut peer_store = PeerStore::default();
+    for addr in [&old_addr, &banned_addr] {
+        peer_store
+            .mut_addr_manager()
+            .add(AddrInfo::new(addr.clone(), 0, 60, 0));
+    }
+    peer_store.add_connected_peer(connected_addr.clone(), SessionType::Outbound);
+
+    // a malformed file leaves the store untouched
+    let broken = dir.path().join("broken.db");
+    std::fs::write(&broken, "{").unwrap();
+    assert!(peer_store.reload_from_path(&broken).is_err());
+    assert!(peer_store
+        .reload_from_path(&dir.path().join("missing.db"))
+        .is_err());
+    assert_eq!(2, peer_store.addr_manager().count());
+    assert!(peer_store.addr_manager().get(&old_addr).is_some());
+
+    peer_store.reload_from_path(&path).unwrap();
+    assert!(peer_store.addr_manager().get(&new_addr).is_some());
+    assert!(peer_store.addr_manager().get(&old_addr).is_none());
+    assert!(peer_store.addr_manager().get(&banned_addr).is_none());
+    assert!(peer_store.is_addr_banned(&banned_addr));
+    let connected_peer_id = extract_peer_id(&connected_addr).unwrap();
+    assert_eq!(
+        peer_store.peer_status(&connected_peer_id),
+        Status::Connected
+    );
+}
+
+#[test]
+fn test_ban_reason_defaults_to_other_on_load() {
+    let legacy = r#"[{"address":"192.168.0.2/32","ban_until":472792659688893,"ban_reason":"test","created_at":1612678877739}]"#;
+    let ban_list = BanList::load(legacy.as_bytes()).unwrap();
+    let bans = ban_list.get_banned_addrs();
+    assert_eq!(1, bans.len());
+    assert_eq!(BanReason::Other, bans[0].reason);
+    assert_eq!("test", bans[0].ban_reason);
+
+    // the reason survives a dump and load
+    let mut ban_list = BanList::default();
+    let now_ms = ckb_systemtime::unix_time_as_millis();
+    ban_list.ban(BannedAddr {
+        address: "192.168.0.3/32".parse().unwrap(),
+        ban_until: now_ms + 100_000,
+        ban_reason: "test".into(),
+        created_at: now_ms,
+        score: None,
+        reason: BanReason::Manual,
+    });
+    let mut dumped = tempfile::tempfile().unwrap();
+    ban_list.dump(dumped.try_clone().unwrap()).unwrap();
+    std::io::Seek::rewind(&mut dumped).unwrap();
+    let loaded = BanList::load(std::io::BufReader::new(dumped)).unwrap();
+    assert_eq!(BanReason::Manual, loaded.get_banned_addrs()[0].reason);
+}
@@ -220,6 +220,13 @@ impl Resource {
         template.render_to(&mut f, context)?;
         Ok(())
     }
+
+    /// Renders a resource with the template context and returns the result as a string.
+    ///
+    /// Unlike [`export`](#method.export), nothing is written to the file system.
+    pub fn render(&self, context: &TemplateContext<'_>) -> Result<String> {
+        Template::new(from_utf8(self.get()?)?).render(context)
+    }
 }
 
 struct SourceFiles<'a> {
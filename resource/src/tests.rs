@@ -48,3 +48,30 @@ fn test_export() {
         .expect("export ckb.toml");
     assert!(Resource::exported_in(root_dir.path()));
 }
+
+#[test]
+fn test_render() {
+    let root_dir = mkdir();
+    let context = TemplateContext::new(
+        "dev",
+        vec![
+            ("rpc_port", "7000"),
+            ("p2p_port", "8000"),
+            ("log_to_file", "true"),
+            ("log_to_stdout", "true"),
+            ("block_assembler", ""),
+            ("spec_source", "bundled"),
+        ],
+    );
+    let rendered = Resource::bundled_ckb_config()
+        .render(&context)
+        .expect("render ckb.toml");
+    assert!(rendered.contains(":7000\""));
+    assert!(!Resource::exported_in(root_dir.path()));
+
+    Resource::bundled_ckb_config()
+        .export(&context, root_dir.path())
+        .expect("export ckb.toml");
+    let exported = fs::read_to_string(root_dir.path().join(CKB_CONFIG_FILE_NAME)).unwrap();
+    assert_eq!(rendered, exported);
+}
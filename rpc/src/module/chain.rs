@@ -1868,10 +1868,8 @@ impl ChainRpc for ChainRpcImpl {
             return Ok(None);
         };
 
-        let issuance = if let Some(issuance) = snapshot
-            .get_block_epoch_index(&block_hash)
-            .and_then(|index| snapshot.get_epoch_ext(&index))
-            .and_then(|epoch_ext| {
+        let issuance = if let Some(issuance) =
+            snapshot.get_block_epoch(&block_hash).and_then(|epoch_ext| {
                 let primary = epoch_ext.block_reward(block_number).ok()?;
                 let secondary = epoch_ext
                     .secondary_block_issuance(
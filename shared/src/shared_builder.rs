@@ -17,7 +17,7 @@ use ckb_notify::{NotifyController, NotifyService};
 use ckb_proposal_table::ProposalTable;
 use ckb_proposal_table::ProposalView;
 use ckb_snapshot::{Snapshot, SnapshotMgr};
-use ckb_store::{ChainDB, ChainStore, Freezer};
+use ckb_store::{ChainDB, ChainStore, Freezer, StoreError};
 use ckb_tx_pool::{
     service::TxVerificationResult, TokioRwLock, TxEntry, TxPool, TxPoolServiceBuilder,
 };
@@ -260,7 +260,7 @@ impl SharedBuilder {
         consensus: &Consensus,
     ) -> (ProposalTable, ProposalView) {
         let proposal_window = consensus.tx_proposal_window();
-        let tip_number = store.get_tip_header().expect("store inited").number();
+        let tip_number = store.tip_number().expect("store inited");
         let mut proposal_ids = ProposalTable::new(proposal_window);
         let proposal_start = tip_number.saturating_sub(proposal_window.farthest());
         for bn in proposal_start..=tip_number {
@@ -289,21 +289,17 @@ impl SharedBuilder {
             .and_then(|header| store.get_current_epoch_ext().map(|epoch| (header, epoch)))
         {
             Some((tip_header, epoch)) => {
-                if let Some(genesis_hash) = store.get_block_hash(0) {
-                    let expect_genesis_hash = consensus.genesis_hash();
-                    if genesis_hash == expect_genesis_hash {
-                        Ok((tip_header, epoch))
-                    } else {
-                        Err(SpecError::GenesisMismatch {
-                            expected: expect_genesis_hash,
-                            actual: genesis_hash,
-                        }
-                        .into())
-                    }
-                } else {
-                    Err(InternalErrorKind::Database
+                if store.get_block_hash(0).is_none() {
+                    return Err(InternalErrorKind::Database
                         .other("genesis does not exist in database")
-                        .into())
+                        .into());
+                }
+                match store.check_genesis(&consensus.genesis_hash()) {
+                    Ok(()) => Ok((tip_header, epoch)),
+                    Err(StoreError::GenesisMismatch { expected, actual }) => {
+                        Err(SpecError::GenesisMismatch { expected, actual }.into())
+                    }
+                    Err(err) => Err(InternalErrorKind::Database.other(err).into()),
                 }
             }
             None => store.init(consensus).map(|_| {
@@ -1,11 +1,12 @@
 use ckb_app_config::StoreConfig;
 use ckb_types::{
     bytes::Bytes,
-    core::{HeaderView, UncleBlockVecView},
+    core::{HeaderView, TransactionView, UncleBlockVecView},
     packed::{self, Byte32, ProposalShortIdVec},
 };
 use ckb_util::Mutex;
 use lru::LruCache;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// The cache of chain store.
 pub struct StoreCache {
@@ -23,6 +24,43 @@ pub struct StoreCache {
     pub block_uncles: Mutex<LruCache<Byte32, UncleBlockVecView>>,
     /// The cache of block extension sections.
     pub block_extensions: Mutex<LruCache<Byte32, Option<packed::Bytes>>>,
+    /// The cache of block bodies.
+    pub block_bodies: Mutex<LruCache<Byte32, Vec<TransactionView>>>,
+    /// Whether headers and transactions read from the database are verified against their hashes
+    pub verify_on_read: bool,
+    /// Whether attaching the cells of a block puts their data into the cell data caches
+    pub prime_cache_on_attach: bool,
+    header_cache_enabled: bool,
+    cell_data_cache_enabled: bool,
+    body_cache_enabled: bool,
+    counters: CacheCounters,
+}
+
+/// Hit and miss counts of the header, cell data and block body caches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Header cache hits
+    pub header_hits: u64,
+    /// Header cache misses
+    pub header_misses: u64,
+    /// Cell data cache hits
+    pub cell_data_hits: u64,
+    /// Cell data cache misses
+    pub cell_data_misses: u64,
+    /// Block body cache hits
+    pub body_hits: u64,
+    /// Block body cache misses
+    pub body_misses: u64,
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    header_hits: AtomicU64,
+    header_misses: AtomicU64,
+    cell_data_hits: AtomicU64,
+    cell_data_misses: AtomicU64,
+    body_hits: AtomicU64,
+    body_misses: AtomicU64,
 }
 
 impl Default for StoreCache {
@@ -42,6 +80,81 @@ impl StoreCache {
             block_tx_hashes: Mutex::new(LruCache::new(config.block_tx_hashes_cache_size)),
             block_uncles: Mutex::new(LruCache::new(config.block_uncles_cache_size)),
             block_extensions: Mutex::new(LruCache::new(config.block_extensions_cache_size)),
+            block_bodies: Mutex::new(LruCache::new(config.body_cache_size)),
+            verify_on_read: config.verify_on_read,
+            prime_cache_on_attach: config.prime_cache_on_attach,
+            header_cache_enabled: config.header_cache_size > 0,
+            cell_data_cache_enabled: config.cell_data_cache_size > 0,
+            body_cache_enabled: config.body_cache_size > 0,
+            counters: CacheCounters::default(),
         }
     }
+
+    /// Whether the header cache is in use; a zero `header_cache_size` bypasses it entirely
+    pub fn header_cache_enabled(&self) -> bool {
+        self.header_cache_enabled
+    }
+
+    /// Whether the cell data caches are in use; a zero `cell_data_cache_size` bypasses them
+    /// entirely
+    pub fn cell_data_cache_enabled(&self) -> bool {
+        self.cell_data_cache_enabled
+    }
+
+    /// Whether the block body cache is in use; it is disabled by default, by a zero
+    /// `body_cache_size`
+    pub fn body_cache_enabled(&self) -> bool {
+        self.body_cache_enabled
+    }
+
+    /// Returns the hit and miss counts accumulated since creation or the last `reset_stats`
+    pub fn stats(&self) -> CacheStats {
+        let counters = &self.counters;
+        CacheStats {
+            header_hits: counters.header_hits.load(Ordering::Relaxed),
+            header_misses: counters.header_misses.load(Ordering::Relaxed),
+            cell_data_hits: counters.cell_data_hits.load(Ordering::Relaxed),
+            cell_data_misses: counters.cell_data_misses.load(Ordering::Relaxed),
+            body_hits: counters.body_hits.load(Ordering::Relaxed),
+            body_misses: counters.body_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets all hit and miss counters to zero
+    pub fn reset_stats(&self) {
+        let counters = &self.counters;
+        counters.header_hits.store(0, Ordering::Relaxed);
+        counters.header_misses.store(0, Ordering::Relaxed);
+        counters.cell_data_hits.store(0, Ordering::Relaxed);
+        counters.cell_data_misses.store(0, Ordering::Relaxed);
+        counters.body_hits.store(0, Ordering::Relaxed);
+        counters.body_misses.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_header(&self, hit: bool) {
+        let counter = if hit {
+            &self.counters.header_hits
+        } else {
+            &self.counters.header_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cell_data(&self, hit: bool) {
+        let counter = if hit {
+            &self.counters.cell_data_hits
+        } else {
+            &self.counters.cell_data_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_body(&self, hit: bool) {
+        let counter = if hit {
+            &self.counters.body_hits
+        } else {
+            &self.counters.body_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
 }
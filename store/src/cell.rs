@@ -1,6 +1,6 @@
-use crate::{ChainStore, StoreTransaction};
+use crate::{ChainStore, StoreCache, StoreTransaction};
 use ckb_error::Error;
-use ckb_types::{core::BlockView, packed, prelude::*};
+use ckb_types::{bytes::Bytes, core::BlockView, packed, prelude::*};
 use std::collections::HashMap;
 
 /**
@@ -68,7 +68,13 @@ pub fn attach_block_cell(txn: &StoreTransaction, block: &BlockView) -> Result<()
                     (out_point, entry, data_entry)
                 })
         });
-    txn.insert_cells(new_cells)?;
+    if txn.cache.prime_cache_on_attach && txn.cache.cell_data_cache_enabled() {
+        let new_cells: Vec<_> = new_cells.collect();
+        txn.insert_cells(new_cells.iter().cloned())?;
+        prime_cell_data_cache(&txn.cache, &new_cells);
+    } else {
+        txn.insert_cells(new_cells)?;
+    }
 
     // mark inputs dead
     // skip cellbase
@@ -81,6 +87,38 @@ pub fn attach_block_cell(txn: &StoreTransaction, block: &BlockView) -> Result<()
     Ok(())
 }
 
+/// Puts the data of newly attached cells into the cell data caches, in the form
+/// `get_cell_data` and `get_cell_data_hash` read them.
+fn prime_cell_data_cache(
+    cache: &StoreCache,
+    cells: &[(
+        packed::OutPoint,
+        packed::CellEntry,
+        Option<packed::CellDataEntry>,
+    )],
+) {
+    let entries: Vec<_> = cells
+        .iter()
+        .map(|(out_point, _, data_entry)| {
+            let value = match data_entry {
+                Some(entry) => (entry.output_data().unpack(), entry.output_data_hash()),
+                None => (Bytes::new(), packed::Byte32::zero()),
+            };
+            (out_point.to_cell_key(), value)
+        })
+        .collect();
+    {
+        let mut cell_data_hash = cache.cell_data_hash.lock();
+        for (key, (_, data_hash)) in &entries {
+            cell_data_hash.put(key.clone(), data_hash.clone());
+        }
+    }
+    let mut cell_data = cache.cell_data.lock();
+    for (key, value) in entries {
+        cell_data.put(key, value);
+    }
+}
+
 /// Undoes the effects of this block on the live cell set.
 pub fn detach_block_cell(txn: &StoreTransaction, block: &BlockView) -> Result<(), Error> {
     let transactions = block.transactions();
@@ -149,3 +187,20 @@ pub fn detach_block_cell(txn: &StoreTransaction, block: &BlockView) -> Result<()
 
     Ok(())
 }
+
+/// Detaches `blocks` from the main chain and undoes their effects on the live cell set.
+///
+/// `blocks` are given in ascending order and detached from the last one, which is the same as
+/// calling `StoreTransaction::detach_block` then `detach_block_cell` for each block from the tip.
+/// All the changes are buffered in `txn` and written together when it commits.
+pub fn detach_blocks<'a, I>(txn: &StoreTransaction, blocks: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a BlockView>,
+    I::IntoIter: DoubleEndedIterator,
+{
+    for block in blocks.into_iter().rev() {
+        txn.detach_block(block)?;
+        detach_block_cell(txn, block)?;
+    }
+    Ok(())
+}
@@ -79,8 +79,7 @@ where
     T: ChainStore,
 {
     fn get_epoch_ext(&self, header: &HeaderView) -> Option<EpochExt> {
-        ChainStore::get_block_epoch_index(self.0.as_ref(), &header.hash())
-            .and_then(|index| ChainStore::get_epoch_ext(self.0.as_ref(), &index))
+        ChainStore::get_block_epoch(self.0.as_ref(), &header.hash())
     }
 
     fn get_block_hash(&self, number: BlockNumber) -> Option<Byte32> {
@@ -132,8 +131,7 @@ impl<'a, T: ChainStore> HeaderProvider for BorrowedDataLoaderWrapper<'a, T> {
 
 impl<'a, T: ChainStore> EpochProvider for BorrowedDataLoaderWrapper<'a, T> {
     fn get_epoch_ext(&self, header: &HeaderView) -> Option<EpochExt> {
-        ChainStore::get_block_epoch_index(self.0, &header.hash())
-            .and_then(|index| ChainStore::get_epoch_ext(self.0, &index))
+        ChainStore::get_block_epoch(self.0, &header.hash())
     }
 
     fn get_block_hash(&self, number: BlockNumber) -> Option<Byte32> {
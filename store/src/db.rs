@@ -1,5 +1,6 @@
-use crate::cache::StoreCache;
-use crate::cell::attach_block_cell;
+use crate::cache::{CacheStats, StoreCache};
+use crate::cell::{attach_block_cell, detach_block_cell};
+use crate::error::StoreError;
 use crate::store::ChainStore;
 use crate::transaction::StoreTransaction;
 use crate::write_batch::StoreWriteBatch;
@@ -10,15 +11,21 @@ use ckb_db::{
     iter::{DBIter, DBIterator, IteratorMode},
     DBPinnableSlice, RocksDB,
 };
-use ckb_db_schema::{Col, CHAIN_SPEC_HASH_KEY, MIGRATION_VERSION_KEY};
+use ckb_db_schema::{
+    Col, ALL_COLUMNS, CHAIN_SPEC_HASH_KEY, COLUMN_META, META_GENESIS_HASH_KEY,
+    MIGRATION_VERSION_KEY,
+};
 use ckb_error::{Error, InternalErrorKind};
 use ckb_freezer::Freezer;
+use ckb_merkle_mountain_range::leaf_index_to_mmr_size;
 use ckb_types::{
-    core::{BlockExt, EpochExt, HeaderView, TransactionView},
+    core::{BlockExt, BlockNumber, BlockView, EpochExt, HeaderView, TransactionView},
     packed,
     prelude::*,
     utilities::merkle_mountain_range::ChainRootMMR,
 };
+use std::cmp;
+use std::io::{self, Read};
 use std::sync::Arc;
 
 /// A database of the chain store based on the RocksDB wrapper `RocksDB`
@@ -27,6 +34,21 @@ pub struct ChainDB {
     db: RocksDB,
     freezer: Option<Freezer>,
     cache: Arc<StoreCache>,
+    max_batch_bytes: usize,
+}
+
+/// The number of blocks detached per commit by `ChainDB::rollback_to`
+const ROLLBACK_BATCH_SIZE: BlockNumber = 100;
+const PROPERTY_NUM_KEYS: &str = "rocksdb.estimate-num-keys";
+const PROPERTY_LIVE_SST_FILES_SIZE: &str = "rocksdb.live-sst-files-size";
+
+/// The estimated size of a column family
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColumnSize {
+    /// Estimated number of keys, including the unflushed memtables
+    pub estimated_keys: u64,
+    /// Total size in bytes of the SST files of the current version
+    pub live_sst_bytes: u64,
 }
 
 impl ChainStore for ChainDB {
@@ -38,10 +60,16 @@ impl ChainStore for ChainDB {
         self.freezer.as_ref()
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError> {
         self.db
             .get_pinned(col, key)
-            .expect("db operation should be ok")
+            .map_err(|err| StoreError::io(col, err))
+    }
+
+    fn try_multi_get(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, StoreError> {
+        self.db
+            .multi_get(col, keys)
+            .map_err(|err| StoreError::io(col, err))
     }
 
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIter {
@@ -75,6 +103,7 @@ impl ChainDB {
             db,
             freezer: None,
             cache: Arc::new(cache),
+            max_batch_bytes: config.max_batch_bytes,
         }
     }
 
@@ -85,9 +114,201 @@ impl ChainDB {
             db,
             freezer: Some(freezer),
             cache: Arc::new(cache),
+            max_batch_bytes: config.max_batch_bytes,
         }
     }
 
+    /// Returns the hit and miss counts of the header, cell data and block body caches
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the cache hit and miss counters, e.g. at the start of a monitoring interval
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats()
+    }
+
+    /// Returns the estimated size of every column family, keyed by the `COLUMN_*` constants
+    pub fn column_sizes(&self) -> Result<Vec<(Col, ColumnSize)>, Error> {
+        ALL_COLUMNS
+            .iter()
+            .map(|col| {
+                let estimated_keys = self
+                    .db
+                    .property_int_value_cf(col, PROPERTY_NUM_KEYS)?
+                    .unwrap_or_default();
+                let live_sst_bytes = self
+                    .db
+                    .property_int_value_cf(col, PROPERTY_LIVE_SST_FILES_SIZE)?
+                    .unwrap_or_default();
+                Ok((
+                    *col,
+                    ColumnSize {
+                        estimated_keys,
+                        live_sst_bytes,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Compacts the key range `[start, end)` of a column family, `None` meaning unbounded
+    pub fn compact_range(
+        &self,
+        col: Col,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        self.db.compact_range(col, start, end)
+    }
+
+    /// Compacts every column family entirely, e.g. after a reindex
+    ///
+    /// This is expensive and blocks until done, schedule it for a low-traffic window.
+    pub fn compact_all(&self) -> Result<(), Error> {
+        for col in ALL_COLUMNS {
+            self.compact_range(col, None, None)?;
+        }
+        Ok(())
+    }
+
+    /// Imports blocks written by `ChainStore::export_range` and attaches them on top of the tip
+    ///
+    /// Blocks are not verified; each one must extend the tip or the block before it, otherwise
+    /// the import stops with an error. Blocks are committed in batches of `batch_size`, so
+    /// batches committed before an error are kept. Returns the number of imported blocks.
+    pub fn import_blocks<R: Read>(
+        &self,
+        consensus: &Consensus,
+        mut reader: R,
+        batch_size: usize,
+    ) -> Result<u64, Error> {
+        let mut tip = self
+            .get_tip_header()
+            .ok_or_else(|| InternalErrorKind::Database.other("the store has no tip"))?;
+        let mut tip_ext = self
+            .get_block_ext(&tip.hash())
+            .expect("tip block ext must be stored");
+        let mut imported = 0;
+        loop {
+            let txn = self.begin_transaction();
+            let mut mmr = ChainRootMMR::new(leaf_index_to_mmr_size(tip.number()), &txn);
+            let mut current_epoch = None;
+            let mut batch_len = 0;
+            while batch_len < batch_size.max(1) {
+                let block = match read_exported_block(&mut reader)? {
+                    Some(block) => block,
+                    None => break,
+                };
+                if block.parent_hash() != tip.hash() || block.number() != tip.number() + 1 {
+                    return Err(InternalErrorKind::DataCorrupted
+                        .other(format!(
+                            "block {} {} does not extend block {} {}",
+                            block.number(),
+                            block.hash(),
+                            tip.number(),
+                            tip.hash()
+                        ))
+                        .into());
+                }
+
+                let next_epoch = consensus
+                    .next_epoch_ext(&tip, &txn.borrow_as_data_loader())
+                    .expect("epoch should be stored");
+                let epoch = next_epoch.epoch();
+                txn.insert_block_epoch_index(
+                    &block.hash(),
+                    &epoch.last_block_hash_in_previous_epoch(),
+                )?;
+                if next_epoch.is_head() {
+                    txn.insert_epoch_ext(&epoch.last_block_hash_in_previous_epoch(), &epoch)?;
+                }
+
+                let ext = BlockExt {
+                    received_at: block.timestamp(),
+                    total_difficulty: tip_ext.total_difficulty.to_owned() + block.difficulty(),
+                    total_uncles_count: tip_ext.total_uncles_count
+                        + block.data().uncles().len() as u64,
+                    verified: None,
+                    txs_fees: vec![],
+                    cycles: None,
+                    txs_sizes: None,
+                };
+                txn.insert_block(&block)?;
+                txn.insert_block_ext(&block.hash(), &ext)?;
+                txn.attach_block(&block)?;
+                attach_block_cell(&txn, &block)?;
+                mmr.push(block.digest())
+                    .map_err(|e| InternalErrorKind::MMR.other(e))?;
+
+                tip = block.header();
+                tip_ext = ext;
+                current_epoch = Some(epoch);
+                batch_len += 1;
+            }
+            if batch_len == 0 {
+                return Ok(imported);
+            }
+
+            mmr.commit().map_err(|e| InternalErrorKind::MMR.other(e))?;
+            txn.insert_tip_header(&tip)?;
+            if let Some(epoch) = current_epoch {
+                txn.insert_current_epoch_ext(&epoch)?;
+            }
+            txn.commit()?;
+            imported += batch_len as u64;
+        }
+    }
+
+    /// Rolls the main chain back, so the block at `number` becomes the tip
+    ///
+    /// Main chain blocks above `number` are detached from the tip down and their effects on the
+    /// live cell set are undone; the blocks themselves are kept in the store. Blocks are detached
+    /// in batches of `ROLLBACK_BATCH_SIZE`, and each batch commits along with the tip header and
+    /// the current epoch of its new tip, so a failed rollback leaves a consistent shorter chain.
+    /// Frozen blocks are final and cannot be rolled back. Does nothing if the tip is already at
+    /// or below `number`.
+    pub fn rollback_to(&self, number: BlockNumber) -> Result<(), Error> {
+        let mut tip = self
+            .get_tip_header()
+            .ok_or_else(|| InternalErrorKind::Database.other("the store has no tip"))?;
+        if tip.number() <= number {
+            return Ok(());
+        }
+        if let Some(freezer) = self.freezer() {
+            if number + 1 < freezer.number() {
+                return Err(InternalErrorKind::Database
+                    .other(format!(
+                        "can not roll back to block {number}, blocks below {} are frozen",
+                        freezer.number()
+                    ))
+                    .into());
+            }
+        }
+
+        while tip.number() > number {
+            let batch_tip = cmp::max(number, tip.number().saturating_sub(ROLLBACK_BATCH_SIZE));
+            let txn = self.begin_transaction();
+            while tip.number() > batch_tip {
+                let block = self
+                    .get_block(&tip.hash())
+                    .expect("main chain block must be stored");
+                txn.detach_block(&block)?;
+                detach_block_cell(&txn, &block)?;
+                tip = self
+                    .get_block_header(&block.parent_hash())
+                    .expect("main chain block parent must be stored");
+            }
+            let epoch = self
+                .get_block_epoch(&tip.hash())
+                .expect("main chain block epoch must be stored");
+            txn.insert_tip_header(&tip)?;
+            txn.insert_current_epoch_ext(&epoch)?;
+            txn.commit()?;
+        }
+        Ok(())
+    }
+
     /// Return the inner RocksDB instance
     pub fn db(&self) -> &RocksDB {
         &self.db
@@ -137,9 +358,14 @@ impl ChainDB {
     }
 
     /// Construct `StoreWriteBatch` with default option.
+    ///
+    /// The batch auto-commits when inserts grow it beyond `max_batch_bytes` of the store config,
+    /// see [`StoreWriteBatch::auto_commits`].
     pub fn new_write_batch(&self) -> StoreWriteBatch {
         StoreWriteBatch {
             inner: self.db.new_write_batch(),
+            max_bytes: self.max_batch_bytes,
+            auto_commits: 0,
         }
     }
 
@@ -187,6 +413,7 @@ impl ChainDB {
         let last_block_hash_in_previous_epoch = epoch.last_block_hash_in_previous_epoch();
 
         db_txn.insert_block(genesis)?;
+        db_txn.insert_raw(COLUMN_META, META_GENESIS_HASH_KEY, genesis_hash.as_slice())?;
         db_txn.insert_block_ext(&genesis_hash, &ext)?;
         db_txn.insert_tip_header(&genesis.header())?;
         db_txn.insert_current_epoch_ext(epoch)?;
@@ -204,3 +431,20 @@ impl ChainDB {
         Ok(())
     }
 }
+
+fn read_exported_block<R: Read>(reader: &mut R) -> Result<Option<BlockView>, Error> {
+    let io_error = |err: io::Error| InternalErrorKind::System.other(err);
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(io_error(err).into()),
+    }
+    let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut data).map_err(io_error)?;
+    let block = packed::BlockReader::from_compatible_slice(&data)
+        .map_err(|err| InternalErrorKind::DataCorrupted.other(err))?
+        .to_entity()
+        .into_view();
+    Ok(Some(block))
+}
@@ -0,0 +1,96 @@
+//! Errors of the fallible `ChainStore` reads
+//!
+//! The infallible getters, e.g. [`ChainStore::get_block_header`], panic on any read failure,
+//! which is the right call on consensus-critical paths: chain verification and block
+//! attachment must not go on with a store they cannot trust. Paths that only serve data to
+//! others should use the `try_` variants and degrade gracefully instead, namely:
+//!
+//! - the RPC chain module, `get_header`, `get_header_by_number` and `get_block_hash`
+//! - the sync protocol responders, `GetHeadersProcess` and `GetBlocksProcess`
+//! - the block filter protocol responders in `sync/src/filter`
+//!
+//! [`ChainStore::get_block_header`]: crate::ChainStore::get_block_header
+
+use ckb_db_schema::Col;
+use ckb_types::packed;
+use thiserror::Error;
+
+/// A read failure of the chain store
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    /// The underlying database failed to serve the read
+    #[error("db read from column {col} failed: {reason}")]
+    Io {
+        /// The column read from
+        col: Col,
+        /// The error reported by the database
+        reason: String,
+    },
+    /// The stored value does not hash to the key it is stored under
+    #[error(
+        "corrupted data read from column {col} at key 0x{}: expected hash {expected}, computed {computed}",
+        hex_key(key)
+    )]
+    Corrupt {
+        /// The column read from
+        col: Col,
+        /// The key read
+        key: Vec<u8>,
+        /// The hash the key refers to
+        expected: packed::Byte32,
+        /// The hash computed from the stored value
+        computed: packed::Byte32,
+    },
+    /// The database was initialized with another genesis block, see `ChainStore::check_genesis`
+    #[error("genesis hash mismatch: expected {expected}, stored {actual}")]
+    GenesisMismatch {
+        /// The hash of the configured genesis block
+        expected: packed::Byte32,
+        /// The hash of the genesis block in the database
+        actual: packed::Byte32,
+    },
+    /// The accumulated capacity decreases from a block to its descendant, see
+    /// `ChainStore::accumulated_capacity_between`
+    #[error(
+        "accumulated capacity decreases from {from_capacity} at block {from} to {to_capacity} at block {to}"
+    )]
+    CapacityUnderflow {
+        /// The hash of the ancestor block
+        from: packed::Byte32,
+        /// The hash of the descendant block
+        to: packed::Byte32,
+        /// The accumulated capacity of the ancestor block, in shannons
+        from_capacity: u64,
+        /// The accumulated capacity of the descendant block, in shannons
+        to_capacity: u64,
+    },
+    /// The stored value is not a valid encoding of the expected type
+    #[error("undecodable data read from column {col} at key 0x{}", hex_key(key))]
+    Decode {
+        /// The column read from
+        col: Col,
+        /// The key read
+        key: Vec<u8>,
+    },
+}
+
+impl StoreError {
+    /// Wraps an error reported by the database on reading `col`
+    pub fn io<E: std::fmt::Display>(col: Col, err: E) -> Self {
+        StoreError::Io {
+            col,
+            reason: err.to_string(),
+        }
+    }
+
+    pub(crate) fn decode(col: Col, key: &[u8]) -> Self {
+        StoreError::Decode {
+            col,
+            key: key.to_vec(),
+        }
+    }
+}
+
+fn hex_key(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -7,6 +7,7 @@ mod cache;
 mod cell;
 pub mod data_loader_wrapper;
 mod db;
+mod error;
 mod snapshot;
 mod store;
 mod transaction;
@@ -15,11 +16,12 @@ mod write_batch;
 #[cfg(test)]
 mod tests;
 
-pub use cache::StoreCache;
-pub use cell::{attach_block_cell, detach_block_cell};
-pub use db::ChainDB;
+pub use cache::{CacheStats, StoreCache};
+pub use cell::{attach_block_cell, detach_block_cell, detach_blocks};
+pub use db::{ChainDB, ColumnSize};
+pub use error::StoreError;
 pub use snapshot::StoreSnapshot;
-pub use store::ChainStore;
+pub use store::{BlockSkeleton, ChainStore, EpochAggregate, IntegrityIssue, MaturityInfo};
 pub use transaction::StoreTransaction;
 pub use write_batch::StoreWriteBatch;
 
@@ -1,4 +1,5 @@
 use crate::cache::StoreCache;
+use crate::error::StoreError;
 use crate::store::ChainStore;
 use ckb_db::{
     iter::{DBIter, DBIterator, IteratorMode},
@@ -9,6 +10,10 @@ use ckb_freezer::Freezer;
 use std::sync::Arc;
 
 /// A snapshot of the chain store.
+///
+/// Reads see the store as it was when the snapshot was taken, even while new blocks are
+/// committed. A live snapshot pins the underlying SST files and keeps them from being compacted
+/// away, so it should be dropped as soon as the scan is done.
 pub struct StoreSnapshot {
     pub(crate) inner: RocksDBSnapshot,
     pub(crate) freezer: Option<Freezer>,
@@ -24,10 +29,16 @@ impl ChainStore for StoreSnapshot {
         self.freezer.as_ref()
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError> {
         self.inner
             .get_pinned(col, key)
-            .expect("db operation should be ok")
+            .map_err(|err| StoreError::io(col, err))
+    }
+
+    fn try_multi_get(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, StoreError> {
+        self.inner
+            .multi_get(col, keys)
+            .map_err(|err| StoreError::io(col, err))
     }
 
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIter {
@@ -1,5 +1,6 @@
 use crate::cache::StoreCache;
 use crate::data_loader_wrapper::BorrowedDataLoaderWrapper;
+use crate::error::StoreError;
 use ckb_db::{
     iter::{DBIter, Direction, IteratorMode},
     DBPinnableSlice,
@@ -9,18 +10,25 @@ use ckb_db_schema::{
     COLUMN_BLOCK_FILTER, COLUMN_BLOCK_FILTER_HASH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
     COLUMN_BLOCK_UNCLE, COLUMN_CELL, COLUMN_CELL_DATA, COLUMN_CELL_DATA_HASH,
     COLUMN_CHAIN_ROOT_MMR, COLUMN_EPOCH, COLUMN_INDEX, COLUMN_META, COLUMN_TRANSACTION_INFO,
-    COLUMN_UNCLES, META_CURRENT_EPOCH_KEY, META_LATEST_BUILT_FILTER_DATA_KEY, META_TIP_HEADER_KEY,
+    COLUMN_UNCLES, META_CURRENT_EPOCH_KEY, META_GENESIS_HASH_KEY,
+    META_LATEST_BUILT_FILTER_DATA_KEY, META_TIP_HEADER_KEY,
 };
 use ckb_freezer::Freezer;
 use ckb_types::{
     bytes::Bytes,
     core::{
-        cell::CellMeta, BlockExt, BlockNumber, BlockView, EpochExt, EpochNumber, HeaderView,
-        TransactionInfo, TransactionView, UncleBlockVecView,
+        cell::CellMeta, BlockExt, BlockNumber, BlockView, Capacity, EpochExt, EpochNumber,
+        EpochNumberWithFraction, HeaderView, TransactionInfo, TransactionView, UncleBlockVecView,
+        UncleBlockView,
     },
     packed::{self, OutPoint},
     prelude::*,
+    utilities::merkle_root,
+    H256,
 };
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::ops::{ControlFlow, Range};
 
 /// The `ChainStore` trait provides chain data store interface
 pub trait ChainStore: Send + Sync + Sized {
@@ -28,10 +36,39 @@ pub trait ChainStore: Send + Sync + Sized {
     fn cache(&self) -> Option<&StoreCache>;
     /// Return freezer reference
     fn freezer(&self) -> Option<&Freezer>;
+    /// Return the bytes associated with a key value and the given column family, or the
+    /// error reported by the database.
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError>;
     /// Return the bytes associated with a key value and the given column family.
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice>;
+    ///
+    /// Panics if the database fails to serve the read, see [`ChainStore::try_get`].
+    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
+        self.try_get(col, key).expect("db operation should be ok")
+    }
+    /// Return the bytes associated with each of `keys` in the given column family, in the order
+    /// of `keys`, or the error reported by the database.
+    ///
+    /// Stores backed by the db or a snapshot of it read all the keys in a single multi-get, the
+    /// default reads them one by one.
+    fn try_multi_get(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, StoreError> {
+        keys.iter()
+            .map(|key| Ok(self.try_get(col, key)?.map(|slice| slice.to_vec())))
+            .collect()
+    }
+    /// Return the bytes associated with each of `keys` in the given column family.
+    ///
+    /// Panics if the database fails to serve the read, see [`ChainStore::try_multi_get`].
+    fn multi_get(&self, col: Col, keys: &[&[u8]]) -> Vec<Option<Vec<u8>>> {
+        self.try_multi_get(col, keys)
+            .expect("db operation should be ok")
+    }
     /// Return an iterator over the database key-value pairs in the given column family.
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIter;
+    /// Whether data read from the database is verified against its hash, see
+    /// `StoreConfig::verify_on_read`
+    fn verify_on_read(&self) -> bool {
+        self.cache().map_or(false, |cache| cache.verify_on_read)
+    }
     /// Return the borrowed data loader wrapper
     fn borrow_as_data_loader(&self) -> BorrowedDataLoaderWrapper<Self> {
         BorrowedDataLoaderWrapper::new(self)
@@ -46,6 +83,14 @@ pub trait ChainStore: Send + Sync + Sized {
                 let raw_block = packed::BlockReader::from_compatible_slice(&raw_block)
                     .expect("checked data")
                     .to_entity();
+                if self.verify_on_read() {
+                    verify_read_hash(
+                        "freezer",
+                        &header.number().to_be_bytes(),
+                        h,
+                        &raw_block.calc_header_hash(),
+                    );
+                }
                 return Some(raw_block.into_view());
             }
         }
@@ -66,41 +111,200 @@ pub trait ChainStore: Send + Sync + Sized {
         Some(block)
     }
 
+    /// Get the header, uncle hashes, proposals and transaction hashes of a block
+    ///
+    /// Cheaper than `get_block` for callers not interested in the transactions, since neither
+    /// the transactions nor the uncles are decoded.
+    fn get_block_skeleton(&self, h: &packed::Byte32) -> Option<BlockSkeleton> {
+        let header = self.get_block_header(h)?;
+        if let Some(freezer) = self.freezer() {
+            if header.number() > 0 && header.number() < freezer.number() {
+                let raw_block = freezer.retrieve(header.number()).expect("block frozen")?;
+                let reader =
+                    packed::BlockReader::from_compatible_slice(&raw_block).expect("checked data");
+                return Some(BlockSkeleton {
+                    header,
+                    uncle_hashes: reader
+                        .uncles()
+                        .iter()
+                        .map(|uncle| uncle.calc_header_hash())
+                        .collect(),
+                    proposals: reader.proposals().to_entity(),
+                    tx_hashes: reader.calc_tx_hashes(),
+                });
+            }
+        }
+        let uncle_hashes = match self.cache().and_then(|cache| {
+            cache
+                .block_uncles
+                .lock()
+                .get(h)
+                .map(|uncles| uncles.hashes().into_iter().collect())
+        }) {
+            Some(hashes) => hashes,
+            None => {
+                let slice = self
+                    .get(COLUMN_BLOCK_UNCLE, h.as_slice())
+                    .expect("block uncles must be stored");
+                packed::UncleBlockVecViewReader::from_slice_should_be_ok(slice.as_ref())
+                    .hashes()
+                    .to_entity()
+                    .into_iter()
+                    .collect()
+            }
+        };
+        let proposals = self
+            .get_block_proposal_txs_ids(h)
+            .expect("block proposal_ids must be stored");
+        Some(BlockSkeleton {
+            header,
+            uncle_hashes,
+            proposals,
+            tx_hashes: self.get_block_txs_hashes(h),
+        })
+    }
+
     /// Get header by block header hash
     fn get_block_header(&self, hash: &packed::Byte32) -> Option<HeaderView> {
-        if let Some(cache) = self.cache() {
+        self.try_get_block_header(hash)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Get block header by block header hash, or the error the read failed with
+    ///
+    /// Unlike `get_block_header`, a corrupted header is reported as an error instead of
+    /// panicking, even when `verify_on_read` is enabled.
+    fn try_get_block_header(
+        &self,
+        hash: &packed::Byte32,
+    ) -> Result<Option<HeaderView>, StoreError> {
+        if let Some(cache) = self.cache().filter(|cache| cache.header_cache_enabled()) {
             if let Some(header) = cache.headers.lock().get(hash) {
-                return Some(header.clone());
+                cache.record_header(true);
+                return Ok(Some(header.clone()));
             }
+            cache.record_header(false);
         };
-        let ret = self.get(COLUMN_BLOCK_HEADER, hash.as_slice()).map(|slice| {
-            let reader = packed::HeaderViewReader::from_slice_should_be_ok(slice.as_ref());
-            Unpack::<HeaderView>::unpack(&reader)
-        });
+        let header = match self.try_get(COLUMN_BLOCK_HEADER, hash.as_slice())? {
+            Some(slice) => {
+                let reader = packed::HeaderViewReader::from_slice(slice.as_ref())
+                    .map_err(|_| StoreError::decode(COLUMN_BLOCK_HEADER, hash.as_slice()))?;
+                Unpack::<HeaderView>::unpack(&reader)
+            }
+            None => return Ok(None),
+        };
+        if self.verify_on_read() {
+            let computed = header.data().calc_header_hash();
+            if &computed != hash {
+                return Err(StoreError::Corrupt {
+                    col: COLUMN_BLOCK_HEADER,
+                    key: hash.as_slice().to_vec(),
+                    expected: hash.clone(),
+                    computed,
+                });
+            }
+        }
 
-        if let Some(cache) = self.cache() {
-            ret.map(|header| {
-                cache.headers.lock().put(hash.clone(), header.clone());
-                header
-            })
+        if let Some(cache) = self.cache().filter(|cache| cache.header_cache_enabled()) {
+            cache.headers.lock().put(hash.clone(), header.clone());
+        }
+        Ok(Some(header))
+    }
+
+    /// Get headers by block header hashes, in the order of `hashes`
+    ///
+    /// The header cache is locked once to resolve hits and once more to store the misses, which
+    /// are read from the database with a single `multi_get`. Unknown hashes yield `None` at their
+    /// position.
+    fn get_block_headers(&self, hashes: &[packed::Byte32]) -> Vec<Option<HeaderView>> {
+        let mut ret: Vec<Option<HeaderView>> = vec![None; hashes.len()];
+        let mut misses = Vec::new();
+        if let Some(cache) = self.cache().filter(|cache| cache.header_cache_enabled()) {
+            let mut headers = cache.headers.lock();
+            for (index, hash) in hashes.iter().enumerate() {
+                match headers.get(hash) {
+                    Some(header) => {
+                        cache.record_header(true);
+                        ret[index] = Some(header.clone());
+                    }
+                    None => {
+                        cache.record_header(false);
+                        misses.push(index);
+                    }
+                }
+            }
         } else {
-            ret
+            misses.extend(0..hashes.len());
+        }
+        if misses.is_empty() {
+            return ret;
         }
+
+        let keys: Vec<&[u8]> = misses
+            .iter()
+            .map(|&index| hashes[index].as_slice())
+            .collect();
+        let mut loaded = Vec::with_capacity(misses.len());
+        for (index, value) in misses
+            .into_iter()
+            .zip(self.multi_get(COLUMN_BLOCK_HEADER, &keys))
+        {
+            if let Some(slice) = value {
+                let reader = packed::HeaderViewReader::from_slice_should_be_ok(&slice);
+                let header: HeaderView = reader.unpack();
+                ret[index] = Some(header.clone());
+                loaded.push((hashes[index].clone(), header));
+            }
+        }
+        if let Some(cache) = self.cache().filter(|cache| cache.header_cache_enabled()) {
+            let mut headers = cache.headers.lock();
+            for (hash, header) in loaded {
+                headers.put(hash, header);
+            }
+        }
+        ret
     }
 
     /// Get block body by block header hash
+    ///
+    /// Bodies are served from the block body cache when `body_cache_size` enables it. An unknown
+    /// block has an empty body, which is not cached.
     fn get_block_body(&self, hash: &packed::Byte32) -> Vec<TransactionView> {
+        if let Some(cache) = self.cache().filter(|cache| cache.body_cache_enabled()) {
+            if let Some(body) = cache.block_bodies.lock().get(hash) {
+                cache.record_body(true);
+                return body.clone();
+            }
+            cache.record_body(false);
+        };
         let prefix = hash.as_slice();
-        self.get_iter(
-            COLUMN_BLOCK_BODY,
-            IteratorMode::From(prefix, Direction::Forward),
-        )
-        .take_while(|(key, _)| key.starts_with(prefix))
-        .map(|(_key, value)| {
-            let reader = packed::TransactionViewReader::from_slice_should_be_ok(value.as_ref());
-            Unpack::<TransactionView>::unpack(&reader)
-        })
-        .collect()
+        let body: Vec<TransactionView> = self
+            .get_iter(
+                COLUMN_BLOCK_BODY,
+                IteratorMode::From(prefix, Direction::Forward),
+            )
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| {
+                let reader = packed::TransactionViewReader::from_slice_should_be_ok(value.as_ref());
+                let tx = Unpack::<TransactionView>::unpack(&reader);
+                if self.verify_on_read() {
+                    verify_read_hash(
+                        COLUMN_BLOCK_BODY,
+                        &key,
+                        &tx.hash(),
+                        &tx.data().calc_tx_hash(),
+                    );
+                }
+                tx
+            })
+            .collect();
+
+        if let Some(cache) = self.cache().filter(|cache| cache.body_cache_enabled()) {
+            if !body.is_empty() {
+                cache.block_bodies.lock().put(hash.clone(), body.clone());
+            }
+        }
+        body
     }
 
     /// Get unfrozen block from ky-store with given hash
@@ -144,6 +348,46 @@ pub trait ChainStore: Send + Sync + Sized {
         Some(block)
     }
 
+    /// Gets the byte size of a stored block
+    ///
+    /// Sums the lengths of the stored header, uncles, proposals, extension and transactions,
+    /// which are pinned but never decoded. The stored views also carry the hashes of the header,
+    /// uncles and transactions, so this is a cheap upper approximation of the serialized size,
+    /// not the exact on-wire size. Frozen blocks report the size of their frozen encoding.
+    fn get_block_size(&self, hash: &packed::Byte32) -> Option<u64> {
+        let header_size = self
+            .get(COLUMN_BLOCK_HEADER, hash.as_slice())?
+            .as_ref()
+            .len();
+        let number = self.get_block_number(hash);
+        if let (Some(freezer), Some(number)) = (self.freezer(), number) {
+            if number > 0 && number < freezer.number() {
+                let raw_block = freezer.retrieve(number).expect("block frozen")?;
+                return Some(raw_block.len() as u64);
+            }
+        }
+        let size_of = |col| {
+            self.get(col, hash.as_slice())
+                .map_or(0, |slice| slice.as_ref().len())
+        };
+        let prefix = hash.as_slice();
+        let body_size: usize = self
+            .get_iter(
+                COLUMN_BLOCK_BODY,
+                IteratorMode::From(prefix, Direction::Forward),
+            )
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(_key, value)| value.len())
+            .sum();
+        Some(
+            (header_size
+                + size_of(COLUMN_BLOCK_UNCLE)
+                + size_of(COLUMN_BLOCK_PROPOSAL_IDS)
+                + size_of(COLUMN_BLOCK_EXTENSION)
+                + body_size) as u64,
+        )
+    }
+
     /// Get all transaction-hashes in block body by block header hash
     fn get_block_txs_hashes(&self, hash: &packed::Byte32) -> Vec<packed::Byte32> {
         if let Some(cache) = self.cache() {
@@ -172,6 +416,104 @@ pub trait ChainStore: Send + Sync + Sized {
         ret
     }
 
+    /// Computes the transactions root of a block from its stored transaction hashes
+    ///
+    /// Only the transaction hashes and witness hashes are read, transactions are not decoded.
+    /// For a valid block the result equals the `transactions_root` recorded in the header.
+    fn get_block_txs_merkle_root(&self, hash: &packed::Byte32) -> Option<packed::Byte32> {
+        let header = self.get_block_header(hash)?;
+        if let Some(freezer) = self.freezer() {
+            if header.number() > 0 && header.number() < freezer.number() {
+                let raw_block = freezer.retrieve(header.number()).expect("block frozen")?;
+                let raw_block = packed::BlockReader::from_compatible_slice(&raw_block)
+                    .expect("checked data")
+                    .to_entity();
+                return Some(raw_block.into_view().calc_transactions_root());
+            }
+        }
+
+        let prefix = hash.as_slice();
+        let (tx_hashes, tx_witness_hashes): (Vec<_>, Vec<_>) = self
+            .get_iter(
+                COLUMN_BLOCK_BODY,
+                IteratorMode::From(prefix, Direction::Forward),
+            )
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(_key, value)| {
+                let reader = packed::TransactionViewReader::from_slice_should_be_ok(value.as_ref());
+                (reader.hash().to_entity(), reader.witness_hash().to_entity())
+            })
+            .unzip();
+
+        Some(merkle_root(&[
+            merkle_root(&tx_hashes),
+            merkle_root(&tx_witness_hashes),
+        ]))
+    }
+
+    /// Recomputes the transactions root and proposals hash of a block from its stored body and
+    /// proposals, and checks them against the roots committed in the stored header
+    ///
+    /// Transaction hashes are recalculated from the transaction data rather than read from the
+    /// stored views, so a corrupted body is reported even if its cached hashes are stale.
+    fn audit_block(&self, hash: &packed::Byte32) -> Result<(), IntegrityIssue> {
+        let header = self
+            .get_block_header(hash)
+            .ok_or_else(|| IntegrityIssue::MissingBlock(hash.clone()))?;
+        let (transactions_root, proposals_hash) = match self.freezer() {
+            Some(freezer) if header.number() > 0 && header.number() < freezer.number() => {
+                let raw_block = freezer
+                    .retrieve(header.number())
+                    .expect("block frozen")
+                    .ok_or_else(|| IntegrityIssue::MissingBlock(hash.clone()))?;
+                let block = packed::BlockReader::from_compatible_slice(&raw_block)
+                    .expect("checked data")
+                    .to_entity()
+                    .into_view();
+                (block.calc_transactions_root(), block.calc_proposals_hash())
+            }
+            _ => {
+                let prefix = hash.as_slice();
+                let (tx_hashes, tx_witness_hashes): (Vec<_>, Vec<_>) = self
+                    .get_iter(
+                        COLUMN_BLOCK_BODY,
+                        IteratorMode::From(prefix, Direction::Forward),
+                    )
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(|(_key, value)| {
+                        let reader =
+                            packed::TransactionViewReader::from_slice_should_be_ok(value.as_ref());
+                        let data = reader.data();
+                        (data.calc_tx_hash(), data.calc_witness_hash())
+                    })
+                    .unzip();
+                let transactions_root =
+                    merkle_root(&[merkle_root(&tx_hashes), merkle_root(&tx_witness_hashes)]);
+                let proposals_hash = self
+                    .get_block_proposal_txs_ids(hash)
+                    .ok_or_else(|| IntegrityIssue::MissingBlock(hash.clone()))?
+                    .calc_proposals_hash();
+                (transactions_root, proposals_hash)
+            }
+        };
+
+        if transactions_root != header.transactions_root() {
+            return Err(IntegrityIssue::TransactionsRootMismatch {
+                block_hash: hash.clone(),
+                expected: header.transactions_root(),
+                actual: transactions_root,
+            });
+        }
+        if proposals_hash != header.proposals_hash() {
+            return Err(IntegrityIssue::ProposalsHashMismatch {
+                block_hash: hash.clone(),
+                expected: header.proposals_hash(),
+                actual: proposals_hash,
+            });
+        }
+        Ok(())
+    }
+
     /// Get proposal short id by block header hash
     fn get_block_proposal_txs_ids(
         &self,
@@ -246,33 +588,170 @@ pub trait ChainStore: Send + Sync + Sized {
     /// Since v0.106, `BlockExt` added two option fields, so we have to use compatibility mode to read
     fn get_block_ext(&self, block_hash: &packed::Byte32) -> Option<BlockExt> {
         self.get(COLUMN_BLOCK_EXT, block_hash.as_slice())
+            .map(|slice| decode_block_ext(slice.as_ref()))
+    }
+
+    /// Get block exts by block header hashes, in the order of `hashes`
+    ///
+    /// All exts are fetched with a single `multi_get`, unknown hashes yield `None` at their
+    /// position.
+    fn get_block_exts(&self, hashes: &[packed::Byte32]) -> Vec<Option<BlockExt>> {
+        let keys: Vec<&[u8]> = hashes.iter().map(|hash| hash.as_slice()).collect();
+        self.multi_get(COLUMN_BLOCK_EXT, &keys)
+            .into_iter()
+            .map(|value| value.map(|slice| decode_block_ext(&slice)))
+            .collect()
+    }
+
+    /// Gets the total issued capacity up to a block
+    ///
+    /// It is the accumulated capacity `C` tracked in the header's DAO field,
+    /// only the stored header is read.
+    fn get_accumulated_capacity(&self, block_hash: &packed::Byte32) -> Option<u64> {
+        self.get(COLUMN_BLOCK_HEADER, block_hash.as_slice())
             .map(|slice| {
-                let reader =
-                    packed::BlockExtReader::from_compatible_slice_should_be_ok(slice.as_ref());
-                match reader.count_extra_fields() {
-                    0 => reader.unpack(),
-                    2 => packed::BlockExtV1Reader::from_slice_should_be_ok(slice.as_ref()).unpack(),
-                    _ => {
-                        panic!(
-                            "BlockExt storage field count doesn't match, expect 7 or 5, actual {}",
-                            reader.field_count()
-                        )
-                    }
-                }
+                let reader = packed::HeaderViewReader::from_slice_should_be_ok(slice.as_ref());
+                let mut accumulated_capacity = [0u8; 8];
+                accumulated_capacity.copy_from_slice(&reader.data().raw().dao().raw_data()[0..8]);
+                u64::from_le_bytes(accumulated_capacity)
             })
     }
 
+    /// Gets the capacity issued after block `from` up to block `to`
+    ///
+    /// Returns `None` if either header is missing or `from` is not an ancestor of `to`. An
+    /// accumulated capacity decreasing along the chain is reported as
+    /// `StoreError::CapacityUnderflow` instead of wrapping around.
+    fn accumulated_capacity_between(
+        &self,
+        from: &packed::Byte32,
+        to: &packed::Byte32,
+    ) -> Result<Option<Capacity>, StoreError> {
+        let from_number = match self.get_block_header(from) {
+            Some(header) => header.number(),
+            None => return Ok(None),
+        };
+        if self
+            .get_ancestor(to, from_number)
+            .map_or(true, |ancestor| ancestor.hash() != *from)
+        {
+            return Ok(None);
+        }
+        match (
+            self.get_accumulated_capacity(from),
+            self.get_accumulated_capacity(to),
+        ) {
+            (Some(from_capacity), Some(to_capacity)) if to_capacity < from_capacity => {
+                Err(StoreError::CapacityUnderflow {
+                    from: from.clone(),
+                    to: to.clone(),
+                    from_capacity,
+                    to_capacity,
+                })
+            }
+            (Some(from_capacity), Some(to_capacity)) => {
+                Ok(Some(Capacity::shannons(to_capacity - from_capacity)))
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Get block header hash by block number
     fn get_block_hash(&self, number: BlockNumber) -> Option<packed::Byte32> {
+        self.try_get_block_hash(number)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Get the main chain block hash by block number, or the error the read failed with
+    fn try_get_block_hash(
+        &self,
+        number: BlockNumber,
+    ) -> Result<Option<packed::Byte32>, StoreError> {
         let block_number: packed::Uint64 = number.pack();
-        self.get(COLUMN_INDEX, block_number.as_slice())
-            .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())
+        self.try_get(COLUMN_INDEX, block_number.as_slice())?
+            .map(|raw| {
+                packed::Byte32Reader::from_slice(raw.as_ref())
+                    .map(|reader| reader.to_entity())
+                    .map_err(|_| StoreError::decode(COLUMN_INDEX, block_number.as_slice()))
+            })
+            .transpose()
+    }
+
+    /// Gets the main chain block header by block number, through the header cache
+    fn get_header_by_number(&self, number: BlockNumber) -> Option<HeaderView> {
+        self.get_block_hash(number)
+            .and_then(|hash| self.get_block_header(&hash))
+    }
+
+    /// Gets the main chain block by block number
+    fn get_block_by_number(&self, number: BlockNumber) -> Option<BlockView> {
+        self.get_block_hash(number)
+            .and_then(|hash| self.get_block(&hash))
+    }
+
+    /// Gets the uncles of the main chain block by block number, without loading the block
+    ///
+    /// Returns an empty list for a block without uncles and `None` for an unindexed number.
+    fn get_uncles_by_number(&self, number: BlockNumber) -> Option<Vec<UncleBlockView>> {
+        self.get_block_hash(number)
+            .and_then(|hash| self.get_block_uncles(&hash))
+            .map(|uncles| uncles.into_iter().collect())
+    }
+
+    /// Iterates the main chain blocks whose numbers fall in `range`, in ascending order
+    ///
+    /// Stops at the first number without an index entry, e.g. past the tip.
+    fn iter_blocks(&self, range: Range<BlockNumber>) -> impl Iterator<Item = BlockView> + '_ {
+        range.map_while(move |number| {
+            self.get_block_hash(number)
+                .and_then(|hash| self.get_block(&hash))
+        })
+    }
+
+    /// Iterates the main chain headers whose numbers fall in `range`, in ascending order
+    ///
+    /// Like `iter_blocks`, but never loads block bodies.
+    fn iter_headers(&self, range: Range<BlockNumber>) -> impl Iterator<Item = HeaderView> + '_ {
+        range.map_while(move |number| self.get_header_by_number(number))
+    }
+
+    /// Writes the main chain blocks in `range` to `writer`, each as its serialized length in
+    /// 4 little-endian bytes followed by the serialized block
+    ///
+    /// Stops at the tip; returns the number of exported blocks. The output is read back by
+    /// `ChainDB::import_blocks`.
+    fn export_range<W: Write>(&self, range: Range<BlockNumber>, mut writer: W) -> io::Result<u64> {
+        let mut exported = 0;
+        for block in self.iter_blocks(range) {
+            let data = block.data();
+            let len = u32::try_from(data.as_slice().len())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(data.as_slice())?;
+            exported += 1;
+        }
+        writer.flush()?;
+        Ok(exported)
     }
 
     /// Get block number by block header hash
     fn get_block_number(&self, hash: &packed::Byte32) -> Option<BlockNumber> {
-        self.get(COLUMN_INDEX, hash.as_slice())
-            .map(|raw| packed::Uint64Reader::from_slice_should_be_ok(raw.as_ref()).unpack())
+        self.try_get_block_number(hash)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Get the main chain block number by block hash, or the error the read failed with
+    fn try_get_block_number(
+        &self,
+        hash: &packed::Byte32,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        self.try_get(COLUMN_INDEX, hash.as_slice())?
+            .map(|raw| {
+                packed::Uint64Reader::from_slice(raw.as_ref())
+                    .map(|reader| reader.unpack())
+                    .map_err(|_| StoreError::decode(COLUMN_INDEX, hash.as_slice()))
+            })
+            .transpose()
     }
 
     /// TODO(doc): @quake
@@ -280,6 +759,28 @@ pub trait ChainStore: Send + Sync + Sized {
         self.get(COLUMN_INDEX, hash.as_slice()).is_some()
     }
 
+    /// Check the database was initialized with the genesis block `expected`
+    ///
+    /// Databases initialized before the genesis hash was recorded are checked against the block
+    /// indexed at number 0. An uninitialized database passes the check.
+    fn check_genesis(&self, expected: &packed::Byte32) -> Result<(), StoreError> {
+        let stored = match self.try_get(COLUMN_META, META_GENESIS_HASH_KEY)? {
+            Some(raw) => Some(
+                packed::Byte32Reader::from_slice(raw.as_ref())
+                    .map_err(|_| StoreError::decode(COLUMN_META, META_GENESIS_HASH_KEY))?
+                    .to_entity(),
+            ),
+            None => self.try_get_block_hash(0)?,
+        };
+        match stored {
+            Some(actual) if &actual != expected => Err(StoreError::GenesisMismatch {
+                expected: expected.clone(),
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+
     /// TODO(doc): @quake
     fn get_tip_header(&self) -> Option<HeaderView> {
         self.get(COLUMN_META, META_TIP_HEADER_KEY)
@@ -291,6 +792,33 @@ pub trait ChainStore: Send + Sync + Sized {
             .map(Into::into)
     }
 
+    /// Gets the number of the tip block
+    ///
+    /// Resolves the tip hash, then its number by the block index, so the tip header is neither
+    /// decoded nor put into the header cache. Returns `None` when no tip is recorded.
+    fn tip_number(&self) -> Option<BlockNumber> {
+        let tip_hash = self
+            .get(COLUMN_META, META_TIP_HEADER_KEY)
+            .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())?;
+        self.get_block_number(&tip_hash)
+    }
+
+    /// Gets the tip header together with its block ext
+    ///
+    /// Returns `None` only when no tip is recorded.
+    fn get_tip_header_with_ext(&self) -> Option<(HeaderView, BlockExt)> {
+        let tip_hash = self
+            .get(COLUMN_META, META_TIP_HEADER_KEY)
+            .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())?;
+        let header = self
+            .get_block_header(&tip_hash)
+            .expect("tip header must be stored");
+        let ext = self
+            .get_block_ext(&tip_hash)
+            .expect("tip block ext must be stored");
+        Some((header, ext))
+    }
+
     /// Returns true if the transaction confirmed in main chain.
     ///
     /// This function is base on transaction index `COLUMN_TRANSACTION_INFO`.
@@ -305,6 +833,16 @@ pub trait ChainStore: Send + Sync + Sized {
             .map(|(tx, tx_info)| (tx, tx_info.block_hash))
     }
 
+    /// Gets the lock script hashes of a committed transaction's outputs
+    fn get_transaction_output_lock_hashes(&self, hash: &packed::Byte32) -> Option<Vec<H256>> {
+        self.get_transaction(hash).map(|(tx, _block_hash)| {
+            tx.outputs()
+                .into_iter()
+                .map(|output| output.lock().calc_script_hash().unpack())
+                .collect()
+        })
+    }
+
     /// TODO(doc): @quake
     fn get_transaction_info(&self, hash: &packed::Byte32) -> Option<TransactionInfo> {
         self.get(COLUMN_TRANSACTION_INFO, hash.as_slice())
@@ -314,6 +852,105 @@ pub trait ChainStore: Send + Sync + Sized {
             })
     }
 
+    /// Gets the fee paid by a committed transaction
+    ///
+    /// `BlockExt.txs_fees` skips the cellbase, so the fee of the transaction at `index` in its
+    /// block is at `index - 1`. Returns zero for a cellbase, and `None` if the transaction or the
+    /// ext of its block is unknown.
+    fn get_transaction_fee(&self, hash: &packed::Byte32) -> Option<Capacity> {
+        let tx_info = self.get_transaction_info(hash)?;
+        if tx_info.is_cellbase() {
+            return Some(Capacity::zero());
+        }
+        self.get_block_ext(&tx_info.block_hash)?
+            .txs_fees
+            .get(tx_info.index - 1)
+            .copied()
+    }
+
+    /// Gets where a committed cellbase was created and whether it can be spent at `tip_number`
+    ///
+    /// A cellbase matures once `cellbase_maturity` has passed since the epoch of its block, as
+    /// checked against the epoch of the main chain block at `tip_number`, the same rule as the
+    /// transaction maturity verifier. The genesis cellbase is always mature. Returns `None` if
+    /// the transaction is unknown or not a cellbase, or no main chain block is at `tip_number`.
+    fn get_cellbase_maturity_info(
+        &self,
+        tx_hash: &packed::Byte32,
+        tip_number: BlockNumber,
+        cellbase_maturity: EpochNumberWithFraction,
+    ) -> Option<MaturityInfo> {
+        let tx_info = self.get_transaction_info(tx_hash)?;
+        if !tx_info.is_cellbase() {
+            return None;
+        }
+        let tip_epoch = self
+            .get_block_hash(tip_number)
+            .and_then(|hash| self.get_block_header(&hash))?
+            .epoch();
+        let threshold = cellbase_maturity.to_rational() + tx_info.block_epoch.to_rational();
+        Some(MaturityInfo {
+            block_number: tx_info.block_number,
+            block_epoch: tx_info.block_epoch,
+            mature: tx_info.is_genesis() || tip_epoch.to_rational() >= threshold,
+        })
+    }
+
+    /// Gets a page of the transactions of the main chain blocks in `range`, with their block hash
+    ///
+    /// The transactions are flattened in chain order, then the first `skip` are skipped and at
+    /// most `take` are returned. Blocks wholly skipped are only counted by their transaction
+    /// hashes, and no block is read once the page is filled. Stops at the tip.
+    fn get_transactions_in_block_range(
+        &self,
+        range: Range<BlockNumber>,
+        skip: usize,
+        take: usize,
+    ) -> Vec<(TransactionView, packed::Byte32)> {
+        let mut ret = Vec::new();
+        let mut skip = skip;
+        for number in range {
+            if ret.len() >= take {
+                break;
+            }
+            let hash = match self.get_block_hash(number) {
+                Some(hash) => hash,
+                None => break,
+            };
+            let frozen = self
+                .freezer()
+                .map_or(false, |freezer| number > 0 && number < freezer.number());
+            if !frozen {
+                let count = self.get_block_txs_hashes(&hash).len();
+                if skip >= count {
+                    skip -= count;
+                    continue;
+                }
+            }
+            let transactions = if frozen {
+                self.get_block(&hash)
+                    .map(|block| block.transactions())
+                    .unwrap_or_default()
+            } else {
+                self.get_block_body(&hash)
+            };
+            if skip >= transactions.len() {
+                skip -= transactions.len();
+                continue;
+            }
+            let remaining = take - ret.len();
+            ret.extend(
+                transactions
+                    .into_iter()
+                    .skip(skip)
+                    .take(remaining)
+                    .map(|tx| (tx, hash.clone())),
+            );
+            skip = 0;
+        }
+        ret
+    }
+
     /// Gets transaction and associated info with correspond hash
     fn get_transaction_with_info(
         &self,
@@ -339,6 +976,9 @@ pub trait ChainStore: Send + Sync + Sized {
     }
 
     /// Return whether cell is live
+    ///
+    /// Only checks the presence of the cell entry, which is neither copied nor decoded, so
+    /// prefer it over `get_cell` when the `CellMeta` itself is not needed.
     fn have_cell(&self, out_point: &OutPoint) -> bool {
         let key = out_point.to_cell_key();
         self.get(COLUMN_CELL, &key).is_some()
@@ -353,13 +993,37 @@ pub trait ChainStore: Send + Sync + Sized {
         })
     }
 
+    /// Get cells by out points, in the order of `out_points`
+    ///
+    /// All cells are fetched with a single `multi_get`. Unknown or dead cells yield `None` at
+    /// their position, so the result can be zipped with the inputs being resolved.
+    fn get_cells(&self, out_points: &[OutPoint]) -> Vec<Option<CellMeta>> {
+        let keys: Vec<_> = out_points
+            .iter()
+            .map(|out_point| out_point.to_cell_key())
+            .collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_slice()).collect();
+        self.multi_get(COLUMN_CELL, &keys)
+            .into_iter()
+            .zip(out_points)
+            .map(|(value, out_point)| {
+                value.map(|slice| {
+                    let reader = packed::CellEntryReader::from_slice_should_be_ok(&slice);
+                    build_cell_meta_from_reader(out_point.clone(), reader)
+                })
+            })
+            .collect()
+    }
+
     /// TODO(doc): @quake
     fn get_cell_data(&self, out_point: &OutPoint) -> Option<(Bytes, packed::Byte32)> {
         let key = out_point.to_cell_key();
-        if let Some(cache) = self.cache() {
+        if let Some(cache) = self.cache().filter(|cache| cache.cell_data_cache_enabled()) {
             if let Some(cached) = cache.cell_data.lock().get(&key) {
+                cache.record_cell_data(true);
                 return Some(cached.clone());
             }
+            cache.record_cell_data(false);
         };
 
         let ret = self.get(COLUMN_CELL_DATA, &key).map(|slice| {
@@ -373,7 +1037,7 @@ pub trait ChainStore: Send + Sync + Sized {
             }
         });
 
-        if let Some(cache) = self.cache() {
+        if let Some(cache) = self.cache().filter(|cache| cache.cell_data_cache_enabled()) {
             ret.map(|cached| {
                 cache.cell_data.lock().put(key, cached.clone());
                 cached
@@ -386,7 +1050,7 @@ pub trait ChainStore: Send + Sync + Sized {
     /// TODO(doc): @quake
     fn get_cell_data_hash(&self, out_point: &OutPoint) -> Option<packed::Byte32> {
         let key = out_point.to_cell_key();
-        if let Some(cache) = self.cache() {
+        if let Some(cache) = self.cache().filter(|cache| cache.cell_data_cache_enabled()) {
             if let Some(cached) = cache.cell_data_hash.lock().get(&key) {
                 return Some(cached.clone());
             }
@@ -400,7 +1064,7 @@ pub trait ChainStore: Send + Sync + Sized {
             }
         });
 
-        if let Some(cache) = self.cache() {
+        if let Some(cache) = self.cache().filter(|cache| cache.cell_data_cache_enabled()) {
             ret.map(|cached| {
                 cache.cell_data_hash.lock().put(key, cached.clone());
                 cached
@@ -410,6 +1074,146 @@ pub trait ChainStore: Send + Sync + Sized {
         }
     }
 
+    /// Verifies the live cell columns are consistent with each other and with the
+    /// transaction index
+    ///
+    /// Every cell entry should have its data and data hash entries, and belong to a transaction
+    /// committed in the main chain; every cell data entry should have its cell entry.
+    /// This scans the whole cell columns, it is intended for offline integrity checks.
+    fn verify_cell_consistency(&self) -> Vec<IntegrityIssue> {
+        let mut issues = Vec::new();
+        for (key, value) in self.get_iter(COLUMN_CELL, IteratorMode::Start) {
+            let out_point = out_point_from_cell_key(&key);
+            let reader = packed::CellEntryReader::from_slice_should_be_ok(value.as_ref());
+            let committed = self
+                .get_transaction_info(&out_point.tx_hash())
+                .map(|info| info.block_hash.as_slice() == reader.block_hash().as_slice())
+                .unwrap_or(false);
+            if !committed {
+                issues.push(IntegrityIssue::OrphanedCell(out_point.clone()));
+            }
+            if self.get(COLUMN_CELL_DATA, &key).is_none() {
+                issues.push(IntegrityIssue::MissingCellData(out_point.clone()));
+            }
+            if self.get(COLUMN_CELL_DATA_HASH, &key).is_none() {
+                issues.push(IntegrityIssue::MissingCellDataHash(out_point));
+            }
+        }
+        for (key, _value) in self.get_iter(COLUMN_CELL_DATA, IteratorMode::Start) {
+            if self.get(COLUMN_CELL, &key).is_none() {
+                issues.push(IntegrityIssue::OrphanedCellData(out_point_from_cell_key(
+                    &key,
+                )));
+            }
+        }
+        issues
+    }
+
+    /// Checks the main chain index of the blocks whose numbers fall in `range`
+    ///
+    /// For each number, the indexed hash should map back to the same number, and its header should
+    /// be stored with that number and point at the hash indexed at the previous number. Only the
+    /// entries of the range are read, so a bounded range is cheap enough to check on a live node.
+    ///
+    /// The range is clamped at the tip, numbers past it have no index and are not reported.
+    fn verify_index(&self, range: Range<BlockNumber>) -> Vec<IntegrityIssue> {
+        let end = self
+            .tip_number()
+            .map_or(0, |tip_number| tip_number + 1)
+            .min(range.end);
+        let range = range.start..end;
+        let mut issues = Vec::new();
+        let mut parent_hash = range
+            .start
+            .checked_sub(1)
+            .and_then(|number| self.get_block_hash(number));
+        for number in range {
+            let block_hash = match self.get_block_hash(number) {
+                Some(block_hash) => block_hash,
+                None => {
+                    issues.push(IntegrityIssue::MissingIndex(number));
+                    parent_hash = None;
+                    continue;
+                }
+            };
+            match self.get_block_number(&block_hash) {
+                Some(actual) if actual != number => {
+                    issues.push(IntegrityIssue::ReverseIndexMismatch {
+                        block_hash: block_hash.clone(),
+                        expected: number,
+                        actual,
+                    })
+                }
+                Some(_) => {}
+                None => issues.push(IntegrityIssue::MissingReverseIndex {
+                    number,
+                    block_hash: block_hash.clone(),
+                }),
+            }
+            match self.get_block_header(&block_hash) {
+                Some(header) => {
+                    if header.number() != number {
+                        issues.push(IntegrityIssue::HeaderNumberMismatch {
+                            block_hash: block_hash.clone(),
+                            expected: number,
+                            actual: header.number(),
+                        });
+                    }
+                    if let Some(parent_hash) = parent_hash {
+                        if header.parent_hash() != parent_hash {
+                            issues.push(IntegrityIssue::ParentMismatch {
+                                block_hash: block_hash.clone(),
+                                expected: parent_hash,
+                                actual: header.parent_hash(),
+                            });
+                        }
+                    }
+                }
+                None => issues.push(IntegrityIssue::MissingBlock(block_hash.clone())),
+            }
+            parent_hash = Some(block_hash);
+        }
+        issues
+    }
+
+    /// Traverses the live cell set in key order, starting from `start` or from the first cell
+    ///
+    /// The callback receives each cell key (the transaction hash followed by the big-endian output
+    /// index) together with the cell, and may return `ControlFlow::Break` to stop early. A key
+    /// passed back as `start` resumes the traversal from that cell, which allows paginated exports.
+    fn traverse_cell_set_from<F>(&self, start: Option<&[u8]>, mut callback: F)
+    where
+        F: FnMut(&[u8], CellMeta) -> ControlFlow<()>,
+    {
+        let mode = match start {
+            Some(key) => IteratorMode::From(key, Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        for (key, value) in self.get_iter(COLUMN_CELL, mode) {
+            let reader = packed::CellEntryReader::from_slice_should_be_ok(value.as_ref());
+            let cell = build_cell_meta_from_reader(out_point_from_cell_key(&key), reader);
+            if callback(&key, cell).is_break() {
+                break;
+            }
+        }
+    }
+
+    /// Traverses the live cell set in key order, passing each cell together with its decoded
+    /// transaction hash and output index
+    ///
+    /// This is a convenience over [`traverse_cell_set_from`](#method.traverse_cell_set_from) for
+    /// tools auditing the cell column, which care about the out point rather than the raw key.
+    fn traverse_cell_meta<F>(&self, mut callback: F)
+    where
+        F: FnMut(packed::Byte32, u32, CellMeta) -> ControlFlow<()>,
+    {
+        self.traverse_cell_set_from(None, |_key, cell| {
+            let tx_hash = cell.out_point.tx_hash();
+            let index: u32 = cell.out_point.index().unpack();
+            callback(tx_hash, index, cell)
+        });
+    }
+
     /// Gets current epoch ext
     fn get_current_epoch_ext(&self) -> Option<EpochExt> {
         self.get(COLUMN_META, META_CURRENT_EPOCH_KEY)
@@ -429,13 +1233,84 @@ pub trait ChainStore: Send + Sync + Sized {
             .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())
     }
 
+    /// Iterates the epoch exts whose numbers fall in `range`, in ascending order
+    ///
+    /// Each number is resolved by `get_epoch_index` then `get_epoch_ext`. Stops at the first
+    /// number without an epoch, e.g. past the current epoch.
+    fn iter_epoch_exts(&self, range: Range<EpochNumber>) -> impl Iterator<Item = EpochExt> + '_ {
+        range.map_while(move |number| {
+            self.get_epoch_index(number)
+                .and_then(|index| self.get_epoch_ext(&index))
+        })
+    }
+
+    /// Aggregates the fees and uncles of the main chain blocks in an epoch
+    ///
+    /// This reads the block ext of every block in the epoch, so it is expensive
+    /// and only computed on explicit calls.
+    fn get_epoch_aggregate(&self, number: EpochNumber) -> Option<EpochAggregate> {
+        let epoch = self
+            .get_epoch_index(number)
+            .and_then(|index| self.get_epoch_ext(&index))?;
+        let start_number = epoch.start_number();
+        // `total_uncles_count` is accumulated, so uncles of a block are the delta to its parent
+        let mut prev_total_uncles_count = if start_number == 0 {
+            0
+        } else {
+            let parent_hash = self.get_block_hash(start_number - 1)?;
+            self.get_block_ext(&parent_hash)?.total_uncles_count
+        };
+
+        let mut aggregate = EpochAggregate::default();
+        for block_number in start_number..start_number + epoch.length() {
+            let ext = match self
+                .get_block_hash(block_number)
+                .and_then(|hash| self.get_block_ext(&hash))
+            {
+                Some(ext) => ext,
+                // the epoch is not finished yet
+                None => break,
+            };
+            for fee in ext.txs_fees {
+                aggregate.txs_fees = aggregate.txs_fees.safe_add(fee).expect("txs fees overflow");
+            }
+            aggregate.uncles_count += ext
+                .total_uncles_count
+                .saturating_sub(prev_total_uncles_count);
+            prev_total_uncles_count = ext.total_uncles_count;
+            aggregate.blocks_count += 1;
+        }
+        Some(aggregate)
+    }
+
+    /// Gets the numbers of the main chain blocks which start an epoch, up to the tip
+    ///
+    /// The numbers are read from the stored epoch exts, blocks are not scanned.
+    fn epoch_start_numbers(&self) -> Vec<BlockNumber> {
+        let tip_number = match self.get_tip_header() {
+            Some(tip) => tip.number(),
+            None => return Vec::new(),
+        };
+        (0..)
+            .map_while(|number| {
+                self.get_epoch_index(number)
+                    .and_then(|index| self.get_epoch_ext(&index))
+            })
+            .map(|epoch| epoch.start_number())
+            .take_while(|start_number| *start_number <= tip_number)
+            .collect()
+    }
+
     /// Gets epoch index by block hash
     fn get_block_epoch_index(&self, block_hash: &packed::Byte32) -> Option<packed::Byte32> {
         self.get(COLUMN_BLOCK_EPOCH, block_hash.as_slice())
             .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())
     }
 
-    /// TODO(doc): @quake
+    /// Gets the epoch ext of the epoch a block belongs to
+    ///
+    /// Resolves the block's epoch index by `get_block_epoch_index`, then the epoch ext by
+    /// `get_epoch_ext`. Returns `None` if the block has no epoch index.
     fn get_block_epoch(&self, hash: &packed::Byte32) -> Option<EpochExt> {
         self.get_block_epoch_index(hash)
             .and_then(|index| self.get_epoch_ext(&index))
@@ -454,9 +1329,12 @@ pub trait ChainStore: Send + Sync + Sized {
         })
     }
 
-    /// TODO(doc): @quake
+    /// Whether the block is stored, on the main chain or not
+    ///
+    /// Only checks the presence of the header entry, which is neither copied nor decoded, so
+    /// prefer it over `get_block_header` when the header itself is not needed.
     fn block_exists(&self, hash: &packed::Byte32) -> bool {
-        if let Some(cache) = self.cache() {
+        if let Some(cache) = self.cache().filter(|cache| cache.header_cache_enabled()) {
             if cache.headers.lock().get(hash).is_some() {
                 return true;
             }
@@ -481,6 +1359,15 @@ pub trait ChainStore: Send + Sync + Sized {
             .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(raw.as_ref()).to_entity())
     }
 
+    /// Gets the number of the latest block with built filter data
+    ///
+    /// The cursor is written in the same transaction as the filter by `insert_block_filter`.
+    /// Returns `None` before any filter is built, or if that block is no longer on the main chain.
+    fn get_latest_built_filter_data_block_number(&self) -> Option<BlockNumber> {
+        self.get_latest_built_filter_data_block_hash()
+            .and_then(|hash| self.get_block_number(&hash))
+    }
+
     /// Gets block filter data by block hash
     fn get_block_filter(&self, hash: &packed::Byte32) -> Option<packed::Bytes> {
         self.get(COLUMN_BLOCK_FILTER, hash.as_slice())
@@ -558,20 +1445,282 @@ pub trait ChainStore: Send + Sync + Sized {
             })
     }
 
+    /// Gets the contiguous headers from `from` up to `to` (inclusive) along parent links
+    ///
+    /// The headers are in ascending order by number. Returns `None` if `to` doesn't descend
+    /// from `from` or the sequence would be longer than `max`.
+    fn get_header_chain(
+        &self,
+        from: &packed::Byte32,
+        to: &packed::Byte32,
+        max: usize,
+    ) -> Option<Vec<HeaderView>> {
+        let from_header = self.get_block_header(from)?;
+        let mut header = self.get_block_header(to)?;
+        if header.number() < from_header.number()
+            || header.number() - from_header.number() >= max as u64
+        {
+            return None;
+        }
+
+        let mut headers = Vec::with_capacity((header.number() - from_header.number() + 1) as usize);
+        while header.number() > from_header.number() {
+            let parent_hash = header.parent_hash();
+            headers.push(header);
+            header = self.get_block_header(&parent_hash)?;
+        }
+        if &header.hash() != from {
+            return None;
+        }
+        headers.push(header);
+        headers.reverse();
+        Some(headers)
+    }
+
+    /// Get the median timestamp of the `count` blocks ending at `block_hash`, **including the
+    /// given one**
+    ///
+    /// Fewer blocks are sampled if the chain is shorter than `count`, and a zero `count` is
+    /// treated as one. The greater one of the middle two is returned when the sample count is
+    /// even, the same as `HeaderFieldsProvider::block_median_time`. Returns `None` if the block
+    /// is unknown.
+    fn get_block_median_time(&self, block_hash: &packed::Byte32, count: usize) -> Option<u64> {
+        let mut header = self.get_block_header(block_hash)?;
+        let mut timestamps: Vec<u64> = Vec::with_capacity(count.max(1));
+        loop {
+            timestamps.push(header.timestamp());
+            if timestamps.len() >= count || header.is_genesis() {
+                break;
+            }
+            header = match self.get_block_header(&header.parent_hash()) {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+
+        timestamps.sort_unstable();
+        Some(timestamps[timestamps.len() >> 1])
+    }
+
     /// Gets ancestor block header by a base block hash and number
+    ///
+    /// Walks the parent links until reaching the main chain, from where the header is resolved
+    /// through the number index. Returns `None` if `number` is greater than the base's number.
     fn get_ancestor(&self, base: &packed::Byte32, number: BlockNumber) -> Option<HeaderView> {
-        let header = self.get_block_header(base)?;
-        if number > header.number() {
-            None
-        } else if number == header.number() {
-            Some(header)
-        } else if self.is_main_chain(base) {
-            self.get_block_hash(number)
-                .and_then(|hash| self.get_block_header(&hash))
-        } else {
-            self.get_ancestor(&header.parent_hash(), number)
+        let mut header = self.get_block_header(base)?;
+        loop {
+            if number > header.number() {
+                return None;
+            } else if number == header.number() {
+                return Some(header);
+            } else if self.is_main_chain(&header.hash()) {
+                return self
+                    .get_block_hash(number)
+                    .and_then(|hash| self.get_block_header(&hash));
+            }
+            header = self.get_block_header(&header.parent_hash())?;
         }
     }
+
+    /// Gets the hash of the latest common ancestor of blocks `a` and `b`
+    ///
+    /// The higher block is walked back to the height of the lower one, then both step back
+    /// together until they meet, reading headers only. If one block is an ancestor of the other
+    /// it is returned. Returns `None` if either block or any header on the way is unknown.
+    fn fork_point(&self, a: &packed::Byte32, b: &packed::Byte32) -> Option<packed::Byte32> {
+        let mut a = self.get_block_header(a)?;
+        let mut b = self.get_block_header(b)?;
+        while a.number() > b.number() {
+            a = self.get_block_header(&a.parent_hash())?;
+        }
+        while b.number() > a.number() {
+            b = self.get_block_header(&b.parent_hash())?;
+        }
+        while a.hash() != b.hash() {
+            if a.number() == 0 {
+                return None;
+            }
+            a = self.get_block_header(&a.parent_hash())?;
+            b = self.get_block_header(&b.parent_hash())?;
+        }
+        Some(a.hash())
+    }
+
+    /// Collects the proposal ids of the blocks numbered in `window` on the chain ending at
+    /// `tip_hash`
+    ///
+    /// The window is clamped to the tip, the blocks are reached by walking back from it, so
+    /// the tip may be on a fork. Only the proposals of the blocks themselves are collected, not
+    /// those of their uncles. Returns an empty set if `tip_hash` is unknown.
+    fn proposals_in_window(
+        &self,
+        tip_hash: &packed::Byte32,
+        window: Range<BlockNumber>,
+    ) -> HashSet<packed::ProposalShortId> {
+        let mut proposals = HashSet::new();
+        let tip_number = match self.get_block_header(tip_hash) {
+            Some(header) => header.number(),
+            None => return proposals,
+        };
+        let end = window.end.min(tip_number + 1);
+        if window.start >= end {
+            return proposals;
+        }
+        let mut header = match self.get_ancestor(tip_hash, end - 1) {
+            Some(header) => header,
+            None => return proposals,
+        };
+        loop {
+            if let Some(ids) = self.get_block_proposal_txs_ids(&header.hash()) {
+                proposals.extend(ids);
+            }
+            // stops at genesis at the latest, its number is never above `window.start`
+            if header.number() <= window.start {
+                break;
+            }
+            header = match self.get_block_header(&header.parent_hash()) {
+                Some(header) => header,
+                None => break,
+            };
+        }
+        proposals
+    }
+}
+
+/// A block without its transactions and uncles, see `ChainStore::get_block_skeleton`
+#[derive(Clone, Debug)]
+pub struct BlockSkeleton {
+    /// The block header
+    pub header: HeaderView,
+    /// Hashes of the uncles, in the block order
+    pub uncle_hashes: Vec<packed::Byte32>,
+    /// The proposal short ids
+    pub proposals: packed::ProposalShortIdVec,
+    /// Hashes of the transactions, in the block order
+    pub tx_hashes: Vec<packed::Byte32>,
+}
+
+/// Reward-relevant aggregate of the blocks in an epoch
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EpochAggregate {
+    /// The number of blocks aggregated, less than the epoch length if the epoch is not finished
+    pub blocks_count: u64,
+    /// Sum of the transaction fees of the blocks
+    pub txs_fees: Capacity,
+    /// Sum of the uncles count of the blocks
+    pub uncles_count: u64,
+}
+
+/// The maturity of a cellbase, see `ChainStore::get_cellbase_maturity_info`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaturityInfo {
+    /// The number of the block the cellbase was created in
+    pub block_number: BlockNumber,
+    /// The epoch of the block the cellbase was created in
+    pub block_epoch: EpochNumberWithFraction,
+    /// Whether the cellbase outputs can be spent at the given tip
+    pub mature: bool,
+}
+
+/// An inconsistency found by the store integrity checks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// The cell entry's transaction is not committed in the main chain
+    OrphanedCell(OutPoint),
+    /// The cell entry has no cell data entry
+    MissingCellData(OutPoint),
+    /// The cell entry has no cell data hash entry
+    MissingCellDataHash(OutPoint),
+    /// The cell data entry has no cell entry
+    OrphanedCellData(OutPoint),
+    /// The block header, body or proposals is not stored
+    MissingBlock(packed::Byte32),
+    /// The transactions root recomputed from the stored body differs from the header's
+    TransactionsRootMismatch {
+        /// The audited block
+        block_hash: packed::Byte32,
+        /// The root committed in the header
+        expected: packed::Byte32,
+        /// The root recomputed from the body
+        actual: packed::Byte32,
+    },
+    /// The proposals hash recomputed from the stored proposals differs from the header's
+    ProposalsHashMismatch {
+        /// The audited block
+        block_hash: packed::Byte32,
+        /// The hash committed in the header
+        expected: packed::Byte32,
+        /// The hash recomputed from the proposals
+        actual: packed::Byte32,
+    },
+    /// No block hash is indexed at the main chain number
+    MissingIndex(BlockNumber),
+    /// The block hash indexed at the number has no `hash -> number` entry
+    MissingReverseIndex {
+        /// The main chain number
+        number: BlockNumber,
+        /// The hash indexed at the number
+        block_hash: packed::Byte32,
+    },
+    /// The `hash -> number` entry differs from the number the hash is indexed at
+    ReverseIndexMismatch {
+        /// The hash indexed at the number
+        block_hash: packed::Byte32,
+        /// The main chain number
+        expected: BlockNumber,
+        /// The number in the `hash -> number` entry
+        actual: BlockNumber,
+    },
+    /// The stored header number differs from the number the hash is indexed at
+    HeaderNumberMismatch {
+        /// The hash indexed at the number
+        block_hash: packed::Byte32,
+        /// The main chain number
+        expected: BlockNumber,
+        /// The number in the header
+        actual: BlockNumber,
+    },
+    /// The header's parent is not the hash indexed at the previous number
+    ParentMismatch {
+        /// The hash indexed at the number
+        block_hash: packed::Byte32,
+        /// The hash indexed at the previous number
+        expected: packed::Byte32,
+        /// The parent hash in the header
+        actual: packed::Byte32,
+    },
+}
+
+fn verify_read_hash(col: Col, key: &[u8], expected: &packed::Byte32, computed: &packed::Byte32) {
+    if expected != computed {
+        panic!(
+            "{}",
+            StoreError::Corrupt {
+                col,
+                key: key.to_vec(),
+                expected: expected.clone(),
+                computed: computed.clone(),
+            }
+        );
+    }
+}
+
+fn decode_block_ext(slice: &[u8]) -> BlockExt {
+    let reader = packed::BlockExtReader::from_compatible_slice_should_be_ok(slice);
+    match reader.count_extra_fields() {
+        0 => reader.unpack(),
+        2 => packed::BlockExtV1Reader::from_slice_should_be_ok(slice).unpack(),
+        _ => {
+            panic!(
+                "BlockExt storage field count doesn't match, expect 7 or 5, actual {}",
+                reader.field_count()
+            )
+        }
+    }
+}
+
+fn out_point_from_cell_key(key: &[u8]) -> OutPoint {
+    OutPoint::from_cell_key(key).expect("cell key should be 36 bytes")
 }
 
 fn build_cell_meta_from_reader(out_point: OutPoint, reader: packed::CellEntryReader) -> CellMeta {
@@ -1,11 +1,79 @@
-use ckb_chain_spec::consensus::ConsensusBuilder;
-use ckb_db::RocksDB;
-use ckb_db_schema::{COLUMNS, COLUMN_BLOCK_HEADER};
+use ckb_app_config::StoreConfig;
+use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder};
+use ckb_db::{iter::IteratorMode, RocksDB};
+use ckb_db_schema::{
+    COLUMNS, COLUMN_BLOCK_BODY, COLUMN_BLOCK_HEADER, COLUMN_CELL, COLUMN_INDEX, COLUMN_META,
+    COLUMN_NUMBER_HASH, META_GENESIS_HASH_KEY,
+};
 use ckb_freezer::Freezer;
-use ckb_types::{core::BlockExt, packed, prelude::*};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        BlockBuilder, BlockExt, BlockView, Capacity, EpochExt, EpochNumberWithFraction, HeaderView,
+        TransactionBuilder, UncleBlockView,
+    },
+    packed,
+    prelude::*,
+    H256, U256,
+};
+use std::collections::HashSet;
+use std::ops::ControlFlow;
 use tempfile::TempDir;
 
-use crate::{db::ChainDB, store::ChainStore};
+use crate::{
+    attach_block_cell, db::ChainDB, detach_blocks, store::ChainStore, CacheStats, EpochAggregate,
+    IntegrityIssue, MaturityInfo, StoreError,
+};
+
+fn insert_main_chain_block(store: &ChainDB, block: &BlockView, ext: &BlockExt) {
+    let txn = store.begin_transaction();
+    txn.insert_block(block).unwrap();
+    txn.insert_block_ext(&block.hash(), ext).unwrap();
+    txn.attach_block(block).unwrap();
+    txn.commit().unwrap();
+}
+
+fn new_store() -> (TempDir, ChainDB, Consensus) {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    (tmp_dir, store, consensus)
+}
+
+fn main_chain_ext(block: &BlockView) -> BlockExt {
+    BlockExt {
+        received_at: block.timestamp(),
+        total_difficulty: block.difficulty(),
+        total_uncles_count: 0,
+        verified: Some(true),
+        txs_fees: vec![],
+        cycles: None,
+        txs_sizes: None,
+    }
+}
+
+// Attaches `count` empty blocks of the genesis epoch on top of the genesis block
+fn build_main_chain(store: &ChainDB, count: u64) -> Vec<BlockView> {
+    let genesis_hash = store.get_block_hash(0).unwrap();
+    let epoch = store.get_block_epoch(&genesis_hash).unwrap();
+    let mut parent = store.get_block_header(&genesis_hash).unwrap();
+    let mut blocks = Vec::new();
+    for _ in 0..count {
+        let number = parent.number() + 1;
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number(number.pack())
+            .epoch(epoch.number_with_fraction(number).pack())
+            .compact_target(parent.compact_target().pack())
+            .build();
+        insert_main_chain_block(store, &block, &main_chain_ext(&block));
+        parent = block.header();
+        blocks.push(block);
+    }
+    blocks
+}
 
 #[test]
 fn save_and_get_block() {
@@ -69,6 +137,209 @@ fn save_and_get_block_ext() {
     assert_eq!(ext, store.get_block_ext(&hash).unwrap());
 }
 
+#[test]
+fn get_transaction_fee() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    // a cellbase followed by three transactions told apart by their version
+    let transactions: Vec<_> = (0..4u32)
+        .map(|version| {
+            TransactionBuilder::default()
+                .version(version.pack())
+                .build()
+        })
+        .collect();
+    let block = BlockBuilder::default()
+        .parent_hash(consensus.genesis_hash())
+        .number(1u64.pack())
+        .transactions(transactions.clone())
+        .build();
+    let fees: Vec<_> = (1..=3).map(Capacity::shannons).collect();
+    let ext = BlockExt {
+        txs_fees: fees.clone(),
+        ..main_chain_ext(&block)
+    };
+    insert_main_chain_block(&store, &block, &ext);
+
+    assert_eq!(
+        Some(Capacity::zero()),
+        store.get_transaction_fee(&transactions[0].hash())
+    );
+    for (tx, fee) in transactions[1..].iter().zip(fees) {
+        assert_eq!(Some(fee), store.get_transaction_fee(&tx.hash()));
+    }
+    assert!(store.get_transaction_fee(&packed::Byte32::zero()).is_none());
+}
+
+#[test]
+fn get_cellbase_maturity_info() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    // epochs of 4 blocks, block n is at index n % 4 of epoch n / 4
+    let epoch = |number: u64| EpochNumberWithFraction::new(number / 4, number % 4, 4);
+    let mut parent = consensus.genesis_block().header();
+    let blocks: Vec<_> = (1..=4u64)
+        .map(|number| {
+            let cellbase = TransactionBuilder::default()
+                .input(packed::CellInput::new_cellbase_input(number))
+                .build();
+            let tx = TransactionBuilder::default().version(number.pack()).build();
+            let block = BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number(number.pack())
+                .epoch(epoch(number).pack())
+                .transactions(vec![cellbase, tx])
+                .build();
+            insert_main_chain_block(&store, &block, &BlockExt::default());
+            parent = block.header();
+            block
+        })
+        .collect();
+
+    // the cellbase of block 1 at 1/4 matures half an epoch later, at block 3
+    let maturity = EpochNumberWithFraction::new(0, 2, 4);
+    let cellbase_hash = blocks[0].transactions()[0].hash();
+    let info = |tip_number| store.get_cellbase_maturity_info(&cellbase_hash, tip_number, maturity);
+    let maturity_info = |mature| MaturityInfo {
+        block_number: 1,
+        block_epoch: epoch(1),
+        mature,
+    };
+    assert_eq!(info(1), Some(maturity_info(false)));
+    assert_eq!(info(2), Some(maturity_info(false)));
+    assert_eq!(info(3), Some(maturity_info(true)));
+    assert_eq!(info(4), Some(maturity_info(true)));
+    // no main chain block at the tip number
+    assert_eq!(info(5), None);
+
+    // not a cellbase, or unknown
+    let tx_hash = blocks[0].transactions()[1].hash();
+    assert!(store
+        .get_cellbase_maturity_info(&tx_hash, 4, maturity)
+        .is_none());
+    assert!(store
+        .get_cellbase_maturity_info(&packed::Byte32::zero(), 4, maturity)
+        .is_none());
+
+    // the genesis cellbase is always mature
+    let genesis_cellbase = consensus.genesis_block().transactions()[0].hash();
+    assert!(
+        store
+            .get_cellbase_maturity_info(&genesis_cellbase, 0, maturity)
+            .unwrap()
+            .mature
+    );
+}
+
+#[test]
+fn get_transactions_in_block_range() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    // blocks 1..=3 of three transactions each, told apart by their version
+    let mut parent = consensus.genesis_block().header();
+    let mut expected = Vec::new();
+    for number in 1..=3u64 {
+        let transactions: Vec<_> = (0..3u32)
+            .map(|i| {
+                TransactionBuilder::default()
+                    .version((number as u32 * 10 + i).pack())
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number(number.pack())
+            .transactions(transactions.clone())
+            .build();
+        let ext = main_chain_ext(&block);
+        insert_main_chain_block(&store, &block, &ext);
+        expected.extend(transactions.into_iter().map(|tx| (tx, block.hash())));
+        parent = block.header();
+    }
+
+    assert_eq!(
+        expected,
+        store.get_transactions_in_block_range(1..4, 0, 100)
+    );
+    // a page across the boundary of blocks 1 and 2
+    assert_eq!(
+        expected[2..5].to_vec(),
+        store.get_transactions_in_block_range(1..4, 2, 3)
+    );
+    // the next page starts in block 2 and ends in block 3
+    assert_eq!(
+        expected[5..8].to_vec(),
+        store.get_transactions_in_block_range(1..4, 5, 3)
+    );
+    assert_eq!(
+        expected[3..6].to_vec(),
+        store.get_transactions_in_block_range(2..3, 0, 10)
+    );
+    assert!(store
+        .get_transactions_in_block_range(1..4, 9, 10)
+        .is_empty());
+    assert!(store.get_transactions_in_block_range(1..4, 0, 0).is_empty());
+    // stops at the tip
+    assert_eq!(
+        expected[6..].to_vec(),
+        store.get_transactions_in_block_range(3..10, 0, 10)
+    );
+}
+
+#[test]
+fn get_block_size() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block();
+    let size = store.get_block_size(&genesis.hash()).unwrap();
+    assert!(size > 0);
+    // the stored views carry the hashes on top of the serialized block
+    assert!(size >= genesis.data().as_slice().len() as u64);
+    assert!(store.get_block_size(&packed::Byte32::zero()).is_none());
+}
+
+#[test]
+fn block_exists() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let block = consensus.genesis_block();
+
+    let hash = block.hash();
+    assert!(!store.block_exists(&hash));
+    let txn = store.begin_transaction();
+    txn.insert_block(block).unwrap();
+    txn.commit().unwrap();
+    assert!(store.block_exists(&hash));
+    assert!(!store.block_exists(&packed::Byte32::zero()));
+}
+
+#[test]
+fn tip_number() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    assert!(store.tip_number().is_none());
+
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    assert_eq!(Some(0), store.tip_number());
+    assert!(store.cache().unwrap().headers.lock().is_empty());
+
+    let genesis = consensus.genesis_block();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .build();
+    let ext = store.get_block_ext(&genesis.hash()).unwrap();
+    insert_main_chain_block(&store, &block, &ext);
+    let txn = store.begin_transaction();
+    txn.insert_tip_header(&block.header()).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(Some(1), store.tip_number());
+}
+
 #[test]
 fn index_store() {
     let tmp_dir = TempDir::new().unwrap();
@@ -90,6 +361,1824 @@ fn index_store() {
     assert_eq!(block.header(), store.get_tip_header().unwrap());
 }
 
+#[test]
+fn get_header_by_number() {
+    let (_tmp_dir, store, _) = new_store();
+    build_main_chain(&store, 3);
+
+    for number in 0..=3 {
+        let expected = store
+            .get_block_hash(number)
+            .and_then(|hash| store.get_block_header(&hash));
+        assert!(expected.is_some());
+        assert_eq!(expected, store.get_header_by_number(number));
+
+        let block = store.get_block_by_number(number).unwrap();
+        assert_eq!(expected, Some(block.header()));
+    }
+    assert!(store.get_header_by_number(4).is_none());
+    assert!(store.get_block_by_number(4).is_none());
+}
+
+#[test]
+fn get_block_exts() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+
+    let exts: Vec<_> = (1..=3u64)
+        .map(|i| {
+            let hash = packed::Byte32::new([i as u8; 32]);
+            let ext = BlockExt {
+                received_at: i,
+                total_difficulty: U256::from(i * 100),
+                total_uncles_count: i,
+                verified: Some(true),
+                txs_fees: vec![Capacity::shannons(i)],
+                cycles: Some(vec![i]),
+                txs_sizes: Some(vec![i]),
+            };
+            (hash, ext)
+        })
+        .collect();
+    let txn = store.begin_transaction();
+    for (hash, ext) in &exts {
+        txn.insert_block_ext(hash, ext).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let hashes = vec![
+        exts[2].0.clone(),
+        packed::Byte32::zero(),
+        exts[0].0.clone(),
+        exts[1].0.clone(),
+        exts[2].0.clone(),
+    ];
+    let expected = vec![
+        Some(exts[2].1.clone()),
+        None,
+        Some(exts[0].1.clone()),
+        Some(exts[1].1.clone()),
+        Some(exts[2].1.clone()),
+    ];
+    assert_eq!(store.get_block_exts(&hashes), expected);
+    assert_eq!(store.get_snapshot().get_block_exts(&hashes), expected);
+    assert_eq!(store.begin_transaction().get_block_exts(&hashes), expected);
+    assert!(store.get_block_exts(&[]).is_empty());
+}
+
+#[test]
+fn get_block_headers() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let genesis = consensus.genesis_block();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .build();
+
+    let txn = store.begin_transaction();
+    txn.insert_block(genesis).unwrap();
+    txn.insert_block(&block).unwrap();
+    txn.commit().unwrap();
+
+    // warm the cache with the genesis header only
+    assert!(store.get_block_header(&genesis.hash()).is_some());
+    let cache = store.cache().unwrap();
+    assert!(!cache.headers.lock().contains(&block.hash()));
+
+    let hashes = vec![
+        block.hash(),
+        packed::Byte32::zero(),
+        genesis.hash(),
+        block.hash(),
+    ];
+    let expected = vec![
+        Some(block.header()),
+        None,
+        Some(genesis.header()),
+        Some(block.header()),
+    ];
+    // the snapshot shares the cache, it reads the misses and warms the cache for `store`
+    assert_eq!(store.get_snapshot().get_block_headers(&hashes), expected);
+    assert_eq!(store.get_block_headers(&hashes), expected);
+    assert!(cache.headers.lock().contains(&block.hash()));
+    assert!(!cache.headers.lock().contains(&packed::Byte32::zero()));
+    assert!(store.get_block_headers(&[]).is_empty());
+}
+
+#[test]
+fn cache_stats() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let genesis = consensus.genesis_block();
+
+    let txn = store.begin_transaction();
+    txn.insert_block(genesis).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(store.cache_stats(), CacheStats::default());
+
+    store.get_block_header(&genesis.hash());
+    store.get_block_header(&genesis.hash());
+    store.get_block_header(&packed::Byte32::zero());
+    store.get_block_headers(&[genesis.hash(), packed::Byte32::zero()]);
+
+    let out_point = genesis.transactions()[0].output_pts()[0].clone();
+    store.get_cell_data(&out_point);
+
+    let stats = store.cache_stats();
+    assert_eq!(stats.header_hits, 2);
+    assert_eq!(stats.header_misses, 3);
+    assert_eq!(stats.cell_data_hits, 0);
+    assert_eq!(stats.cell_data_misses, 1);
+
+    store.reset_cache_stats();
+    assert_eq!(store.cache_stats(), CacheStats::default());
+    // snapshots share the cache and its counters
+    store.get_snapshot().get_block_header(&genesis.hash());
+    assert_eq!(store.cache_stats().header_hits, 1);
+}
+
+#[test]
+fn body_cache() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let config = StoreConfig {
+        body_cache_size: 2,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    let consensus = ConsensusBuilder::default().build();
+    let genesis = consensus.genesis_block();
+
+    let txn = store.begin_transaction();
+    txn.insert_block(genesis).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        genesis.transactions(),
+        store.get_block_body(&genesis.hash())
+    );
+    assert_eq!(
+        genesis.transactions(),
+        store.get_block_body(&genesis.hash())
+    );
+    let stats = store.cache_stats();
+    assert_eq!(stats.body_hits, 1);
+    assert_eq!(stats.body_misses, 1);
+
+    // the unknown block has an empty body which is not cached
+    assert!(store.get_block_body(&packed::Byte32::zero()).is_empty());
+    assert!(store.get_block_body(&packed::Byte32::zero()).is_empty());
+    assert_eq!(store.cache_stats().body_misses, 3);
+
+    // disabled by default
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    store.init(&consensus).unwrap();
+    store.get_block_body(&genesis.hash());
+    store.get_block_body(&genesis.hash());
+    assert_eq!(store.cache_stats(), CacheStats::default());
+}
+
+#[test]
+fn iter_blocks_and_headers() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut blocks = vec![consensus.genesis_block().clone()];
+    blocks.extend(build_main_chain(&store, 3));
+
+    let iterated: Vec<_> = store.iter_blocks(1..3).collect();
+    assert_eq!(iterated, blocks[1..3].to_vec());
+    // stops at the tip
+    let iterated: Vec<_> = store.iter_blocks(2..10).collect();
+    assert_eq!(iterated, blocks[2..].to_vec());
+
+    let headers: Vec<_> = store.iter_headers(0..10).collect();
+    let expected: Vec<_> = blocks.iter().map(|block| block.header()).collect();
+    assert_eq!(headers, expected);
+    assert_eq!(store.iter_headers(4..10).count(), 0);
+    assert_eq!(store.iter_blocks(3..3).count(), 0);
+}
+
+#[test]
+fn get_tip_header_with_ext() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    assert!(store.get_tip_header_with_ext().is_none());
+
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    let genesis = consensus.genesis_block();
+
+    let (header, ext) = store.get_tip_header_with_ext().unwrap();
+    assert_eq!(header, genesis.header());
+    assert_eq!(ext.total_difficulty, genesis.difficulty());
+    assert_eq!(Some(header), store.get_tip_header());
+}
+
+#[test]
+fn snapshot_keeps_consistent_view() {
+    let (_tmp_dir, store, consensus) = new_store();
+    let genesis = consensus.genesis_block();
+
+    let snapshot = store.get_snapshot();
+
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .build();
+    let ext = main_chain_ext(&block);
+    let txn = store.begin_transaction();
+    txn.insert_block(&block).unwrap();
+    txn.insert_block_ext(&block.hash(), &ext).unwrap();
+    txn.attach_block(&block).unwrap();
+    txn.insert_tip_header(&block.header()).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(Some(block.header()), store.get_tip_header());
+    assert_eq!(Some(genesis.header()), snapshot.get_tip_header());
+    assert!(snapshot.get_block_hash(1).is_none());
+    assert!(!snapshot.is_main_chain(&block.hash()));
+    assert_eq!(Some(block.hash()), store.get_snapshot().get_block_hash(1));
+}
+
+#[test]
+fn verify_on_read() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let config = StoreConfig {
+        verify_on_read: true,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    let consensus = ConsensusBuilder::default().build();
+    let block = consensus.genesis_block();
+
+    let txn = store.begin_transaction();
+    txn.insert_block(block).unwrap();
+    txn.commit().unwrap();
+    assert!(store.verify_on_read());
+    assert_eq!(block, &store.get_block(&block.hash()).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "corrupted data read from column")]
+fn verify_on_read_detects_mismatch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let config = StoreConfig {
+        verify_on_read: true,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    let consensus = ConsensusBuilder::default().build();
+    let header = consensus.genesis_block().header();
+
+    // store a valid header under a key that is not its hash
+    let wrong_hash = packed::Byte32::zero();
+    let txn = store.begin_transaction();
+    txn.insert_raw(
+        COLUMN_BLOCK_HEADER,
+        wrong_hash.as_slice(),
+        header.pack().as_slice(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    store.get_block_header(&wrong_hash);
+}
+
+#[test]
+fn try_get_block_header_reports_errors() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let config = StoreConfig {
+        verify_on_read: true,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    let header = consensus.genesis_block().header();
+
+    assert_eq!(
+        Ok(Some(header.clone())),
+        store.try_get_block_header(&header.hash())
+    );
+    assert_eq!(Ok(Some(header.hash())), store.try_get_block_hash(0));
+    assert_eq!(Ok(Some(0)), store.try_get_block_number(&header.hash()));
+    assert_eq!(
+        Ok(None),
+        store.try_get_block_header(&packed::Byte32::new([1u8; 32]))
+    );
+
+    let wrong_hash = packed::Byte32::zero();
+    let garbage_hash = packed::Byte32::new([2u8; 32]);
+    let txn = store.begin_transaction();
+    txn.insert_raw(
+        COLUMN_BLOCK_HEADER,
+        wrong_hash.as_slice(),
+        header.pack().as_slice(),
+    )
+    .unwrap();
+    txn.insert_raw(COLUMN_BLOCK_HEADER, garbage_hash.as_slice(), &[0u8; 3])
+        .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        Err(StoreError::Corrupt {
+            col: COLUMN_BLOCK_HEADER,
+            key: wrong_hash.as_slice().to_vec(),
+            expected: wrong_hash.clone(),
+            computed: header.hash(),
+        }),
+        store.try_get_block_header(&wrong_hash)
+    );
+    assert_eq!(
+        Err(StoreError::Decode {
+            col: COLUMN_BLOCK_HEADER,
+            key: garbage_hash.as_slice().to_vec(),
+        }),
+        store.try_get_block_header(&garbage_hash)
+    );
+}
+
+#[test]
+fn check_genesis() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let genesis_hash = consensus.genesis_hash();
+    let other_hash = packed::Byte32::new([1u8; 32]);
+
+    // nothing stored yet
+    assert_eq!(Ok(()), store.check_genesis(&other_hash));
+
+    store.init(&consensus).unwrap();
+    assert_eq!(
+        Some(genesis_hash.as_slice().to_vec()),
+        store
+            .get(COLUMN_META, META_GENESIS_HASH_KEY)
+            .map(|slice| slice.as_ref().to_vec())
+    );
+    assert_eq!(Ok(()), store.check_genesis(&genesis_hash));
+    assert_eq!(
+        Err(StoreError::GenesisMismatch {
+            expected: other_hash.clone(),
+            actual: genesis_hash.clone(),
+        }),
+        store.check_genesis(&other_hash)
+    );
+
+    // databases initialized without the genesis hash record fall back to the index
+    let txn = store.begin_transaction();
+    txn.delete(COLUMN_META, META_GENESIS_HASH_KEY).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(Ok(()), store.check_genesis(&genesis_hash));
+    assert!(store.check_genesis(&other_hash).is_err());
+}
+
+#[test]
+fn zero_size_caches_are_bypassed() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let config = StoreConfig {
+        header_cache_size: 0,
+        cell_data_cache_size: 0,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    let genesis = consensus.genesis_block();
+
+    let cache = store.cache().unwrap();
+    assert!(!cache.header_cache_enabled());
+    assert!(!cache.cell_data_cache_enabled());
+
+    for _ in 0..2 {
+        assert_eq!(
+            Some(genesis.header()),
+            store.get_block_header(&genesis.hash())
+        );
+    }
+    assert_eq!(
+        vec![Some(genesis.header())],
+        store.get_block_headers(&[genesis.hash()])
+    );
+    assert!(store.block_exists(&genesis.hash()));
+
+    let cellbase = &genesis.transactions()[0];
+    let out_point = cellbase.output_pts()[0].clone();
+    let (data, data_hash) = store.get_cell_data(&out_point).unwrap();
+    assert_eq!(data, cellbase.outputs_data().get(0).unwrap().raw_data());
+    assert_eq!(Some(data_hash), store.get_cell_data_hash(&out_point));
+
+    assert!(cache.headers.lock().is_empty());
+    assert!(cache.cell_data.lock().is_empty());
+    assert!(cache.cell_data_hash.lock().is_empty());
+    assert_eq!(store.cache_stats(), CacheStats::default());
+}
+
+#[test]
+fn get_latest_built_filter_data_block_number() {
+    let (_tmp_dir, store, consensus) = new_store();
+    assert!(store.get_latest_built_filter_data_block_number().is_none());
+
+    let mut parent = consensus.genesis_block().header();
+    let mut parent_filter_hash = packed::Byte32::zero();
+    for number in 1..=3u64 {
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number(number.pack())
+            .build();
+        let filter: packed::Bytes = [number as u8][..].pack();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.insert_block_filter(&block.hash(), &filter, &parent_filter_hash)
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            Some(number),
+            store.get_latest_built_filter_data_block_number()
+        );
+        parent_filter_hash = store.get_block_filter_hash(&block.hash()).unwrap();
+        parent = block.header();
+    }
+}
+
+#[test]
+fn block_filter_survives_attach_and_detach() {
+    let (_tmp_dir, store, consensus) = new_store();
+    let genesis = consensus.genesis_block();
+
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .build();
+    let filter: packed::Bytes = [1u8, 2, 3][..].pack();
+    let txn = store.begin_transaction();
+    txn.insert_block(&block).unwrap();
+    txn.insert_block_filter(&block.hash(), &filter, &packed::Byte32::zero())
+        .unwrap();
+    txn.commit().unwrap();
+    assert_eq!(Some(filter.clone()), store.get_block_filter(&block.hash()));
+    let filter_hash = store.get_block_filter_hash(&block.hash());
+    assert!(filter_hash.is_some());
+
+    let txn = store.begin_transaction();
+    txn.attach_block(&block).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(Some(filter.clone()), store.get_block_filter(&block.hash()));
+
+    let txn = store.begin_transaction();
+    txn.detach_block(&block).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(Some(filter), store.get_block_filter(&block.hash()));
+    assert_eq!(filter_hash, store.get_block_filter_hash(&block.hash()));
+}
+
+#[test]
+fn column_sizes() {
+    let (_tmp_dir, store, _) = new_store();
+
+    let sizes = store.column_sizes().unwrap();
+    assert_eq!(sizes.len(), COLUMNS as usize);
+    let header_size = sizes
+        .iter()
+        .find(|(col, _)| *col == COLUMN_BLOCK_HEADER)
+        .map(|(_, size)| *size)
+        .unwrap();
+    assert!(header_size.estimated_keys >= 1);
+}
+
+#[test]
+fn compact_all() {
+    let (_tmp_dir, store, consensus) = new_store();
+    let genesis = consensus.genesis_block();
+
+    store
+        .compact_range(COLUMN_BLOCK_HEADER, Some(genesis.hash().as_slice()), None)
+        .unwrap();
+    store.compact_all().unwrap();
+    assert_eq!(Some(genesis.header()), store.get_tip_header());
+    assert_eq!(genesis, &store.get_block(&genesis.hash()).unwrap());
+}
+
+#[test]
+fn export_and_import_blocks() {
+    let (_source_dir, source, consensus) = new_store();
+    let parent = build_main_chain(&source, 100).pop().unwrap().header();
+
+    let mut exported = Vec::new();
+    assert_eq!(100, source.export_range(1..200, &mut exported).unwrap());
+
+    let (_target_dir, target, _) = new_store();
+    assert_eq!(
+        100,
+        target
+            .import_blocks(&consensus, exported.as_slice(), 7)
+            .unwrap()
+    );
+    assert_eq!(Some(parent.clone()), target.get_tip_header());
+    for number in 0..=100 {
+        assert_eq!(
+            source.get_block_by_number(number),
+            target.get_block_by_number(number)
+        );
+    }
+    let tip_ext = target.get_block_ext(&parent.hash()).unwrap();
+    let expected_difficulty = (0..=100).fold(U256::zero(), |total, number| {
+        total + target.get_header_by_number(number).unwrap().difficulty()
+    });
+    assert_eq!(tip_ext.total_difficulty, expected_difficulty);
+    assert!(target.get_current_epoch_ext().is_some());
+
+    // importing again no longer extends the tip
+    let err = target
+        .import_blocks(&consensus, exported.as_slice(), 7)
+        .unwrap_err();
+    assert!(err.to_string().contains("does not extend"));
+
+    // a gap is rejected as well
+    let mut gapped = Vec::new();
+    source.export_range(2..5, &mut gapped).unwrap();
+    let (_gapped_dir, gapped_store, _) = new_store();
+    assert!(gapped_store
+        .import_blocks(&consensus, gapped.as_slice(), 7)
+        .is_err());
+    assert_eq!(
+        Some(consensus.genesis_block().header()),
+        gapped_store.get_tip_header()
+    );
+}
+
+#[test]
+fn rollback_to() {
+    let (_source_dir, source, consensus) = new_store();
+    build_main_chain(&source, 250);
+    let mut exported = Vec::new();
+    source.export_range(1..251, &mut exported).unwrap();
+
+    let (_tmp_dir, store, _) = new_store();
+    store
+        .import_blocks(&consensus, exported.as_slice(), 50)
+        .unwrap();
+    let detached: Vec<_> = (21..=250)
+        .map(|number| store.get_block_hash(number).unwrap())
+        .collect();
+
+    store.rollback_to(20).unwrap();
+    let tip = source.get_header_by_number(20).unwrap();
+    assert_eq!(Some(tip.clone()), store.get_tip_header());
+    assert_eq!(
+        store.get_block_epoch(&tip.hash()),
+        store.get_current_epoch_ext()
+    );
+    assert_eq!(Some(tip.hash()), store.get_block_hash(20));
+    assert!(store.get_block_hash(21).is_none());
+    for hash in &detached {
+        assert!(!store.is_main_chain(hash));
+        assert!(store.get_block_header(hash).is_some());
+    }
+
+    // already at the target, or below it
+    store.rollback_to(20).unwrap();
+    store.rollback_to(100).unwrap();
+    assert_eq!(Some(tip), store.get_tip_header());
+}
+
+#[test]
+fn get_cells() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block();
+    let mut expected: Vec<_> = genesis
+        .transactions()
+        .iter()
+        .enumerate()
+        .flat_map(|(tx_index, tx)| {
+            tx.outputs_with_data_iter().zip(tx.output_pts()).map(
+                move |((output, data), out_point)| {
+                    Some((out_point, output, data.len() as u64, tx_index))
+                },
+            )
+        })
+        .collect();
+    assert!(expected.len() > 3);
+    expected.insert(2, None);
+    let mut out_points: Vec<_> = expected
+        .iter()
+        .flatten()
+        .map(|(out_point, ..)| out_point.clone())
+        .collect();
+    out_points.insert(2, packed::OutPoint::new(packed::Byte32::new([1; 32]), 0));
+
+    let cells = store.get_cells(&out_points);
+    assert_eq!(cells.len(), expected.len());
+    for (cell, expected) in cells.into_iter().zip(expected) {
+        match (cell, expected) {
+            (Some(cell), Some((out_point, output, data_size, tx_index))) => {
+                assert_eq!(cell.out_point, out_point);
+                assert_eq!(cell.cell_output, output);
+                assert_eq!(cell.data_bytes, data_size);
+                let info = cell.transaction_info.unwrap();
+                assert_eq!(info.block_hash, genesis.hash());
+                assert_eq!(info.block_number, 0);
+                assert_eq!(info.index, tx_index);
+            }
+            (None, None) => {}
+            (cell, expected) => panic!("unexpected cell {cell:?}, expected {expected:?}"),
+        }
+    }
+    assert!(store.get_cells(&[]).is_empty());
+}
+
+#[test]
+fn prime_cache_on_attach() {
+    for prime_cache_on_attach in [true, false] {
+        let tmp_dir = TempDir::new().unwrap();
+        let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+        let config = StoreConfig {
+            prime_cache_on_attach,
+            ..Default::default()
+        };
+        let store = ChainDB::new(db, config);
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+
+        let data = Bytes::from(vec![1, 2, 3]);
+        let tx = TransactionBuilder::default()
+            .output(packed::CellOutput::new_builder().build())
+            .output_data(data.pack())
+            .output(packed::CellOutput::new_builder().build())
+            .output_data(Bytes::new().pack())
+            .build();
+        let block = BlockBuilder::default()
+            .parent_hash(consensus.genesis_hash())
+            .number(1u64.pack())
+            .transaction(tx.clone())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        attach_block_cell(&txn, &block).unwrap();
+        txn.commit().unwrap();
+        store.reset_cache_stats();
+
+        let out_points = tx.output_pts();
+        assert_eq!(
+            Some((data.clone(), packed::CellOutput::calc_data_hash(&data))),
+            store.get_cell_data(&out_points[0])
+        );
+        assert_eq!(
+            Some((Bytes::new(), packed::Byte32::zero())),
+            store.get_cell_data(&out_points[1])
+        );
+        let stats = store.cache_stats();
+        if prime_cache_on_attach {
+            assert_eq!((2, 0), (stats.cell_data_hits, stats.cell_data_misses));
+            assert!(store
+                .cache()
+                .unwrap()
+                .cell_data_hash
+                .lock()
+                .contains(&out_points[0].to_cell_key()));
+        } else {
+            assert_eq!((0, 2), (stats.cell_data_hits, stats.cell_data_misses));
+        }
+    }
+}
+
+#[test]
+fn have_cell() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block();
+    let spent = genesis.transactions()[0].output_pts()[0].clone();
+    let spend = TransactionBuilder::default()
+        .input(packed::CellInput::new(spent.clone(), 0))
+        .output(packed::CellOutput::new_builder().build())
+        .output_data(Bytes::new().pack())
+        .build();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .transaction(TransactionBuilder::default().build())
+        .transaction(spend.clone())
+        .build();
+    let txn = store.begin_transaction();
+    txn.insert_block(&block).unwrap();
+    txn.attach_block(&block).unwrap();
+    attach_block_cell(&txn, &block).unwrap();
+    txn.commit().unwrap();
+
+    let mut out_points: Vec<_> = genesis
+        .transactions()
+        .iter()
+        .chain(block.transactions().iter())
+        .flat_map(|tx| tx.output_pts())
+        .collect();
+    out_points.push(packed::OutPoint::new(spend.hash(), 1));
+    out_points.push(packed::OutPoint::new(packed::Byte32::new([1; 32]), 0));
+    for out_point in &out_points {
+        assert_eq!(
+            store.have_cell(out_point),
+            store.get_cell(out_point).is_some()
+        );
+    }
+    assert!(!store.have_cell(&spent));
+    assert!(store.have_cell(&spend.output_pts()[0]));
+}
+
+#[test]
+fn get_uncles_by_number() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block();
+    let uncles: Vec<_> = (1..=2u64)
+        .map(|i| {
+            BlockBuilder::default()
+                .parent_hash(genesis.hash())
+                .number(1u64.pack())
+                .timestamp(i.pack())
+                .build()
+                .as_uncle()
+        })
+        .collect();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .uncles(uncles.clone())
+        .build();
+    let ext = BlockExt {
+        total_uncles_count: 2,
+        ..main_chain_ext(&block)
+    };
+    insert_main_chain_block(&store, &block, &ext);
+
+    let hashes = |uncles: Vec<UncleBlockView>| -> Vec<_> {
+        uncles.iter().map(|uncle| uncle.hash()).collect()
+    };
+    assert!(store.get_uncles_by_number(0).unwrap().is_empty());
+    assert_eq!(
+        Some(hashes(uncles)),
+        store.get_uncles_by_number(1).map(hashes)
+    );
+    assert!(store.get_uncles_by_number(2).is_none());
+}
+
+#[test]
+fn get_block_skeleton() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block();
+    let uncle = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .timestamp(1u64.pack())
+        .build()
+        .as_uncle();
+    let transactions: Vec<_> = (0..3u64)
+        .map(|i| {
+            TransactionBuilder::default()
+                .witness(i.pack().as_bytes().pack())
+                .build()
+        })
+        .collect();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .uncle(uncle)
+        .transactions(transactions)
+        .proposal(packed::ProposalShortId::new([1u8; 10]))
+        .build();
+    let ext = BlockExt {
+        total_uncles_count: 1,
+        ..main_chain_ext(&block)
+    };
+    insert_main_chain_block(&store, &block, &ext);
+
+    let full = store.get_block(&block.hash()).unwrap();
+    let skeleton = store.get_block_skeleton(&block.hash()).unwrap();
+    assert_eq!(full.header(), skeleton.header);
+    assert_eq!(full.tx_hashes(), skeleton.tx_hashes.as_slice());
+    assert_eq!(
+        full.uncles()
+            .into_iter()
+            .map(|uncle| uncle.hash())
+            .collect::<Vec<_>>(),
+        skeleton.uncle_hashes
+    );
+    assert_eq!(
+        full.data().proposals().as_slice(),
+        skeleton.proposals.as_slice()
+    );
+    assert_eq!(3, skeleton.tx_hashes.len());
+
+    assert!(store
+        .get_block_skeleton(&packed::Byte32::new([1u8; 32]))
+        .is_none());
+}
+
+#[test]
+fn write_batch_insert_blocks() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut parent = consensus.genesis_block().header();
+    let blocks: Vec<_> = (0..50u64)
+        .map(|i| {
+            let block = BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number((parent.number() + 1).pack())
+                .transactions((0..i % 3).map(|j| {
+                    TransactionBuilder::default()
+                        .witness((i * 3 + j).pack().as_bytes().pack())
+                        .build()
+                }))
+                .build();
+            parent = block.header();
+            block
+        })
+        .collect();
+
+    let mut batch = store.new_write_batch();
+    batch.insert_blocks(&blocks).unwrap();
+    store.write(&batch).unwrap();
+
+    for block in &blocks {
+        assert_eq!(block, &store.get_block(&block.hash()).unwrap());
+        assert_eq!(block.tx_hashes(), store.get_block_txs_hashes(&block.hash()));
+        let number_hash = packed::NumberHash::new_builder()
+            .number(block.number().pack())
+            .block_hash(block.hash())
+            .build();
+        let txs_len: packed::Uint32 = (block.transactions().len() as u32).pack();
+        assert_eq!(
+            Some(txs_len.as_slice().to_vec()),
+            store
+                .get(COLUMN_NUMBER_HASH, number_hash.as_slice())
+                .map(|slice| slice.as_ref().to_vec())
+        );
+    }
+}
+
+#[test]
+fn write_batch_commit_and_clear() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+
+    let mut batch = store.new_write_batch();
+    for chunk in 0..3u8 {
+        for i in 0..10u8 {
+            batch.put(COLUMN_META, &[chunk, i], &[i]).unwrap();
+        }
+        // overwrite a key written by the previous chunk
+        if chunk > 0 {
+            batch.put(COLUMN_META, &[chunk - 1, 0], &[u8::MAX]).unwrap();
+        }
+        assert!(store.get(COLUMN_META, &[chunk, 0]).is_none());
+        batch.commit_and_clear().unwrap();
+        assert!(batch.is_empty());
+        assert_eq!(
+            Some(vec![9]),
+            store.get(COLUMN_META, &[chunk, 9]).map(|v| v.to_vec())
+        );
+    }
+
+    assert_eq!(
+        Some(vec![u8::MAX]),
+        store.get(COLUMN_META, &[1, 0]).map(|v| v.to_vec())
+    );
+    assert_eq!(
+        Some(vec![0]),
+        store.get(COLUMN_META, &[2, 0]).map(|v| v.to_vec())
+    );
+    // committing an empty batch is a no-op
+    batch.commit_and_clear().unwrap();
+}
+
+#[test]
+fn write_batch_max_batch_bytes() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let consensus = ConsensusBuilder::default().build();
+
+    // blocks without transactions all take the same number of bytes in a batch
+    let mut parent = consensus.genesis_block().header();
+    let blocks: Vec<_> = (0..20)
+        .map(|_| {
+            let block = BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number((parent.number() + 1).pack())
+                .build();
+            parent = block.header();
+            block
+        })
+        .collect();
+
+    // the size of a batch holding 4 blocks, so the 5th one crosses the limit
+    let limit = {
+        let store = ChainDB::new(db.clone(), Default::default());
+        let mut batch = store.new_write_batch();
+        batch.insert_blocks(&blocks[..4]).unwrap();
+        batch.size_in_bytes()
+    };
+    let config = StoreConfig {
+        max_batch_bytes: limit,
+        ..Default::default()
+    };
+    let store = ChainDB::new(db, config);
+    store.init(&consensus).unwrap();
+
+    let mut batch = store.new_write_batch();
+    for (i, block) in blocks[..7].iter().enumerate() {
+        batch.insert_block(block).unwrap();
+        assert_eq!(batch.auto_commits(), (i + 1) / 5);
+        assert_eq!(batch.is_empty(), i == 4);
+    }
+    // the committed blocks are visible before the batch is written
+    assert!(store.get_block(&blocks[4].hash()).is_some());
+    assert!(store.get_block(&blocks[5].hash()).is_none());
+    store.write(&batch).unwrap();
+
+    let mut batch = store.new_write_batch();
+    batch.insert_blocks(&blocks[7..]).unwrap();
+    assert_eq!(batch.auto_commits(), 2);
+    assert!(store.get_block(&blocks[16].hash()).is_some());
+    assert!(store.get_block(&blocks[17].hash()).is_none());
+    store.write(&batch).unwrap();
+    for block in &blocks {
+        assert_eq!(block, &store.get_block(&block.hash()).unwrap());
+    }
+}
+
+#[test]
+fn detach_blocks_in_bulk() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let cellbase = |number: u64| {
+        TransactionBuilder::default()
+            .input(packed::CellInput::new_cellbase_input(number))
+            .output(
+                packed::CellOutput::new_builder()
+                    .capacity(Capacity::bytes(100).unwrap().pack())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    };
+    let mut blocks: Vec<BlockView> = Vec::new();
+    for number in 1..=4u64 {
+        let parent = blocks
+            .last()
+            .map(|block| block.header())
+            .unwrap_or_else(|| consensus.genesis_block().header());
+        let mut builder = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number(number.pack())
+            .transaction(cellbase(number));
+        // block 3 spends the cellbase output of block 1
+        if number == 3 {
+            let spent = blocks[0].transactions()[0].output_pts()[0].clone();
+            builder = builder.transaction(
+                TransactionBuilder::default()
+                    .input(packed::CellInput::new(spent, 0))
+                    .output(packed::CellOutput::new_builder().build())
+                    .output_data(Bytes::new().pack())
+                    .build(),
+            );
+        }
+        let block = builder.build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        attach_block_cell(&txn, &block).unwrap();
+        txn.commit().unwrap();
+        blocks.push(block);
+    }
+    let block1_cell = blocks[0].transactions()[0].output_pts()[0].clone();
+    assert!(store.get_cell(&block1_cell).is_none());
+
+    let txn = store.begin_transaction();
+    detach_blocks(&txn, &blocks[1..]).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(Some(blocks[0].hash()), store.get_block_hash(1));
+    assert!(store.get_cell(&block1_cell).is_some());
+    for block in &blocks[1..] {
+        assert!(store.get_block_hash(block.number()).is_none());
+        assert!(store.get_block_number(&block.hash()).is_none());
+        for tx in block.transactions() {
+            assert!(store.get_transaction_info(&tx.hash()).is_none());
+            for out_point in tx.output_pts() {
+                assert!(store.get_cell(&out_point).is_none());
+            }
+        }
+    }
+}
+
+#[test]
+fn get_block_txs_merkle_root() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let block = consensus.genesis_block();
+    let hash = block.hash();
+    store.init(&consensus).unwrap();
+
+    assert_eq!(
+        block.transactions_root(),
+        store.get_block_txs_merkle_root(&hash).unwrap()
+    );
+    assert!(store
+        .get_block_txs_merkle_root(&packed::Byte32::zero())
+        .is_none());
+}
+
+#[test]
+fn audit_block() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    let block = consensus.genesis_block();
+    let hash = block.hash();
+    store.init(&consensus).unwrap();
+    assert_eq!(Ok(()), store.audit_block(&hash));
+    assert_eq!(
+        Err(IntegrityIssue::MissingBlock(packed::Byte32::zero())),
+        store.audit_block(&packed::Byte32::zero())
+    );
+
+    // corrupt the cellbase data but keep the stored hashes stale
+    let cellbase = block.transactions()[0].clone();
+    let corrupted = cellbase
+        .data()
+        .as_builder()
+        .raw(
+            cellbase
+                .data()
+                .raw()
+                .as_builder()
+                .version(1u32.pack())
+                .build(),
+        )
+        .build();
+    let tx_view = packed::TransactionView::new_builder()
+        .data(corrupted.clone())
+        .hash(cellbase.hash())
+        .witness_hash(cellbase.witness_hash())
+        .build();
+    let key = packed::TransactionKey::new_builder()
+        .block_hash(hash.clone())
+        .index(0u32.pack())
+        .build();
+    let txn = store.begin_transaction();
+    txn.insert_raw(COLUMN_BLOCK_BODY, key.as_slice(), tx_view.as_slice())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let actual = BlockBuilder::default()
+        .transactions(
+            std::iter::once(corrupted.into_view())
+                .chain(block.transactions().into_iter().skip(1))
+                .collect::<Vec<_>>(),
+        )
+        .build()
+        .transactions_root();
+    assert_eq!(
+        Err(IntegrityIssue::TransactionsRootMismatch {
+            block_hash: hash,
+            expected: block.transactions_root(),
+            actual,
+        }),
+        store.audit_block(&block.hash())
+    );
+}
+
+#[test]
+fn get_transaction_output_lock_hashes() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let locks: Vec<packed::Script> = (0..3u8)
+        .map(|i| {
+            packed::Script::new_builder()
+                .code_hash(packed::Byte32::new([i; 32]))
+                .args(Bytes::from(vec![i]).pack())
+                .build()
+        })
+        .collect();
+    let tx = TransactionBuilder::default()
+        .outputs(
+            locks
+                .iter()
+                .map(|lock| packed::CellOutput::new_builder().lock(lock.clone()).build()),
+        )
+        .outputs_data(locks.iter().map(|_| Bytes::new().pack()))
+        .build();
+    let genesis = consensus.genesis_block().header();
+    let block = BlockBuilder::default()
+        .parent_hash(genesis.hash())
+        .number(1u64.pack())
+        .transaction(tx.clone())
+        .build();
+    let ext = main_chain_ext(&block);
+    insert_main_chain_block(&store, &block, &ext);
+
+    let expected: Vec<H256> = locks
+        .iter()
+        .map(|lock| lock.calc_script_hash().unpack())
+        .collect();
+    assert_eq!(
+        Some(expected),
+        store.get_transaction_output_lock_hashes(&tx.hash())
+    );
+    assert!(store
+        .get_transaction_output_lock_hashes(&packed::Byte32::zero())
+        .is_none());
+}
+
+#[test]
+fn get_epoch_aggregate() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let blocks_fees_and_uncles = [(vec![10, 20], 1), (vec![], 0), (vec![5], 2)];
+    let mut parent = consensus.genesis_block().header();
+    let mut total_uncles_count = 0;
+    for (fees, uncles_count) in blocks_fees_and_uncles.iter() {
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((parent.number() + 1).pack())
+            .build();
+        total_uncles_count += uncles_count;
+        let ext = BlockExt {
+            received_at: block.timestamp(),
+            total_difficulty: block.difficulty(),
+            total_uncles_count,
+            verified: Some(true),
+            txs_fees: fees.iter().map(|fee| Capacity::shannons(*fee)).collect(),
+            cycles: None,
+            txs_sizes: None,
+        };
+        insert_main_chain_block(&store, &block, &ext);
+        parent = block.header();
+    }
+
+    assert_eq!(
+        Some(EpochAggregate {
+            blocks_count: 4,
+            txs_fees: Capacity::shannons(35),
+            uncles_count: 3,
+        }),
+        store.get_epoch_aggregate(0)
+    );
+    assert!(store.get_epoch_aggregate(1).is_none());
+}
+
+#[test]
+fn iter_epoch_exts() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut expected = vec![consensus.genesis_epoch_ext().to_owned()];
+    for number in 1..3u64 {
+        let epoch = EpochExt::new_builder()
+            .number(number)
+            .start_number(number * 1000)
+            .length(1000)
+            .last_block_hash_in_previous_epoch(packed::Byte32::new([number as u8; 32]))
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_epoch_ext(&epoch.last_block_hash_in_previous_epoch(), &epoch)
+            .unwrap();
+        txn.commit().unwrap();
+        expected.push(epoch);
+    }
+
+    assert_eq!(expected, store.iter_epoch_exts(0..10).collect::<Vec<_>>());
+    assert_eq!(
+        expected[1..2].to_vec(),
+        store.iter_epoch_exts(1..2).collect::<Vec<_>>()
+    );
+    assert_eq!(0, store.iter_epoch_exts(3..10).count());
+}
+
+#[test]
+fn get_block_epoch() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis_hash = consensus.genesis_hash();
+    assert_eq!(
+        Some(consensus.genesis_epoch_ext().to_owned()),
+        store.get_block_epoch(&genesis_hash)
+    );
+    assert_eq!(
+        store
+            .get_block_epoch_index(&genesis_hash)
+            .and_then(|index| store.get_epoch_ext(&index)),
+        store.get_block_epoch(&genesis_hash)
+    );
+    assert!(store.get_block_epoch(&packed::Byte32::zero()).is_none());
+}
+
+#[test]
+fn epoch_start_numbers() {
+    let (_tmp_dir, store, consensus) = new_store();
+    assert_eq!(vec![0], store.epoch_start_numbers());
+
+    // epochs of 3 blocks starting at block 1
+    let mut parent = consensus.genesis_block().header();
+    let mut expected = vec![0];
+    for _ in 0..7 {
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((parent.number() + 1).pack())
+            .build();
+        let ext = main_chain_ext(&block);
+        insert_main_chain_block(&store, &block, &ext);
+        if block.number() % 3 == 1 {
+            let epoch = EpochExt::new_builder()
+                .number(expected.len() as u64)
+                .start_number(block.number())
+                .length(3)
+                .last_block_hash_in_previous_epoch(parent.hash())
+                .build();
+            let txn = store.begin_transaction();
+            txn.insert_epoch_ext(&parent.hash(), &epoch).unwrap();
+            txn.insert_current_epoch_ext(&epoch).unwrap();
+            txn.commit().unwrap();
+            expected.push(block.number());
+        }
+        parent = block.header();
+    }
+
+    assert_eq!(vec![0, 1, 4, 7], expected);
+    assert_eq!(expected, store.epoch_start_numbers());
+}
+
+#[test]
+fn verify_cell_consistency() {
+    let (_tmp_dir, store, consensus) = new_store();
+    assert!(store.verify_cell_consistency().is_empty());
+
+    let cellbase = consensus.genesis_block().transactions()[0].clone();
+    let out_point = packed::OutPoint::new(cellbase.hash(), 0);
+    let txn = store.begin_transaction();
+    txn.delete(COLUMN_CELL, &out_point.to_cell_key()).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        vec![IntegrityIssue::OrphanedCellData(out_point)],
+        store.verify_cell_consistency()
+    );
+}
+
+#[test]
+fn verify_index() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut blocks = vec![consensus.genesis_block().clone()];
+    for number in 1..=5u64 {
+        let block = BlockBuilder::default()
+            .parent_hash(blocks.last().unwrap().hash())
+            .number(number.pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.insert_tip_header(&block.header()).unwrap();
+        txn.commit().unwrap();
+        blocks.push(block);
+    }
+    assert!(store.verify_index(0..6).is_empty());
+    // clamped at the tip
+    assert!(store.verify_index(0..100).is_empty());
+    assert!(store.verify_index(6..100).is_empty());
+
+    let fork = BlockBuilder::default()
+        .parent_hash(blocks[1].hash())
+        .number(3u64.pack())
+        .timestamp(1u64.pack())
+        .build();
+    let txn = store.begin_transaction();
+    txn.insert_raw(
+        COLUMN_INDEX,
+        blocks[1].hash().as_slice(),
+        7u64.pack().as_slice(),
+    )
+    .unwrap();
+    txn.delete(COLUMN_INDEX, blocks[2].hash().as_slice())
+        .unwrap();
+    txn.insert_block(&fork).unwrap();
+    txn.insert_raw(COLUMN_INDEX, 3u64.pack().as_slice(), fork.hash().as_slice())
+        .unwrap();
+    txn.insert_raw(
+        COLUMN_INDEX,
+        5u64.pack().as_slice(),
+        blocks[4].hash().as_slice(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        store.verify_index(0..7),
+        vec![
+            IntegrityIssue::ReverseIndexMismatch {
+                block_hash: blocks[1].hash(),
+                expected: 1,
+                actual: 7,
+            },
+            IntegrityIssue::MissingReverseIndex {
+                number: 2,
+                block_hash: blocks[2].hash(),
+            },
+            IntegrityIssue::MissingReverseIndex {
+                number: 3,
+                block_hash: fork.hash(),
+            },
+            IntegrityIssue::ParentMismatch {
+                block_hash: fork.hash(),
+                expected: blocks[2].hash(),
+                actual: blocks[1].hash(),
+            },
+            IntegrityIssue::ParentMismatch {
+                block_hash: blocks[4].hash(),
+                expected: fork.hash(),
+                actual: blocks[3].hash(),
+            },
+            IntegrityIssue::ReverseIndexMismatch {
+                block_hash: blocks[4].hash(),
+                expected: 5,
+                actual: 4,
+            },
+            IntegrityIssue::HeaderNumberMismatch {
+                block_hash: blocks[4].hash(),
+                expected: 5,
+                actual: 4,
+            },
+            IntegrityIssue::ParentMismatch {
+                block_hash: blocks[4].hash(),
+                expected: blocks[4].hash(),
+                actual: blocks[3].hash(),
+            },
+        ]
+    );
+    // the parent of the first block is checked against the index too
+    assert_eq!(
+        store.verify_index(4..5),
+        vec![IntegrityIssue::ParentMismatch {
+            block_hash: blocks[4].hash(),
+            expected: fork.hash(),
+            actual: blocks[3].hash(),
+        }]
+    );
+
+    let txn = store.begin_transaction();
+    txn.delete(COLUMN_INDEX, 3u64.pack().as_slice()).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        store.verify_index(3..4),
+        vec![IntegrityIssue::MissingIndex(3)]
+    );
+}
+
+#[test]
+fn traverse_cell_set_from() {
+    let (_tmp_dir, store, _) = new_store();
+
+    let mut all = Vec::new();
+    store.traverse_cell_set_from(None, |key, cell| {
+        assert_eq!(key, cell.out_point.to_cell_key().as_slice());
+        all.push(cell.out_point);
+        ControlFlow::Continue(())
+    });
+    let expected: Vec<_> = store
+        .get_iter(COLUMN_CELL, IteratorMode::Start)
+        .map(|(key, _)| key.to_vec())
+        .collect();
+    assert!(expected.len() > 3);
+    assert_eq!(
+        all.iter()
+            .map(|out_point| out_point.to_cell_key())
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    // stop early, then resume from the next key as a cursor
+    let mut page = Vec::new();
+    store.traverse_cell_set_from(None, |key, _| {
+        page.push(key.to_vec());
+        if page.len() == 2 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(page, expected[..2]);
+
+    let mut rest = Vec::new();
+    store.traverse_cell_set_from(Some(expected[2].as_slice()), |key, _| {
+        rest.push(key.to_vec());
+        ControlFlow::Continue(())
+    });
+    assert_eq!(rest, expected[2..]);
+}
+
+#[test]
+fn traverse_cell_meta() {
+    let (_tmp_dir, store, _) = new_store();
+
+    let mut decoded = Vec::new();
+    store.traverse_cell_meta(|tx_hash, index, cell| {
+        assert_eq!(
+            cell.out_point,
+            packed::OutPoint::new(tx_hash.clone(), index)
+        );
+        decoded.push((tx_hash, index));
+        if decoded.len() == 3 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    let expected: Vec<_> = store
+        .get_iter(COLUMN_CELL, IteratorMode::Start)
+        .take(3)
+        .map(|(key, _)| {
+            let out_point = packed::OutPoint::from_cell_key(&key).unwrap();
+            let index: u32 = out_point.index().unpack();
+            (out_point.tx_hash(), index)
+        })
+        .collect();
+    assert_eq!(decoded.len(), 3);
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn get_header_chain() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut headers = vec![consensus.genesis_block().header()];
+    for _ in 0..4 {
+        let parent = headers.last().unwrap();
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((parent.number() + 1).pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        headers.push(block.header());
+    }
+
+    assert_eq!(
+        Some(headers[1..4].to_vec()),
+        store.get_header_chain(&headers[1].hash(), &headers[3].hash(), 3)
+    );
+    assert_eq!(
+        Some(vec![headers[2].clone()]),
+        store.get_header_chain(&headers[2].hash(), &headers[2].hash(), 1)
+    );
+    // capped by max
+    assert!(store
+        .get_header_chain(&headers[0].hash(), &headers[4].hash(), 4)
+        .is_none());
+    assert_eq!(
+        Some(headers.clone()),
+        store.get_header_chain(&headers[0].hash(), &headers[4].hash(), 5)
+    );
+    // not connected in ascending order
+    assert!(store
+        .get_header_chain(&headers[3].hash(), &headers[1].hash(), 5)
+        .is_none());
+
+    // a fork block at the same height is not connected to the chain
+    let fork = BlockBuilder::default()
+        .parent_hash(headers[1].hash())
+        .number(2.pack())
+        .timestamp(1.pack())
+        .build();
+    let txn = store.begin_transaction();
+    txn.insert_block(&fork).unwrap();
+    txn.commit().unwrap();
+    assert!(store
+        .get_header_chain(&headers[2].hash(), &fork.hash(), 5)
+        .is_none());
+}
+
+#[test]
+fn get_ancestor() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let mut headers = vec![consensus.genesis_block().header()];
+    headers.extend(
+        build_main_chain(&store, 4)
+            .iter()
+            .map(|block| block.header()),
+    );
+
+    // main chain fast path
+    let tip_hash = headers[4].hash();
+    for (number, header) in headers.iter().enumerate() {
+        assert_eq!(
+            Some(header.clone()),
+            store.get_ancestor(&tip_hash, number as u64)
+        );
+    }
+    assert!(store.get_ancestor(&tip_hash, 5).is_none());
+    assert_eq!(
+        Some(headers[0].clone()),
+        store.get_ancestor(&headers[0].hash(), 0)
+    );
+
+    // a side chain forked from block 1
+    let mut fork_headers = vec![headers[1].clone()];
+    for _ in 0..3 {
+        let parent = fork_headers.last().unwrap();
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((parent.number() + 1).pack())
+            .timestamp(1u64.pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        fork_headers.push(block.header());
+    }
+    let fork_tip_hash = fork_headers[3].hash();
+    assert_eq!(
+        Some(fork_headers[2].clone()),
+        store.get_ancestor(&fork_tip_hash, 3)
+    );
+    assert_eq!(
+        Some(headers[1].clone()),
+        store.get_ancestor(&fork_tip_hash, 1)
+    );
+    assert_eq!(
+        Some(headers[0].clone()),
+        store.get_ancestor(&fork_tip_hash, 0)
+    );
+    assert!(store.get_ancestor(&fork_tip_hash, 5).is_none());
+    assert!(store.get_ancestor(&packed::Byte32::zero(), 0).is_none());
+}
+
+#[test]
+fn fork_point() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    // a chain of 10 blocks and a side chain of 6 blocks forked from block 4
+    let extend = |parent: &HeaderView, count: usize, timestamp: u64| {
+        let mut headers = vec![parent.clone()];
+        for _ in 0..count {
+            let parent = headers.last().unwrap();
+            let block = BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number((parent.number() + 1).pack())
+                .timestamp(timestamp.pack())
+                .build();
+            let txn = store.begin_transaction();
+            txn.insert_block(&block).unwrap();
+            txn.commit().unwrap();
+            headers.push(block.header());
+        }
+        headers
+    };
+    let headers = extend(&consensus.genesis_block().header(), 10, 0);
+    let fork_headers = extend(&headers[4], 6, 1);
+
+    let fork_point = |a: &HeaderView, b: &HeaderView| store.fork_point(&a.hash(), &b.hash());
+    assert_eq!(
+        Some(headers[4].hash()),
+        fork_point(&headers[10], &fork_headers[6])
+    );
+    assert_eq!(
+        Some(headers[4].hash()),
+        fork_point(&fork_headers[6], &headers[10])
+    );
+    assert_eq!(
+        Some(headers[4].hash()),
+        fork_point(&headers[5], &fork_headers[1])
+    );
+    // one is an ancestor of the other
+    assert_eq!(
+        Some(headers[3].hash()),
+        fork_point(&headers[3], &fork_headers[6])
+    );
+    assert_eq!(
+        Some(headers[7].hash()),
+        fork_point(&headers[10], &headers[7])
+    );
+    assert_eq!(
+        Some(headers[10].hash()),
+        fork_point(&headers[10], &headers[10])
+    );
+
+    // unknown blocks
+    let unknown = packed::Byte32::zero();
+    assert!(store.fork_point(&unknown, &headers[10].hash()).is_none());
+    assert!(store.fork_point(&headers[10].hash(), &unknown).is_none());
+}
+
+#[test]
+fn proposals_in_window() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    // block n proposes ids n and 10 + n, block 2 repeats the proposal of block 1
+    let id = |n: u8| packed::ProposalShortId::new([n; 10]);
+    let mut parent = consensus.genesis_block().header();
+    for number in 1..=4u8 {
+        let mut builder = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((number as u64).pack())
+            .proposal(id(number))
+            .proposal(id(10 + number));
+        if number == 2 {
+            builder = builder.proposal(id(1));
+        }
+        let block = builder.build();
+        let ext = store.get_block_ext(&parent.hash()).unwrap();
+        insert_main_chain_block(&store, &block, &ext);
+        parent = block.header();
+    }
+    let tip_hash = parent.hash();
+    let ids = |numbers: &[u8]| -> HashSet<packed::ProposalShortId> {
+        numbers.iter().map(|&n| id(n)).collect()
+    };
+
+    assert_eq!(
+        ids(&[2, 12, 1, 3, 13]),
+        store.proposals_in_window(&tip_hash, 2..4)
+    );
+    // clamped at the tip and at genesis, which proposes nothing
+    assert_eq!(
+        ids(&[1, 11, 2, 12, 3, 13, 4, 14]),
+        store.proposals_in_window(&tip_hash, 0..100)
+    );
+    // the window ends at the given tip, not at the chain tip
+    let block_2 = store.get_block_hash(2).unwrap();
+    assert_eq!(
+        ids(&[1, 11, 2, 12]),
+        store.proposals_in_window(&block_2, 1..4)
+    );
+    assert!(store.proposals_in_window(&tip_hash, 5..10).is_empty());
+    assert!(store.proposals_in_window(&tip_hash, 3..3).is_empty());
+    assert!(store
+        .proposals_in_window(&packed::Byte32::zero(), 0..10)
+        .is_empty());
+}
+
+#[test]
+fn get_accumulated_capacity() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let accumulated_capacity = 12_345_678u64;
+    let mut dao = [0u8; 32];
+    dao[0..8].copy_from_slice(&accumulated_capacity.to_le_bytes());
+    let genesis = ConsensusBuilder::default()
+        .build()
+        .genesis_block()
+        .as_advanced_builder()
+        .dao(packed::Byte32::new(dao))
+        .build();
+    let consensus = ConsensusBuilder::default().genesis_block(genesis).build();
+    store.init(&consensus).unwrap();
+
+    assert_eq!(
+        Some(accumulated_capacity),
+        store.get_accumulated_capacity(&consensus.genesis_hash())
+    );
+    assert!(store
+        .get_accumulated_capacity(&packed::Byte32::zero())
+        .is_none());
+}
+
+#[test]
+fn get_block_median_time() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let genesis = consensus.genesis_block().header();
+    let mut headers = vec![genesis.clone()];
+    for timestamp in [40u64, 10, 30, 20] {
+        let parent = headers.last().unwrap();
+        let block = BlockBuilder::default()
+            .parent_hash(parent.hash())
+            .number((parent.number() + 1).pack())
+            .timestamp(timestamp.pack())
+            .build();
+        let ext = main_chain_ext(&block);
+        insert_main_chain_block(&store, &block, &ext);
+        headers.push(block.header());
+    }
+    let tip = headers[4].hash();
+
+    // odd sample count, median of [20, 30, 10]
+    assert_eq!(Some(20), store.get_block_median_time(&tip, 3));
+    // even sample count, the greater middle of [20, 30, 10, 40]
+    assert_eq!(Some(30), store.get_block_median_time(&tip, 4));
+    assert_eq!(Some(10), store.get_block_median_time(&headers[2].hash(), 1));
+    assert_eq!(Some(10), store.get_block_median_time(&headers[2].hash(), 0));
+
+    // the chain is shorter than the count, all the 5 blocks are sampled
+    let mut timestamps = vec![genesis.timestamp(), 40, 10, 30, 20];
+    timestamps.sort_unstable();
+    assert_eq!(Some(timestamps[2]), store.get_block_median_time(&tip, 11));
+    assert_eq!(
+        Some(genesis.timestamp()),
+        store.get_block_median_time(&genesis.hash(), 11)
+    );
+
+    assert_eq!(
+        None,
+        store.get_block_median_time(&packed::Byte32::new([1u8; 32]), 11)
+    );
+}
+
+#[test]
+fn accumulated_capacity_between() {
+    let (_tmp_dir, store, consensus) = new_store();
+
+    let with_capacity = |builder: BlockBuilder, capacity: u64| {
+        let mut dao = [0u8; 32];
+        dao[0..8].copy_from_slice(&capacity.to_le_bytes());
+        builder.dao(packed::Byte32::new(dao)).build()
+    };
+    let genesis = consensus.genesis_block().header();
+    let genesis_capacity = store.get_accumulated_capacity(&genesis.hash()).unwrap();
+    let mut headers = vec![genesis];
+    for i in 1..=3u64 {
+        let parent = headers.last().unwrap();
+        let block = with_capacity(
+            BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number((parent.number() + 1).pack()),
+            genesis_capacity + i * 1_000,
+        );
+        let ext = main_chain_ext(&block);
+        insert_main_chain_block(&store, &block, &ext);
+        headers.push(block.header());
+    }
+
+    assert_eq!(
+        Some(Capacity::shannons(3_000)),
+        store
+            .accumulated_capacity_between(&headers[0].hash(), &headers[3].hash())
+            .unwrap()
+    );
+    assert_eq!(
+        Some(Capacity::shannons(1_000)),
+        store
+            .accumulated_capacity_between(&headers[2].hash(), &headers[3].hash())
+            .unwrap()
+    );
+    assert_eq!(
+        Some(Capacity::zero()),
+        store
+            .accumulated_capacity_between(&headers[1].hash(), &headers[1].hash())
+            .unwrap()
+    );
+    // `from` is not an ancestor of `to`
+    assert!(store
+        .accumulated_capacity_between(&headers[3].hash(), &headers[1].hash())
+        .unwrap()
+        .is_none());
+    // missing header
+    assert!(store
+        .accumulated_capacity_between(&packed::Byte32::zero(), &headers[3].hash())
+        .unwrap()
+        .is_none());
+
+    // a descendant with a smaller accumulated capacity is an error
+    let block = with_capacity(
+        BlockBuilder::default()
+            .parent_hash(headers[3].hash())
+            .number(4u64.pack()),
+        genesis_capacity,
+    );
+    let txn = store.begin_transaction();
+    txn.insert_block(&block).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(
+        Err(StoreError::CapacityUnderflow {
+            from: headers[3].hash(),
+            to: block.hash(),
+            from_capacity: genesis_capacity + 3_000,
+            to_capacity: genesis_capacity,
+        }),
+        store.accumulated_capacity_between(&headers[3].hash(), &block.hash())
+    );
+}
+
 #[test]
 fn freeze_blockv0() {
     let tmp_dir = TempDir::new().unwrap();
@@ -1,4 +1,5 @@
 use crate::cache::StoreCache;
+use crate::error::StoreError;
 use crate::store::ChainStore;
 use ckb_chain_spec::versionbits::VersionbitsIndexer;
 use ckb_db::{
@@ -43,10 +44,10 @@ impl ChainStore for StoreTransaction {
         self.freezer.as_ref()
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice<'_>> {
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice<'_>>, StoreError> {
         self.inner
             .get_pinned(col, key)
-            .expect("db operation should be ok")
+            .map_err(|err| StoreError::io(col, err))
     }
 
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIter {
@@ -116,10 +117,10 @@ impl<'a> ChainStore for StoreTransactionSnapshot<'a> {
         self.freezer.as_ref()
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError> {
         self.inner
             .get_pinned(col, key)
-            .expect("db operation should be ok")
+            .map_err(|err| StoreError::io(col, err))
     }
 
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIter {
@@ -172,43 +173,7 @@ impl StoreTransaction {
 
     /// TODO(doc): @quake
     pub fn insert_block(&self, block: &BlockView) -> Result<(), Error> {
-        let hash = block.hash();
-        let header = block.header().pack();
-        let uncles = block.uncles().pack();
-        let proposals = block.data().proposals();
-        let txs_len: packed::Uint32 = (block.transactions().len() as u32).pack();
-        self.insert_raw(COLUMN_BLOCK_HEADER, hash.as_slice(), header.as_slice())?;
-        self.insert_raw(COLUMN_BLOCK_UNCLE, hash.as_slice(), uncles.as_slice())?;
-        if let Some(extension) = block.extension() {
-            self.insert_raw(
-                COLUMN_BLOCK_EXTENSION,
-                hash.as_slice(),
-                extension.as_slice(),
-            )?;
-        }
-        self.insert_raw(
-            COLUMN_NUMBER_HASH,
-            packed::NumberHash::new_builder()
-                .number(block.number().pack())
-                .block_hash(hash.clone())
-                .build()
-                .as_slice(),
-            txs_len.as_slice(),
-        )?;
-        self.insert_raw(
-            COLUMN_BLOCK_PROPOSAL_IDS,
-            hash.as_slice(),
-            proposals.as_slice(),
-        )?;
-        for (index, tx) in block.transactions().into_iter().enumerate() {
-            let key = packed::TransactionKey::new_builder()
-                .block_hash(hash.clone())
-                .index(index.pack())
-                .build();
-            let tx_data = tx.pack();
-            self.insert_raw(COLUMN_BLOCK_BODY, key.as_slice(), tx_data.as_slice())?;
-        }
-        Ok(())
+        put_block(block, |col, key, value| self.insert_raw(col, key, value))
     }
 
     /// TODO(doc): @quake
@@ -408,6 +373,51 @@ impl StoreTransaction {
     }
 }
 
+/// Writes the header, uncles, extension, number index, proposals and body columns of a block
+/// through `put`, shared by `StoreTransaction::insert_block` and `StoreWriteBatch::insert_blocks`
+pub(crate) fn put_block<F>(block: &BlockView, mut put: F) -> Result<(), Error>
+where
+    F: FnMut(Col, &[u8], &[u8]) -> Result<(), Error>,
+{
+    let hash = block.hash();
+    let header = block.header().pack();
+    let uncles = block.uncles().pack();
+    let proposals = block.data().proposals();
+    let txs_len: packed::Uint32 = (block.transactions().len() as u32).pack();
+    put(COLUMN_BLOCK_HEADER, hash.as_slice(), header.as_slice())?;
+    put(COLUMN_BLOCK_UNCLE, hash.as_slice(), uncles.as_slice())?;
+    if let Some(extension) = block.extension() {
+        put(
+            COLUMN_BLOCK_EXTENSION,
+            hash.as_slice(),
+            extension.as_slice(),
+        )?;
+    }
+    put(
+        COLUMN_NUMBER_HASH,
+        packed::NumberHash::new_builder()
+            .number(block.number().pack())
+            .block_hash(hash.clone())
+            .build()
+            .as_slice(),
+        txs_len.as_slice(),
+    )?;
+    put(
+        COLUMN_BLOCK_PROPOSAL_IDS,
+        hash.as_slice(),
+        proposals.as_slice(),
+    )?;
+    for (index, tx) in block.transactions().into_iter().enumerate() {
+        let key = packed::TransactionKey::new_builder()
+            .block_hash(hash.clone())
+            .index(index.pack())
+            .build();
+        let tx_data = tx.pack();
+        put(COLUMN_BLOCK_BODY, key.as_slice(), tx_data.as_slice())?;
+    }
+    Ok(())
+}
+
 impl MMRStore<packed::HeaderDigest> for &StoreTransaction {
     fn get_elem(&self, pos: u64) -> MMRResult<Option<packed::HeaderDigest>> {
         Ok(self.get_header_digest(pos))
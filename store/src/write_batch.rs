@@ -1,14 +1,22 @@
+use crate::transaction::put_block;
 use ckb_db::RocksDBWriteBatch;
 use ckb_db_schema::{
     Col, COLUMN_BLOCK_BODY, COLUMN_BLOCK_EXTENSION, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
     COLUMN_BLOCK_UNCLE, COLUMN_CELL, COLUMN_CELL_DATA, COLUMN_CELL_DATA_HASH, COLUMN_NUMBER_HASH,
 };
 use ckb_error::Error;
-use ckb_types::{core::BlockNumber, packed, prelude::*};
+use ckb_types::{
+    core::{BlockNumber, BlockView},
+    packed,
+    prelude::*,
+};
 
 /// Wrapper of `RocksDBWriteBatch`, provides atomic batch of write operations.
 pub struct StoreWriteBatch {
     pub(crate) inner: RocksDBWriteBatch,
+    // zero means unlimited
+    pub(crate) max_bytes: usize,
+    pub(crate) auto_commits: usize,
 }
 
 impl StoreWriteBatch {
@@ -42,6 +50,35 @@ impl StoreWriteBatch {
         self.inner.clear()
     }
 
+    /// Commit the updates buffered in this batch and clear it for reuse.
+    ///
+    /// It's equivalent to `ChainDB::write` followed by `clear`. Each commit is applied atomically,
+    /// and the updates of a commit are visible to all the reads started after it returns, so
+    /// chunks committed one by one through the same batch land in order. If the commit fails, the
+    /// updates stay buffered and nothing is written.
+    pub fn commit_and_clear(&mut self) -> Result<(), Error> {
+        self.inner.commit_and_clear()
+    }
+
+    /// Returns how many times `insert_cells` and `insert_blocks` committed this batch on their own
+    ///
+    /// With `max_batch_bytes` set in the store config, an insert that grows the batch beyond that
+    /// size commits it right after the cell or block that crossed the limit, so a bulk import
+    /// writes in bounded chunks rather than in one huge batch. A cell or block is never split
+    /// across commits, but the inserts are no longer atomic as a whole. Without the limit the
+    /// batch only grows, and this stays zero.
+    pub fn auto_commits(&self) -> usize {
+        self.auto_commits
+    }
+
+    fn commit_if_oversized(&mut self) -> Result<(), Error> {
+        if self.max_bytes > 0 && self.size_in_bytes() > self.max_bytes {
+            self.commit_and_clear()?;
+            self.auto_commits += 1;
+        }
+        Ok(())
+    }
+
     /// Put cells into this write batch
     pub fn insert_cells(
         &mut self,
@@ -67,6 +104,7 @@ impl StoreWriteBatch {
                 self.put(COLUMN_CELL_DATA, &key, &[])?;
                 self.put(COLUMN_CELL_DATA_HASH, &key, &[])?;
             }
+            self.commit_if_oversized()?;
         }
         Ok(())
     }
@@ -86,6 +124,20 @@ impl StoreWriteBatch {
         Ok(())
     }
 
+    /// Put a block into this write batch, the same entries as `StoreTransaction::insert_block`
+    pub fn insert_block(&mut self, block: &BlockView) -> Result<(), Error> {
+        self.insert_blocks(std::slice::from_ref(block))
+    }
+
+    /// Put blocks into this write batch, equivalent to `insert_block` for each of them
+    pub fn insert_blocks(&mut self, blocks: &[BlockView]) -> Result<(), Error> {
+        for block in blocks {
+            put_block(block, |col, key, value| self.put(col, key, value))?;
+            self.commit_if_oversized()?;
+        }
+        Ok(())
+    }
+
     /// Removes the block body from database with corresponding hash, number and txs number
     pub fn delete_block_body(
         &mut self,
@@ -1661,8 +1661,7 @@ impl ActiveChain {
 
     pub fn get_latest_built_filter_block_number(&self) -> BlockNumber {
         self.snapshot
-            .get_latest_built_filter_data_block_hash()
-            .and_then(|hash| self.snapshot.get_block_number(&hash))
+            .get_latest_built_filter_data_block_number()
             .unwrap_or_default()
     }
 
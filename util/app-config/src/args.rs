@@ -1,10 +1,11 @@
-use crate::{CKBAppConfig, MemoryTrackerConfig, MinerConfig};
+use crate::{cli, CKBAppConfig, MemoryTrackerConfig, MinerConfig};
 use ckb_chain_spec::consensus::Consensus;
 use ckb_jsonrpc_types::ScriptHashType;
 use ckb_pow::PowEngine;
 use ckb_systemtime::unix_time_as_millis;
 use ckb_types::packed::Byte32;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
 /// Parsed command line arguments for `ckb export`.
@@ -127,6 +128,10 @@ pub struct InitArgs {
     pub list_chains: bool,
     /// Force file overwriting.
     pub force: bool,
+    /// Print the generated files to stdout without writing anything.
+    pub dry_run: bool,
+    /// Remove a database of another chain left in the data directory without prompting.
+    pub assume_yes: bool,
     /// Block assembler lock script code hash.
     pub block_assembler_code_hash: Option<String>,
     /// Block assembler lock script args.
@@ -135,10 +140,15 @@ pub struct InitArgs {
     pub block_assembler_hash_type: ScriptHashType,
     /// Block assembler cellbase transaction message.
     pub block_assembler_message: Option<String>,
+    /// Generate a new secp256k1 key as the block assembler lock.
+    pub block_assembler_generate: bool,
+    /// Alternate block assembler locks, see `BlockAssemblerProfile`.
+    pub block_assembler_profiles: Vec<BlockAssemblerProfile>,
     /// Import the spec file.
     ///
     /// When this is set to `-`, the spec file is imported from stdin and the file content must be
-    /// encoded by base64. Otherwise it must be a path to the spec file.
+    /// encoded by base64. When it is an `http://` or `https://` URL, the spec file is downloaded,
+    /// and decoded if the body is base64 encoded. Otherwise it must be a path to the spec file.
     ///
     /// The spec file will be saved into `specs/{CHAIN}.toml`, where `CHAIN` is the chain name.
     pub import_spec: Option<String>,
@@ -148,6 +158,69 @@ pub struct InitArgs {
     pub customize_spec: CustomizeSpec,
 }
 
+/// A named block assembler lock given by `ckb init --ba-profile`.
+///
+/// Every profile is written to `ckb.toml` as a labeled `[block_assembler]` section, all but the
+/// active one commented out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockAssemblerProfile {
+    /// The label of the profile.
+    pub name: String,
+    /// Lock script code hash.
+    pub code_hash: String,
+    /// Lock script args.
+    pub args: Vec<String>,
+    /// Lock script hash type.
+    pub hash_type: ScriptHashType,
+    /// Whether the profile is marked as the active one.
+    pub active: bool,
+}
+
+impl FromStr for BlockAssemblerProfile {
+    type Err = String;
+
+    /// Parses `name:code_hash:args:hash_type`, optionally followed by `:active`.
+    ///
+    /// Multiple args are separated by commas. The code hash and args are validated like
+    /// `--ba-code-hash` and `--ba-arg`.
+    fn from_str(profile: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = profile.split(':').collect();
+        let (name, code_hash, args, hash_type, active) = match fields[..] {
+            [name, code_hash, args, hash_type] => (name, code_hash, args, hash_type, false),
+            [name, code_hash, args, hash_type, "active"] => {
+                (name, code_hash, args, hash_type, true)
+            }
+            _ => {
+                return Err(
+                    "Must be name:code_hash:args:hash_type, optionally followed by :active"
+                        .to_owned(),
+                )
+            }
+        };
+        if name.is_empty() {
+            return Err("The profile name must not be empty".to_owned());
+        }
+        let code_hash = cli::is_h256(code_hash).map_err(|err| format!("code_hash: {err}"))?;
+        let args = args
+            .split(',')
+            .map(|arg| cli::is_hex(arg).map_err(|err| format!("args: {err}")))
+            .collect::<Result<_, _>>()?;
+        let hash_type = match hash_type {
+            "data" | "type" | "data1" => {
+                serde_plain::from_str(hash_type).map_err(|err| format!("hash_type: {err}"))?
+            }
+            _ => return Err("hash_type must be one of data, type and data1".to_owned()),
+        };
+        Ok(BlockAssemblerProfile {
+            name: name.to_owned(),
+            code_hash,
+            args,
+            hash_type,
+            active,
+        })
+    }
+}
+
 /// Customize parameters for chain spec.
 pub struct CustomizeSpec {
     /// Specify a string as the genesis message.
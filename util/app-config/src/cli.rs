@@ -1,7 +1,9 @@
 //! CKB command line arguments parser.
+use crate::BlockAssemblerProfile;
 use ckb_build_info::Version;
 use ckb_resource::{AVAILABLE_SPECS, DEFAULT_P2P_PORT, DEFAULT_RPC_PORT, DEFAULT_SPEC};
 use clap::{Arg, ArgGroup, ArgMatches, Command};
+use std::str::FromStr;
 
 /// binary file name(ckb)
 pub const BIN_NAME: &str = "ckb";
@@ -60,6 +62,10 @@ pub const ARG_P2P_PORT: &str = "p2p-port";
 pub const ARG_RPC_PORT: &str = "rpc-port";
 /// Command line argument `--force`.
 pub const ARG_FORCE: &str = "force";
+/// Command line argument `--dry-run`.
+pub const ARG_DRY_RUN: &str = "dry-run";
+/// Command line argument `--yes`.
+pub const ARG_YES: &str = "yes";
 /// Command line argument `--include-background`.
 pub const ARG_INCLUDE_BACKGROUND: &str = "include-background";
 /// Command line argument `--log-to`.
@@ -74,6 +80,10 @@ pub const ARG_BA_ARG: &str = "ba-arg";
 pub const ARG_BA_HASH_TYPE: &str = "ba-hash-type";
 /// Command line argument `--ba-message`.
 pub const ARG_BA_MESSAGE: &str = "ba-message";
+/// Command line argument `--ba-generate`.
+pub const ARG_BA_GENERATE: &str = "ba-generate";
+/// Command line argument `--ba-profile`.
+pub const ARG_BA_PROFILE: &str = "ba-profile";
 /// Command line argument `--ba-advanced`.
 pub const ARG_BA_ADVANCED: &str = "ba-advanced";
 /// Command line argument `--daemon`
@@ -507,7 +517,8 @@ fn init() -> Command {
                 .action(clap::ArgAction::Set)
                 .help(
                     "Use the specified file as the chain spec. Specially, \
-                     The dash \"-\" denotes importing the spec from stdin encoded in base64",
+                     The dash \"-\" denotes importing the spec from stdin encoded in base64, \
+                     and an http(s) URL downloads the spec",
                 ),
         )
         .arg(
@@ -524,6 +535,23 @@ fn init() -> Command {
                 .action(clap::ArgAction::SetTrue)
                 .help("Enforce overwriting existing files"),
         )
+        .arg(
+            Arg::new(ARG_DRY_RUN)
+                .long(ARG_DRY_RUN)
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with(ARG_IMPORT_SPEC)
+                .help("Print the generated config files to stdout instead of writing them"),
+        )
+        .arg(
+            Arg::new(ARG_YES)
+                .short('y')
+                .long(ARG_YES)
+                .action(clap::ArgAction::SetTrue)
+                .help(
+                    "Remove a database of another chain left in the data directory \
+                     without prompting",
+                ),
+        )
         .arg(
             Arg::new(ARG_RPC_PORT)
                 .long(ARG_RPC_PORT)
@@ -564,9 +592,37 @@ fn init() -> Command {
                 .default_value("type")
                 .help("Set hash type in [block_assembler]"),
         )
+        .arg(
+            Arg::new(ARG_BA_GENERATE)
+                .long(ARG_BA_GENERATE)
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([ARG_BA_CODE_HASH, ARG_BA_ARG, ARG_BA_HASH_TYPE])
+                .help(
+                    "Generate a new secp256k1 key for [block_assembler] \
+                     and print the private key to stdout",
+                ),
+        )
+        .arg(
+            Arg::new(ARG_BA_PROFILE)
+                .long(ARG_BA_PROFILE)
+                .value_name("name:code_hash:args:hash_type[:active]")
+                .action(clap::ArgAction::Append)
+                .value_parser(BlockAssemblerProfile::from_str)
+                .conflicts_with_all([ARG_BA_CODE_HASH, ARG_BA_ARG, ARG_BA_GENERATE])
+                .help(
+                    "Add a labeled [block_assembler] profile, multiple args are separated by \
+                     commas. All profiles are written to the config file, only the one marked \
+                     active or else the first one is enabled",
+                ),
+        )
         .group(
             ArgGroup::new(GROUP_BA)
-                .args([ARG_BA_CODE_HASH, ARG_BA_ARG])
+                .args([
+                    ARG_BA_CODE_HASH,
+                    ARG_BA_ARG,
+                    ARG_BA_GENERATE,
+                    ARG_BA_PROFILE,
+                ])
                 .multiple(true),
         )
         .arg(
@@ -627,7 +683,8 @@ fn peer_id() -> Command {
         )
 }
 
-fn is_hex(hex: &str) -> Result<String, String> {
+/// Validates a 0x-prefixed hexadecimal string, as accepted by `--ba-arg` and `--ba-message`
+pub fn is_hex(hex: &str) -> Result<String, String> {
     let tmp = hex.as_bytes();
     if tmp.len() < 2 {
         Err("Must be a 0x-prefixed hexadecimal string".to_string())
@@ -649,7 +706,8 @@ fn is_hex(hex: &str) -> Result<String, String> {
     }
 }
 
-fn is_h256(hex: &str) -> Result<String, String> {
+/// Validates a 0x-prefixed 32 bytes hexadecimal string, as accepted by `--ba-code-hash`
+pub fn is_h256(hex: &str) -> Result<String, String> {
     if hex.len() != 66 {
         Err("Must be 0x-prefixed hexadecimal string and string length is 66".to_owned())
     } else {
@@ -14,6 +14,17 @@ pub struct Config {
     /// The capacity of RocksDB cache, which caches uncompressed data blocks, indexes and filters, default is 128MB
     #[serde(default)]
     pub cache_size: Option<usize>,
+    /// The size of the memtable of each column family, overrides the options file when set.
+    ///
+    /// Must be greater than 0.
+    #[serde(default)]
+    pub write_buffer_size: Option<usize>,
+    /// The maximum number of concurrent RocksDB flush and compaction jobs, overrides the options
+    /// file when set.
+    ///
+    /// Must be greater than 0.
+    #[serde(default)]
+    pub max_background_jobs: Option<i32>,
     /// Provide RocksDB options.
     ///
     /// More details can be found in [the official tuning guide](https://github.com/facebook/rocksdb/wiki/RocksDB-Tuning-Guide).
@@ -16,6 +16,17 @@ pub struct Config {
     pub block_uncles_cache_size: usize,
     /// The maximum number of blocks which extension section is cached.
     pub block_extensions_cache_size: usize,
+    /// The maximum number of blocks which transactions are cached, zero disables the cache.
+    pub body_cache_size: usize,
     /// whether enable freezer
     pub freezer_enable: bool,
+    /// Whether to recompute the hashes of headers and transactions read from the database and
+    /// panic on a mismatch with the stored hash
+    pub verify_on_read: bool,
+    /// Whether attaching the cells of a block puts their data into the cell data caches, so
+    /// reading just attached cells during a bulk import hits the cache
+    pub prime_cache_on_attach: bool,
+    /// The serialized size in bytes above which a write batch commits the inserted blocks or
+    /// cells on its own and starts over, zero means unlimited
+    pub max_batch_bytes: usize,
 }
@@ -11,8 +11,16 @@ pub(crate) struct StoreConfig {
     pub(crate) cellbase_cache_size: Option<usize>,
     #[serde(default = "default_block_extensions_cache_size")]
     block_extensions_cache_size: usize,
+    #[serde(default)]
+    body_cache_size: usize,
     #[serde(default = "default_freezer_enable")]
     freezer_enable: bool,
+    #[serde(default)]
+    verify_on_read: bool,
+    #[serde(default)]
+    prime_cache_on_attach: bool,
+    #[serde(default)]
+    max_batch_bytes: usize,
 }
 
 const fn default_block_extensions_cache_size() -> usize {
@@ -39,7 +47,11 @@ impl Default for StoreConfig {
             block_uncles_cache_size: 30,
             cellbase_cache_size: None,
             block_extensions_cache_size: default_block_extensions_cache_size(),
+            body_cache_size: 0,
             freezer_enable: default_freezer_enable(),
+            verify_on_read: false,
+            prime_cache_on_attach: false,
+            max_batch_bytes: 0,
         }
     }
 }
@@ -54,7 +66,11 @@ impl From<StoreConfig> for crate::StoreConfig {
             block_uncles_cache_size,
             cellbase_cache_size: _,
             block_extensions_cache_size,
+            body_cache_size,
             freezer_enable,
+            verify_on_read,
+            prime_cache_on_attach,
+            max_batch_bytes,
         } = input;
         Self {
             header_cache_size,
@@ -63,7 +79,11 @@ impl From<StoreConfig> for crate::StoreConfig {
             block_tx_hashes_cache_size,
             block_uncles_cache_size,
             block_extensions_cache_size,
+            body_cache_size,
             freezer_enable,
+            verify_on_read,
+            prime_cache_on_attach,
+            max_batch_bytes,
         }
     }
 }
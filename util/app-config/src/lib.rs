@@ -15,8 +15,8 @@ pub use app_config::{
     AppConfig, CKBAppConfig, ChainConfig, LogConfig, MetricsConfig, MinerAppConfig,
 };
 pub use args::{
-    DaemonArgs, ExportArgs, ImportArgs, InitArgs, MigrateArgs, MinerArgs, PeerIDArgs, ReplayArgs,
-    ResetDataArgs, RunArgs, StatsArgs,
+    BlockAssemblerProfile, DaemonArgs, ExportArgs, ImportArgs, InitArgs, MigrateArgs, MinerArgs,
+    PeerIDArgs, ReplayArgs, ResetDataArgs, RunArgs, StatsArgs,
 };
 use ckb_logger::info;
 pub use configs::*;
@@ -280,6 +280,7 @@ impl Setup {
             matches.get_flag(cli::ARG_LIST_CHAINS) || matches.contains_id("list-specs");
         let interactive = matches.get_flag(cli::ARG_INTERACTIVE);
         let force = matches.get_flag(cli::ARG_FORCE);
+        let dry_run = matches.get_flag(cli::ARG_DRY_RUN);
         let chain = if !matches.contains_id("spec") {
             matches
                 .get_one::<String>(cli::ARG_CHAIN)
@@ -317,6 +318,13 @@ impl Setup {
             .and_then(|hash_type| serde_plain::from_str::<ScriptHashType>(hash_type).ok())
             .expect("has default value");
         let block_assembler_message = matches.get_one::<String>(cli::ARG_BA_MESSAGE).cloned();
+        let block_assembler_generate = matches.get_flag(cli::ARG_BA_GENERATE);
+        let block_assembler_profiles = matches
+            .get_many::<BlockAssemblerProfile>(cli::ARG_BA_PROFILE)
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        let assume_yes = matches.get_flag(cli::ARG_YES);
 
         let import_spec = matches.get_one::<String>(cli::ARG_IMPORT_SPEC).cloned();
 
@@ -333,12 +341,16 @@ impl Setup {
             p2p_port,
             list_chains,
             force,
+            dry_run,
+            assume_yes,
             log_to_file,
             log_to_stdout,
             block_assembler_code_hash,
             block_assembler_args,
             block_assembler_hash_type,
             block_assembler_message,
+            block_assembler_generate,
+            block_assembler_profiles,
             import_spec,
             customize_spec,
         })
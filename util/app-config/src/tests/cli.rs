@@ -139,3 +139,65 @@ fn h256_as_validator() {
     let err = err_matches.err().unwrap();
     assert_eq!(clap::error::ErrorKind::ValueValidation, err.kind());
 }
+
+#[test]
+fn ba_profile() {
+    let code_hash = "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8";
+    let matches = basic_app()
+        .try_get_matches_from([
+            BIN_NAME,
+            "init",
+            "--ba-profile",
+            &format!("solo:{code_hash}:0x00:type"),
+            "--ba-profile",
+            &format!("pool:{code_hash}:0x01,0x02:data1:active"),
+            "--ba-message",
+            "0x00",
+        ])
+        .unwrap();
+    let (_, init) = matches.subcommand().unwrap();
+    let profiles: Vec<_> = init
+        .get_many::<crate::BlockAssemblerProfile>(ARG_BA_PROFILE)
+        .unwrap()
+        .collect();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].name, "solo");
+    assert_eq!(profiles[0].args, vec!["0x00".to_owned()]);
+    assert!(!profiles[0].active);
+    assert_eq!(profiles[1].name, "pool");
+    assert_eq!(profiles[1].code_hash, code_hash);
+    assert_eq!(profiles[1].args, vec!["0x01".to_owned(), "0x02".to_owned()]);
+    assert_eq!(
+        profiles[1].hash_type,
+        ckb_jsonrpc_types::ScriptHashType::Data1
+    );
+    assert!(profiles[1].active);
+
+    for invalid in [
+        format!("solo:{code_hash}:0x00"),
+        format!(":{code_hash}:0x00:type"),
+        format!("solo:{}:0x00:type", &code_hash[..64]),
+        format!("solo:{code_hash}:0x0:type"),
+        format!("solo:{code_hash}:0x00:data2"),
+        format!("solo:{code_hash}:0x00:type:default"),
+    ] {
+        let err = basic_app()
+            .try_get_matches_from([BIN_NAME, "init", "--ba-profile", &invalid])
+            .err()
+            .unwrap();
+        assert_eq!(clap::error::ErrorKind::ValueValidation, err.kind());
+    }
+
+    let err = basic_app()
+        .try_get_matches_from([
+            BIN_NAME,
+            "init",
+            "--ba-profile",
+            &format!("solo:{code_hash}:0x00:type"),
+            "--ba-arg",
+            "0x00",
+        ])
+        .err()
+        .unwrap();
+    assert_eq!(clap::error::ErrorKind::ArgumentConflict, err.kind());
+}
@@ -14,7 +14,7 @@ impl<'a, S: ChainStore> ChainIterator<'a, S> {
     /// TODO(doc): @quake
     pub fn new(store: &'a S) -> Self {
         let current = store.get_block_hash(0).and_then(|h| store.get_block(&h));
-        let tip = store.get_tip_header().expect("store inited").number();
+        let tip = store.tip_number().expect("store inited");
         ChainIterator {
             store,
             current,
@@ -87,6 +87,24 @@ impl packed::OutPoint {
         key.extend_from_slice(&index.to_be_bytes());
         key
     }
+
+    /// Parses a key generated by [`Self::to_cell_key()`](#method.to_cell_key) back into an
+    /// `OutPoint`.
+    ///
+    /// Returns `None` if `key` is not exactly 36 bytes long.
+    pub fn from_cell_key(key: &[u8]) -> Option<Self> {
+        if key.len() != 36 {
+            return None;
+        }
+        let mut tx_hash = [0u8; 32];
+        tx_hash.copy_from_slice(&key[..32]);
+        let mut index = [0u8; 4];
+        index.copy_from_slice(&key[32..]);
+        Some(Self::new(
+            packed::Byte32::new(tx_hash),
+            u32::from_be_bytes(index),
+        ))
+    }
 }
 
 impl packed::CellInput {
@@ -11,3 +11,6 @@ mod serialized_size;
 
 #[cfg(feature = "std")]
 mod rust_core_traits;
+
+#[cfg(feature = "std")]
+mod shortcut;
@@ -0,0 +1,33 @@
+use crate::{packed, prelude::*};
+use ckb_hash::blake2b_256;
+
+#[test]
+fn cell_key_round_trip() {
+    let indices = [0u32, 1, 0xff, 0x100, 0x1234_5678, u32::max_value()];
+    for seed in 0u32..16 {
+        let tx_hash = blake2b_256(seed.to_le_bytes()).pack();
+        for index in indices {
+            let out_point = packed::OutPoint::new(tx_hash.clone(), index);
+            let key = out_point.to_cell_key();
+            let decoded = packed::OutPoint::from_cell_key(&key).expect("decode cell key");
+            assert_eq!(decoded, out_point);
+            assert_eq!(decoded.to_cell_key(), key);
+        }
+    }
+}
+
+#[test]
+fn cell_key_keeps_index_order() {
+    let tx_hash = blake2b_256(b"cell key order").pack();
+    let first = packed::OutPoint::new(tx_hash.clone(), 0xff).to_cell_key();
+    let second = packed::OutPoint::new(tx_hash, 0x100).to_cell_key();
+    assert!(first < second);
+}
+
+#[test]
+fn cell_key_rejects_wrong_length() {
+    let key = packed::OutPoint::new(packed::Byte32::max_value(), 1).to_cell_key();
+    assert!(packed::OutPoint::from_cell_key(&key[..35]).is_none());
+    assert!(packed::OutPoint::from_cell_key(&[key.as_slice(), &[0]].concat()).is_none());
+    assert!(packed::OutPoint::from_cell_key(&[]).is_none());
+}
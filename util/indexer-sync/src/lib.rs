@@ -295,8 +295,7 @@ impl IndexerSyncService {
     }
 
     fn get_block_by_number(&self, block_number: u64) -> Option<core::BlockView> {
-        let block_hash = self.secondary_db.get_block_hash(block_number)?;
-        self.secondary_db.get_block(&block_hash)
+        self.secondary_db.get_block_by_number(block_number)
     }
 }
 
@@ -1,7 +1,7 @@
 use crate::error::Error;
 
 use ckb_db_schema::Col;
-use ckb_store::{ChainStore, Freezer, StoreCache};
+use ckb_store::{ChainStore, Freezer, StoreCache, StoreError};
 use rocksdb::{
     ops::OpenCF, prelude::*, ColumnFamilyDescriptor, DBIterator, DBPinnableSlice, IteratorMode,
     SecondaryDB as SecondaryRocksDB, SecondaryOpenDescriptor,
@@ -112,9 +112,9 @@ impl ChainStore for SecondaryDB {
         None
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError> {
         self.get_pinned(col, key)
-            .expect("db operation should be ok")
+            .map_err(|err| StoreError::io(col, err))
     }
 
     fn get_iter(&self, col: Col, mode: IteratorMode) -> DBIterator {
@@ -1,18 +1,14 @@
 use ckb_shared::Snapshot;
-use ckb_store::ChainStore;
+pub(crate) use ckb_store::ChainStore;
 use ckb_types::{
-    core::{BlockExt, BlockNumber, BlockView, HeaderView},
+    core::{BlockExt, BlockNumber},
     packed,
     prelude::*,
     utilities::compact_to_difficulty,
     U256,
 };
 
-pub(crate) trait SnapshotExt {
-    fn get_header_by_number(&self, num: BlockNumber) -> Option<HeaderView>;
-
-    fn get_block_by_number(&self, num: BlockNumber) -> Option<BlockView>;
-
+pub(crate) trait SnapshotExt: ChainStore {
     fn get_block_ext_by_number(&self, num: BlockNumber) -> Option<BlockExt>;
 
     fn get_verifiable_header_by_number(&self, num: BlockNumber)
@@ -41,16 +37,6 @@ pub(crate) trait SnapshotExt {
 }
 
 impl SnapshotExt for Snapshot {
-    fn get_header_by_number(&self, num: BlockNumber) -> Option<HeaderView> {
-        self.get_block_hash(num)
-            .and_then(|hash| self.get_block_header(&hash))
-    }
-
-    fn get_block_by_number(&self, num: BlockNumber) -> Option<BlockView> {
-        self.get_block_hash(num)
-            .and_then(|hash| self.get_block(&hash))
-    }
-
     fn get_block_ext_by_number(&self, num: BlockNumber) -> Option<BlockExt> {
         self.get_block_hash(num)
             .and_then(|hash| self.get_block_ext(&hash))
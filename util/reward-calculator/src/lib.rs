@@ -7,7 +7,7 @@ use ckb_logger::debug;
 use ckb_store::ChainStore;
 use ckb_types::{
     core::{BlockReward, Capacity, CapacityResult, HeaderView},
-    packed::{Byte32, CellbaseWitness, ProposalShortId, Script},
+    packed::{Byte32, CellOutput, CellbaseWitness, ProposalShortId, Script},
     prelude::*,
 };
 use std::cmp;
@@ -74,6 +74,45 @@ impl<'a, CS: ChainStore> RewardCalculator<'a, CS> {
         self.block_reward_internal(target, &parent)
     }
 
+    /// Returns the total block reward of the main chain block `block_hash` and its components.
+    ///
+    /// The reward of block(i) is issued by the cellbase of block(i + finalization_delay_length),
+    /// so it can only be calculated once block(i + finalization_delay_length - 1) is in the main
+    /// chain. The cellbases of the blocks up to the finalization delay have no output, so the
+    /// genesis block is never rewarded.
+    ///
+    /// Returns `None` if the block is unknown, not in the main chain, the main chain is not long
+    /// enough yet to calculate its reward, or the reward is not paid: for the genesis block, and
+    /// when the reward is too small to create the cellbase output.
+    pub fn get_block_reward(&self, block_hash: &Byte32) -> Result<Option<BlockReward>, DaoError> {
+        if !self.store.is_main_chain(block_hash) {
+            return Ok(None);
+        }
+        let target = match self.store.get_block_header(block_hash) {
+            Some(target) if target.number() > 0 => target,
+            _ => return Ok(None),
+        };
+        let finalization_parent_number =
+            target.number() + self.consensus.finalization_delay_length() - 1;
+        let parent = match self
+            .store
+            .get_block_hash(finalization_parent_number)
+            .and_then(|hash| self.store.get_block_header(&hash))
+        {
+            Some(parent) => parent,
+            None => return Ok(None),
+        };
+        let (target_lock, block_reward) = self.block_reward_internal(&target, &parent)?;
+        let output = CellOutput::new_builder()
+            .capacity(block_reward.total.pack())
+            .lock(target_lock)
+            .build();
+        if output.is_lack_of_capacity(Capacity::zero())? {
+            return Ok(None);
+        }
+        Ok(Some(block_reward))
+    }
+
     /// Calculates the block reward and returns the reward distribution as well as the lock script
     /// for the target block. Returns a `DaoError` if the calculation fails for any reason.
     ///
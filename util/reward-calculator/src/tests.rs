@@ -1,11 +1,16 @@
-use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder, ProposalWindow};
+use ckb_chain_spec::consensus::{
+    build_genesis_epoch_ext, Consensus, ConsensusBuilder, ProposalWindow,
+};
 use ckb_db::RocksDB;
 use ckb_db_schema::COLUMNS;
 use ckb_occupied_capacity::IntoCapacity;
 use ckb_store::{ChainDB, ChainStore};
 use ckb_types::{
-    core::{BlockBuilder, BlockExt, EpochNumberWithFraction, HeaderBuilder, TransactionBuilder},
-    packed::ProposalShortId,
+    core::{
+        BlockBuilder, BlockExt, Capacity, EpochNumberWithFraction, HeaderBuilder, HeaderView,
+        TransactionBuilder,
+    },
+    packed::{Byte32, CellInput, ProposalShortId, Script},
     prelude::*,
 };
 use std::collections::HashSet;
@@ -321,3 +326,119 @@ fn test_proposal_reward() {
 
     assert_eq!(proposal_reward, expected.into_capacity());
 }
+
+// Attaches a child of `parent` in the genesis epoch, with a cellbase paying to the default lock
+fn attach_child(store: &ChainDB, consensus: &Consensus, parent: &HeaderView) -> HeaderView {
+    let number = parent.number() + 1;
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new_cellbase_input(number))
+        .witness(Script::default().into_witness())
+        .build();
+    let block = BlockBuilder::default()
+        .parent_hash(parent.hash())
+        .number(number.pack())
+        .transaction(cellbase)
+        .build();
+    let ext = BlockExt {
+        received_at: block.timestamp(),
+        total_difficulty: block.difficulty(),
+        total_uncles_count: 0,
+        verified: Some(true),
+        txs_fees: vec![],
+        cycles: None,
+        txs_sizes: None,
+    };
+    let epoch_hash = consensus
+        .genesis_epoch_ext()
+        .last_block_hash_in_previous_epoch();
+    let txn = store.begin_transaction();
+    txn.insert_block(&block).unwrap();
+    txn.insert_block_ext(&block.hash(), &ext).unwrap();
+    txn.insert_block_epoch_index(&block.hash(), &epoch_hash)
+        .unwrap();
+    txn.attach_block(&block).unwrap();
+    txn.commit().unwrap();
+    block.header()
+}
+
+#[test]
+fn test_get_block_reward() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let consensus = ConsensusBuilder::default().build();
+    store.init(&consensus).unwrap();
+    let reward_calculator = RewardCalculator::new(&consensus, &store);
+
+    // the reward of block 1 needs block(finalization_delay_length)
+    let mut headers = vec![consensus.genesis_block().header()];
+    for _ in 1..consensus.finalization_delay_length() {
+        headers.push(attach_child(&store, &consensus, headers.last().unwrap()));
+    }
+    assert!(matches!(
+        reward_calculator.get_block_reward(&headers[1].hash()),
+        Ok(None)
+    ));
+    headers.push(attach_child(&store, &consensus, headers.last().unwrap()));
+
+    let reward = reward_calculator
+        .get_block_reward(&headers[1].hash())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reward.primary,
+        consensus.genesis_epoch_ext().block_reward(1).unwrap()
+    );
+    assert_eq!(reward.tx_fee, Capacity::zero());
+    assert_eq!(reward.proposal_reward, Capacity::zero());
+    assert_eq!(
+        reward.total,
+        reward.primary.safe_add(reward.secondary).unwrap()
+    );
+    // the genesis block is never rewarded
+    assert!(matches!(
+        reward_calculator.get_block_reward(&headers[0].hash()),
+        Ok(None)
+    ));
+    // block 2 is finalized by a block not built yet
+    assert!(matches!(
+        reward_calculator.get_block_reward(&headers[2].hash()),
+        Ok(None)
+    ));
+    assert!(matches!(
+        reward_calculator.get_block_reward(&Byte32::new([1u8; 32])),
+        Ok(None)
+    ));
+}
+
+#[test]
+fn test_get_block_reward_too_small_to_create_cell() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db = RocksDB::open_in(&tmp_dir, COLUMNS);
+    let store = ChainDB::new(db, Default::default());
+    let default_consensus = ConsensusBuilder::default().build();
+    // a primary reward of one shannon per block and no secondary reward
+    let genesis_epoch_length = default_consensus.genesis_epoch_ext().length();
+    let epoch_ext = build_genesis_epoch_ext(
+        Capacity::shannons(genesis_epoch_length),
+        default_consensus.genesis_block().compact_target(),
+        genesis_epoch_length,
+        default_consensus.epoch_duration_target(),
+        (1, 40),
+    );
+    let consensus = ConsensusBuilder::new(default_consensus.genesis_block().clone(), epoch_ext)
+        .initial_primary_epoch_reward(Capacity::shannons(genesis_epoch_length))
+        .secondary_epoch_reward(Capacity::zero())
+        .build();
+    store.init(&consensus).unwrap();
+
+    let mut headers = vec![consensus.genesis_block().header()];
+    for _ in 0..consensus.finalization_delay_length() {
+        headers.push(attach_child(&store, &consensus, headers.last().unwrap()));
+    }
+    let reward_calculator = RewardCalculator::new(&consensus, &store);
+    assert!(matches!(
+        reward_calculator.get_block_reward(&headers[1].hash()),
+        Ok(None)
+    ));
+}
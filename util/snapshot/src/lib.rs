@@ -15,7 +15,7 @@ use ckb_merkle_mountain_range::{
     leaf_index_to_mmr_size, Error as MMRError, MMRStore, Result as MMRResult,
 };
 use ckb_proposal_table::ProposalView;
-use ckb_store::{ChainStore, StoreCache, StoreSnapshot};
+use ckb_store::{ChainStore, StoreCache, StoreError, StoreSnapshot};
 use ckb_traits::{HeaderFields, HeaderFieldsProvider, HeaderProvider};
 use ckb_types::core::error::OutPointError;
 use ckb_types::{
@@ -189,8 +189,8 @@ impl ChainStore for Snapshot {
         self.store.cache()
     }
 
-    fn get(&self, col: Col, key: &[u8]) -> Option<DBPinnableSlice> {
-        self.store.get(col, key)
+    fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>, StoreError> {
+        self.store.try_get(col, key)
     }
 
     fn freezer(&self) -> Option<&Freezer> {
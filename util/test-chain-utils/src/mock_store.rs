@@ -42,10 +42,7 @@ impl MockStore {
     #[doc(hidden)]
     pub fn new(parent: &HeaderView, chain_store: &ChainDB) -> Self {
         let block = chain_store.get_block(&parent.hash()).unwrap();
-        let epoch_ext = chain_store
-            .get_block_epoch_index(&parent.hash())
-            .and_then(|index| chain_store.get_epoch_ext(&index))
-            .unwrap();
+        let epoch_ext = chain_store.get_block_epoch(&parent.hash()).unwrap();
         let parent_block_ext = BlockExt {
             received_at: unix_time_as_millis(),
             total_difficulty: Default::default(),